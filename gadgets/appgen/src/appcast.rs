@@ -0,0 +1,308 @@
+//! Sparkle appcast (`appcast.xml`) generation from a directory of release archives.
+//!
+//! Every `.zip`, `.tar.gz` or `.tgz` archive in the directory is opened to find the
+//! bundle's Info.plist, which provides `sparkle:version` (CFBundleVersion) and
+//! `sparkle:shortVersionString`. Archives are signed with an ed25519 key so that Sparkle
+//! can check them against `SUPublicEDKey`. Items already present in an existing appcast
+//! are kept unless an archive with the same version replaces them.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ed25519_dalek::{Signer, SigningKey};
+use flate2::read::GzDecoder;
+use quick_xml::events::Event;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::pkg::escape_xml;
+
+const SPARKLE_NAMESPACE: &str = "http://www.andymatuschak.org/xml-namespaces/sparkle";
+
+/// Options of the `appcast` subcommand
+#[derive(clap::Args, Debug)]
+pub struct AppcastArgs {
+    /// Directory containing the versioned release archives
+    #[arg(value_name = "DIR")]
+    archives_dir: String,
+
+    /// URL prefix the archives are downloaded from, the archive file name is appended
+    #[arg(short, long)]
+    download_url_prefix: String,
+
+    /// Output appcast path, updated in place if it exists (defaults to {DIR}/appcast.xml)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Private ed25519 key file (base64, as exported by Sparkle's `generate_keys -x`)
+    #[arg(long, value_name = "FILE")]
+    ed_key_file: Option<String>,
+
+    /// Minimum macOS version (defaults to LSMinimumSystemVersion of each bundle)
+    #[arg(long)]
+    minimum_system_version: Option<String>,
+
+    /// Release notes link, placeholders: {version}, {build}
+    /// Example: https://example.com/release-notes/{version}.html
+    #[arg(long, value_name = "URL")]
+    release_notes_url: Option<String>,
+
+    /// Channel title (defaults to the app name)
+    #[arg(long)]
+    title: Option<String>,
+}
+
+/// An `<item>` of the feed
+struct Item {
+    /// CFBundleVersion, used for ordering and replacing items
+    build: String,
+    xml: String,
+}
+
+/// Generates or updates the appcast, returning its path
+pub fn create_appcast(args: &AppcastArgs) -> Result<PathBuf> {
+    let dir = Path::new(&args.archives_dir);
+    if !dir.is_dir() {
+        anyhow::bail!("Archive directory not found: {}", args.archives_dir);
+    }
+    let output = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => dir.join("appcast.xml"),
+    };
+    let signing_key = args.ed_key_file.as_deref().map(read_signing_key).transpose()?;
+    if let Some(key) = &signing_key {
+        println!("SUPublicEDKey: {}", BASE64.encode(key.verifying_key().as_bytes()));
+    }
+
+    let mut archives: Vec<PathBuf> = fs::read_dir(dir)
+        .context(format!("Failed to read directory {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+    archives.sort();
+
+    let mut title = args.title.clone();
+    let mut items = Vec::new();
+    for archive in archives {
+        let file_name = archive.file_name().unwrap().to_string_lossy().to_string();
+        let info = if file_name.ends_with(".zip") {
+            info_plist_from_zip(&archive)?
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            info_plist_from_tar_gz(&archive)?
+        } else {
+            continue;
+        };
+        let Some(info) = info else {
+            println!("Warning: no app bundle found in {}, skipping", archive.display());
+            continue;
+        };
+        let info_string = |key: &str| info.get(key).and_then(|v| v.as_string()).map(str::to_string);
+        let build = info_string("CFBundleVersion").context(format!("No CFBundleVersion in {}", file_name))?;
+        let short_version = info_string("CFBundleShortVersionString").unwrap_or_else(|| build.clone());
+        if title.is_none() {
+            title = info_string("CFBundleName");
+        }
+
+        let data = fs::read(&archive).context(format!("Failed to read {}", archive.display()))?;
+        let pub_date = fs::metadata(&archive)
+            .and_then(|m| m.modified())
+            .map(chrono::DateTime::<chrono::Utc>::from)
+            .unwrap_or_else(|_| chrono::Utc::now());
+
+        let mut xml = String::new();
+        xml.push_str("        <item>\n");
+        xml.push_str(&format!("            <title>{}</title>\n", escape_xml(&short_version)));
+        xml.push_str(&format!("            <pubDate>{}</pubDate>\n", pub_date.to_rfc2822()));
+        xml.push_str(&format!("            <sparkle:version>{}</sparkle:version>\n", escape_xml(&build)));
+        xml.push_str(&format!(
+            "            <sparkle:shortVersionString>{}</sparkle:shortVersionString>\n",
+            escape_xml(&short_version)
+        ));
+        let minimum = args.minimum_system_version.clone().or_else(|| info_string("LSMinimumSystemVersion"));
+        if let Some(minimum) = minimum {
+            xml.push_str(&format!(
+                "            <sparkle:minimumSystemVersion>{}</sparkle:minimumSystemVersion>\n",
+                escape_xml(&minimum)
+            ));
+        }
+        if let Some(notes) = &args.release_notes_url {
+            let notes = notes.replace("{version}", &short_version).replace("{build}", &build);
+            xml.push_str(&format!(
+                "            <sparkle:releaseNotesLink>{}</sparkle:releaseNotesLink>\n",
+                escape_xml(&notes)
+            ));
+        }
+        let url = format!("{}/{}", args.download_url_prefix.trim_end_matches('/'), url_encode(&file_name));
+        xml.push_str(&format!(
+            "            <enclosure url=\"{}\" length=\"{}\" type=\"application/octet-stream\"",
+            escape_xml(&url),
+            data.len()
+        ));
+        if let Some(key) = &signing_key {
+            let signature = key.sign(&data);
+            xml.push_str(&format!(" sparkle:edSignature=\"{}\"", BASE64.encode(signature.to_bytes())));
+        }
+        xml.push_str("/>\n        </item>\n");
+        println!("Added {} ({} / {})", file_name, short_version, build);
+        items.push(Item { build, xml });
+    }
+
+    if output.exists() {
+        let existing = fs::read_to_string(&output).context(format!("Failed to read {}", output.display()))?;
+        for item in existing_items(&existing)? {
+            if !items.iter().any(|i| i.build == item.build) {
+                items.push(item);
+            }
+        }
+    }
+    items.sort_by(|a, b| compare_versions(&b.build, &a.build));
+
+    let mut feed = String::new();
+    feed.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str(&format!(
+        "<rss version=\"2.0\" xmlns:sparkle=\"{}\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n",
+        SPARKLE_NAMESPACE
+    ));
+    feed.push_str("    <channel>\n");
+    feed.push_str(&format!(
+        "        <title>{}</title>\n",
+        escape_xml(title.as_deref().unwrap_or("Updates"))
+    ));
+    for item in &items {
+        feed.push_str(&item.xml);
+    }
+    feed.push_str("    </channel>\n</rss>\n");
+    fs::write(&output, feed).context(format!("Failed to write {}", output.display()))?;
+    Ok(output)
+}
+
+/// Reads a base64 ed25519 private key: a 32 byte seed, or 64 bytes of seed and public key
+fn read_signing_key(path: &str) -> Result<SigningKey> {
+    let content = fs::read_to_string(path).context(format!("Failed to read key file: {}", path))?;
+    let bytes = BASE64
+        .decode(content.trim())
+        .context("Key file is not valid base64")?;
+    let seed: [u8; 32] = match bytes.len() {
+        32 | 64 => bytes[..32].try_into().unwrap(),
+        n => anyhow::bail!("Unsupported ed25519 key length {} in {}, expected 32 or 64 bytes", n, path),
+    };
+    let key = SigningKey::from_bytes(&seed);
+    if bytes.len() == 64 && key.verifying_key().as_bytes() != &bytes[32..] {
+        anyhow::bail!("The public key in {} does not match its private key", path);
+    }
+    Ok(key)
+}
+
+/// Whether `name` is the Info.plist of a top level bundle, such as `Foo.app/Contents/Info.plist`
+fn is_bundle_info_plist(name: &str) -> bool {
+    let name = name.trim_start_matches("./");
+    let parts: Vec<&str> = name.split('/').collect();
+    parts.len() == 3 && parts[0].ends_with(".app") && parts[1] == "Contents" && parts[2] == "Info.plist"
+}
+
+fn info_plist_from_zip(path: &Path) -> Result<Option<plist::Dictionary>> {
+    let file = fs::File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file).context(format!("Failed to read zip {}", path.display()))?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if is_bundle_info_plist(entry.name()) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            return parse_dictionary(&data, path).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn info_plist_from_tar_gz(path: &Path) -> Result<Option<plist::Dictionary>> {
+    let file = fs::File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    for entry in archive.entries().context(format!("Failed to read tarball {}", path.display()))? {
+        let mut entry = entry?;
+        if is_bundle_info_plist(&entry.path()?.to_string_lossy()) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            return parse_dictionary(&data, path).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn parse_dictionary(data: &[u8], archive: &Path) -> Result<plist::Dictionary> {
+    plist::Value::from_reader(std::io::Cursor::new(data))
+        .ok()
+        .and_then(plist::Value::into_dictionary)
+        .context(format!("Invalid Info.plist in {}", archive.display()))
+}
+
+/// Extracts the raw `<item>` elements of an existing appcast with their build versions
+fn existing_items(xml: &str) -> Result<Vec<Item>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut items = Vec::new();
+    let mut item_start = None;
+    let mut in_version = false;
+    let mut build = None;
+    loop {
+        let position = reader.buffer_position() as usize;
+        match reader.read_event().context("Failed to parse existing appcast")? {
+            Event::Start(e) if e.name().as_ref() == b"item" => {
+                item_start = Some(position);
+                build = None;
+            }
+            Event::Start(e) if e.name().as_ref() == b"sparkle:version" => in_version = true,
+            Event::End(e) if e.name().as_ref() == b"sparkle:version" => in_version = false,
+            Event::Text(text) if in_version => {
+                build = Some(text.unescape().context("Invalid sparkle:version")?.trim().to_string());
+            }
+            Event::Empty(e) if e.name().as_ref() == b"enclosure" && build.is_none() => {
+                if let Some(attr) = e.try_get_attribute("sparkle:version")? {
+                    build = Some(attr.unescape_value()?.to_string());
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"item" => {
+                if let (Some(start), Some(build)) = (item_start.take(), build.take()) {
+                    let end = reader.buffer_position() as usize;
+                    items.push(Item {
+                        build,
+                        xml: format!("        {}\n", &xml[start..end]),
+                    });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(items)
+}
+
+/// Compares dot separated versions numerically where possible
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (a, b) => {
+                let a = a.unwrap_or("0");
+                let b = b.unwrap_or("0");
+                let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    _ => a.cmp(b),
+                };
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
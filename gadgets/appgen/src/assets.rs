@@ -0,0 +1,456 @@
+//! Asset catalogs.
+//!
+//! An `.xcassets` folder passed to `--icon` is compiled to `Contents/Resources/Assets.car`
+//! without `actool`, and the images of its icon set are also converted to an .icns file so both
+//! `CFBundleIconName` (macOS 11 and later) and `CFBundleIconFile` (older versions) find the icon.
+//!
+//! Only a subset of the catalog format is written: the `mac` images of one `.appiconset`, each
+//! stored as a zlib-compressed, premultiplied BGRA bitmap rendition, plus a multi-size rendition
+//! listing the point sizes of the icon. Other sets of the catalog are ignored.
+//!
+//! An Assets.car file is a BOM store (a big-endian block table and named variables) with:
+//!
+//! - `CARHEADER`: versions, the rendition count and a UUID of the contents
+//! - `EXTENDED_METADATA`: deployment platform and authoring tool
+//! - `KEYFORMAT`: the attributes of a rendition key, in order
+//! - `FACETKEYS`: a tree from the icon name to its element, part and identifier attributes
+//! - `RENDITIONS`: a tree from rendition keys (one u16 per `KEYFORMAT` attribute) to a CSI
+//!   header (little-endian) followed by the rendition data
+
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the compiled catalog in Contents/Resources
+pub const CAR_NAME: &str = "Assets.car";
+
+/// Rendition key attributes, in KEYFORMAT order
+const KEY_ATTRIBUTES: &[u32] = &[
+    ATTRIBUTE_SCALE,
+    ATTRIBUTE_IDIOM,
+    ATTRIBUTE_SUBTYPE,
+    ATTRIBUTE_DIMENSION1,
+    ATTRIBUTE_IDENTIFIER,
+    ATTRIBUTE_ELEMENT,
+    ATTRIBUTE_PART,
+    ATTRIBUTE_STATE,
+    ATTRIBUTE_VALUE,
+];
+const ATTRIBUTE_ELEMENT: u32 = 1;
+const ATTRIBUTE_PART: u32 = 2;
+const ATTRIBUTE_VALUE: u32 = 6;
+const ATTRIBUTE_DIMENSION1: u32 = 8;
+const ATTRIBUTE_STATE: u32 = 10;
+const ATTRIBUTE_SCALE: u32 = 12;
+const ATTRIBUTE_IDIOM: u32 = 15;
+const ATTRIBUTE_SUBTYPE: u32 = 16;
+const ATTRIBUTE_IDENTIFIER: u32 = 17;
+
+/// Element and part of icon images
+const ELEMENT_ICON: u16 = 85;
+const PART_ICON: u16 = 220;
+
+const LAYOUT_ONE_PART_SCALE: u16 = 12;
+const LAYOUT_MULTISIZE_IMAGE_SET: u16 = 1010;
+const COMPRESSION_ZIP: u32 = 2;
+const COLOR_SPACE_SRGB: u32 = 1;
+
+/// Node size of the BOM trees
+const TREE_BLOCK_SIZE: usize = 4096;
+
+/// An image of an icon set
+struct IconImage {
+    path: PathBuf,
+    /// Size in points
+    size: u32,
+    scale: u32,
+}
+
+/// The `mac` images of an `.appiconset`
+pub struct IconSet {
+    pub name: String,
+    images: Vec<IconImage>,
+}
+
+impl IconSet {
+    /// Image files, from the smallest
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.images.iter().map(|image| image.path.clone()).collect()
+    }
+}
+
+/// Whether the icon is an asset catalog
+pub fn is_asset_catalog(icon: &Path) -> bool {
+    icon.extension().is_some_and(|ext| ext == "xcassets")
+}
+
+/// Reads `{name}.appiconset` of an asset catalog
+pub fn read_icon_set(catalog: &Path, name: &str) -> Result<IconSet> {
+    let set = catalog.join(format!("{}.appiconset", name));
+    let contents_path = set.join("Contents.json");
+    if !contents_path.is_file() {
+        anyhow::bail!(
+            "Icon set {} not found in {} (choose another one with --icon-name)",
+            name,
+            catalog.display()
+        );
+    }
+    let contents: Json = serde_json::from_slice(
+        &fs::read(&contents_path).context(format!("Failed to read {}", contents_path.display()))?,
+    )
+    .context(format!("Invalid {}", contents_path.display()))?;
+
+    let mut images = Vec::new();
+    for entry in contents["images"].as_array().into_iter().flatten() {
+        let (Some("mac"), Some(filename)) = (entry["idiom"].as_str(), entry["filename"].as_str()) else {
+            continue;
+        };
+        let size = entry["size"]
+            .as_str()
+            .and_then(|size| size.split_once('x'))
+            .and_then(|(width, _)| width.parse::<f64>().ok())
+            .context(format!("Invalid size of {} in {}", filename, contents_path.display()))?;
+        let scale = entry["scale"]
+            .as_str()
+            .unwrap_or("1x")
+            .trim_end_matches('x')
+            .parse::<u32>()
+            .context(format!("Invalid scale of {} in {}", filename, contents_path.display()))?;
+        let path = set.join(filename);
+        if !path.is_file() {
+            anyhow::bail!("Icon set image not found: {}", path.display());
+        }
+        images.push(IconImage {
+            path,
+            size: size as u32,
+            scale,
+        });
+    }
+    if images.is_empty() {
+        anyhow::bail!("Icon set {} has no mac images", set.display());
+    }
+    images.sort_by_key(|image| (image.size * image.scale, image.scale));
+    Ok(IconSet {
+        name: name.to_string(),
+        images,
+    })
+}
+
+/// Compiles the icon set to an Assets.car file
+pub fn compile(set: &IconSet) -> Result<Vec<u8>> {
+    let identifier = (crc32fast::hash(set.name.as_bytes()) & 0x7fff) as u16 + 1;
+    let mut sizes: Vec<u32> = set.images.iter().map(|image| image.size).collect();
+    sizes.sort();
+    sizes.dedup();
+
+    let mut renditions = Vec::new();
+    for image in &set.images {
+        let bitmap = crate::icon::load_png(&image.path)?;
+        let pixels = image.size * image.scale;
+        if bitmap.width() != pixels || bitmap.height() != pixels {
+            println!(
+                "Warning: {} is {}x{} pixels, {}x{} expected for {}pt@{}x",
+                image.path.display(),
+                bitmap.width(),
+                bitmap.height(),
+                pixels,
+                pixels,
+                image.size,
+                image.scale
+            );
+        }
+        let index = sizes.iter().position(|size| *size == image.size).unwrap() as u16;
+        let key = rendition_key(identifier, image.scale as u16, index);
+        let name = image.path.file_name().unwrap().to_string_lossy();
+        renditions.push((key, bitmap_rendition(&name, image.scale, &bitmap)?));
+    }
+    renditions.push((rendition_key(identifier, 0, 0), multisize_rendition(&set.name, &sizes)));
+    renditions.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut bom = Bom::default();
+    let mut hasher = Sha256::new();
+    for (_, data) in &renditions {
+        hasher.update(data);
+    }
+    let digest = hasher.finalize();
+    bom.add_var("CARHEADER", car_header(renditions.len() as u32, &digest[..16]));
+    bom.add_var("EXTENDED_METADATA", extended_metadata());
+    bom.add_var("KEYFORMAT", key_format());
+    let facet = facet_token(identifier);
+    bom.add_tree("FACETKEYS", vec![(set.name.as_bytes().to_vec(), facet)]);
+    bom.add_tree(
+        "RENDITIONS",
+        renditions
+            .into_iter()
+            .map(|(key, data)| (key.iter().flat_map(|v| v.to_le_bytes()).collect(), data))
+            .collect(),
+    );
+    Ok(bom.write())
+}
+
+/// Multi-character constant as written by a little-endian C compiler
+fn four_cc(code: &[u8; 4]) -> [u8; 4] {
+    u32::from_be_bytes(*code).to_le_bytes()
+}
+
+fn rendition_key(identifier: u16, scale: u16, dimension1: u16) -> Vec<u16> {
+    KEY_ATTRIBUTES
+        .iter()
+        .map(|attribute| match *attribute {
+            ATTRIBUTE_SCALE => scale,
+            ATTRIBUTE_DIMENSION1 => dimension1,
+            ATTRIBUTE_IDENTIFIER => identifier,
+            ATTRIBUTE_ELEMENT => ELEMENT_ICON,
+            ATTRIBUTE_PART => PART_ICON,
+            _ => 0,
+        })
+        .collect()
+}
+
+/// Fixed-size, NUL-padded C string
+fn c_string(text: &str, size: usize) -> Vec<u8> {
+    let mut bytes = text.as_bytes()[..text.len().min(size - 1)].to_vec();
+    bytes.resize(size, 0);
+    bytes
+}
+
+fn car_header(rendition_count: u32, uuid: &[u8]) -> Vec<u8> {
+    let mut data = four_cc(b"CTAR").to_vec();
+    for value in [
+        // coreuiVersion, storageVersion, storageTimestamp, renditionCount
+        498u32,
+        15,
+        0,
+        rendition_count,
+    ] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    let version = format!("appgen {}", env!("CARGO_PKG_VERSION"));
+    data.extend(c_string(&version, 128));
+    data.extend(c_string(&version, 256));
+    data.extend_from_slice(uuid);
+    // associatedChecksum, schemaVersion, colorSpaceID, keySemantics
+    for value in [0u32, 2, COLOR_SPACE_SRGB, 2] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data
+}
+
+fn extended_metadata() -> Vec<u8> {
+    let mut data = four_cc(b"META").to_vec();
+    data.extend(c_string("", 256));
+    data.extend(c_string("10.10.0", 256));
+    data.extend(c_string("macosx", 256));
+    data.extend(c_string(&format!("appgen {}", env!("CARGO_PKG_VERSION")), 256));
+    data
+}
+
+fn key_format() -> Vec<u8> {
+    let mut data = four_cc(b"kfmt").to_vec();
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&(KEY_ATTRIBUTES.len() as u32).to_le_bytes());
+    for attribute in KEY_ATTRIBUTES {
+        data.extend_from_slice(&attribute.to_le_bytes());
+    }
+    data
+}
+
+fn facet_token(identifier: u16) -> Vec<u8> {
+    let attributes = [
+        (ATTRIBUTE_ELEMENT, ELEMENT_ICON),
+        (ATTRIBUTE_PART, PART_ICON),
+        (ATTRIBUTE_IDENTIFIER, identifier),
+    ];
+    // Cursor hot spot, then the attribute count and (name, value) pairs
+    let mut data = vec![0; 4];
+    data.extend_from_slice(&(attributes.len() as u16).to_le_bytes());
+    for (name, value) in attributes {
+        data.extend_from_slice(&(name as u16).to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data
+}
+
+/// CSI header, TLV records and rendition data
+fn csi(
+    name: &str,
+    layout: u16,
+    (width, height, scale): (u32, u32, u32),
+    pixel_format: [u8; 4],
+    tlv: &[u8],
+    rendition: &[u8],
+) -> Vec<u8> {
+    let mut data = four_cc(b"CTSI").to_vec();
+    // version, flags, width, height, scaleFactor
+    for value in [1u32, 0, width, height, scale * 100] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&pixel_format);
+    data.extend_from_slice(&COLOR_SPACE_SRGB.to_le_bytes());
+    // csimetadata: modtime, layout, padding, name
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&layout.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend(c_string(name, 128));
+    // csibitmaplist: TLV length, bitmap count, padding, rendition length
+    for value in [tlv.len() as u32, 1, 0, rendition.len() as u32] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(tlv);
+    data.extend_from_slice(rendition);
+    data
+}
+
+fn tlv(records: &[(u32, Vec<u32>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (kind, values) in records {
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&(values.len() as u32 * 4).to_le_bytes());
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    data
+}
+
+fn bitmap_rendition(name: &str, scale: u32, image: &image::RgbaImage) -> Result<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let mut pixels = Vec::with_capacity(image.as_raw().len());
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let premultiply = |c: u8| ((c as u32 * a as u32 + 127) / 255) as u8;
+        pixels.extend_from_slice(&[premultiply(b), premultiply(g), premultiply(r), a]);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&pixels)?;
+    let compressed = encoder.finish().context("Failed to compress icon image")?;
+
+    let mut rendition = four_cc(b"CELM").to_vec();
+    for value in [0u32, COMPRESSION_ZIP, compressed.len() as u32] {
+        rendition.extend_from_slice(&value.to_le_bytes());
+    }
+    rendition.extend(compressed);
+    let records = tlv(&[
+        // Slices: one slice covering the image
+        (1001, vec![1, 0, 0, width, height]),
+        // Metrics: no insets, then the image size
+        (1003, vec![1, 0, 0, 0, 0, width, height]),
+        // Bytes per row
+        (1007, vec![width * 4]),
+    ]);
+    Ok(csi(
+        name,
+        LAYOUT_ONE_PART_SCALE,
+        (width, height, scale),
+        four_cc(b"ARGB"),
+        &records,
+        &rendition,
+    ))
+}
+
+fn multisize_rendition(name: &str, sizes: &[u32]) -> Vec<u8> {
+    let mut rendition = four_cc(b"SISM").to_vec();
+    rendition.extend_from_slice(&1u32.to_le_bytes());
+    rendition.extend_from_slice(&(sizes.len() as u32).to_le_bytes());
+    for (index, size) in sizes.iter().enumerate() {
+        rendition.extend_from_slice(&size.to_le_bytes());
+        rendition.extend_from_slice(&size.to_le_bytes());
+        // Dimension1 of the renditions of this size, then the idiom
+        rendition.extend_from_slice(&(index as u16).to_le_bytes());
+        rendition.extend_from_slice(&0u16.to_le_bytes());
+    }
+    csi(name, LAYOUT_MULTISIZE_IMAGE_SET, (0, 0, 0), [0; 4], &[], &rendition)
+}
+
+/// A BOM store being written, block 0 being the null block
+#[derive(Default)]
+struct Bom {
+    blocks: Vec<Vec<u8>>,
+    vars: Vec<(String, u32)>,
+}
+
+impl Bom {
+    fn add_block(&mut self, data: Vec<u8>) -> u32 {
+        self.blocks.push(data);
+        self.blocks.len() as u32
+    }
+
+    fn add_var(&mut self, name: &str, data: Vec<u8>) {
+        let index = self.add_block(data);
+        self.vars.push((name.to_string(), index));
+    }
+
+    /// Adds a tree of one leaf node, entries sorted by key
+    fn add_tree(&mut self, name: &str, entries: Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut node = Vec::new();
+        node.extend_from_slice(&1u16.to_be_bytes());
+        node.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        // Forward and backward sibling nodes
+        node.extend_from_slice(&[0; 8]);
+        let count = entries.len() as u32;
+        for (key, value) in entries {
+            let value = self.add_block(value);
+            let key = self.add_block(key);
+            node.extend_from_slice(&value.to_be_bytes());
+            node.extend_from_slice(&key.to_be_bytes());
+        }
+        let block_size = node.len().max(TREE_BLOCK_SIZE);
+        node.resize(block_size, 0);
+        let child = self.add_block(node);
+
+        let mut tree = b"tree".to_vec();
+        for value in [1u32, child, block_size as u32, count] {
+            tree.extend_from_slice(&value.to_be_bytes());
+        }
+        tree.push(0);
+        self.add_var(name, tree);
+    }
+
+    fn write(self) -> Vec<u8> {
+        const HEADER_SIZE: usize = 512;
+        let mut data = vec![0; HEADER_SIZE];
+        let mut pointers = vec![(0u32, 0u32)];
+        for block in &self.blocks {
+            pointers.push((data.len() as u32, block.len() as u32));
+            data.extend_from_slice(block);
+        }
+
+        let index_offset = data.len();
+        data.extend_from_slice(&(pointers.len() as u32).to_be_bytes());
+        for (address, length) in &pointers {
+            data.extend_from_slice(&address.to_be_bytes());
+            data.extend_from_slice(&length.to_be_bytes());
+        }
+        // Empty free list
+        data.extend_from_slice(&0u32.to_be_bytes());
+        let index_length = data.len() - index_offset;
+
+        let vars_offset = data.len();
+        data.extend_from_slice(&(self.vars.len() as u32).to_be_bytes());
+        for (name, index) in &self.vars {
+            data.extend_from_slice(&index.to_be_bytes());
+            data.push(name.len() as u8);
+            data.extend_from_slice(name.as_bytes());
+        }
+        let vars_length = data.len() - vars_offset;
+
+        let mut header = b"BOMStore".to_vec();
+        for value in [
+            1,
+            pointers.len() as u32,
+            index_offset as u32,
+            index_length as u32,
+            vars_offset as u32,
+            vars_length as u32,
+        ] {
+            header.extend_from_slice(&value.to_be_bytes());
+        }
+        data[..header.len()].copy_from_slice(&header);
+        data
+    }
+}
@@ -0,0 +1,226 @@
+//! Homebrew cask and `curl | bash` install script generation for a built bundle.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Options of the `cask` subcommand
+#[derive(clap::Args, Debug)]
+pub struct CaskArgs {
+    /// Path to the .app bundle the cask installs
+    #[arg(value_name = "APP")]
+    app: String,
+
+    /// Release archive (.zip, .tar.gz or .dmg) containing the bundle, used for the sha256
+    #[arg(short, long)]
+    archive: String,
+
+    /// Download URL of the archive
+    /// Placeholders: {version}, {name}, {archive}, {bundle_id}
+    /// Example: https://example.com/releases/v{version}/{archive}
+    #[arg(short, long)]
+    url: String,
+
+    /// Output path of the cask (defaults to {token}.rb in the current directory)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Cask token (defaults to the lowercased app name with dashes)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Optional one line description
+    #[arg(long)]
+    desc: Option<String>,
+
+    /// Optional homepage URL
+    #[arg(long)]
+    homepage: Option<String>,
+
+    /// Paths removed by `brew uninstall --zap`, {bundle_id} is replaced
+    /// Example: --zap "~/Library/Application Support/{bundle_id}"
+    #[arg(long, value_name = "PATH")]
+    zap: Vec<String>,
+
+    /// Also write a curl-pipe install script verifying the archive checksum to this path
+    #[arg(long, value_name = "PATH")]
+    install_script: Option<String>,
+}
+
+/// Bundle metadata the cask is generated from
+struct CaskInfo {
+    token: String,
+    name: String,
+    app_file_name: String,
+    version: String,
+    bundle_id: String,
+    sha256: String,
+    url: String,
+    archive_name: String,
+}
+
+/// Writes the cask (and the install script if requested), returning the written paths
+pub fn create_cask(args: &CaskArgs) -> Result<Vec<PathBuf>> {
+    let app_path = Path::new(&args.app);
+    if !app_path.is_dir() {
+        anyhow::bail!("App bundle not found: {}", args.app);
+    }
+    let archive_path = Path::new(&args.archive);
+    if !archive_path.is_file() {
+        anyhow::bail!("Archive not found: {}", args.archive);
+    }
+
+    let info = crate::read_info_plist(app_path)?;
+    let info_string = |key: &str| info.get(key).and_then(|v| v.as_string()).map(str::to_string);
+    let app_file_name = app_path.file_name().unwrap().to_string_lossy().to_string();
+    let name = info_string("CFBundleName")
+        .unwrap_or_else(|| app_file_name.trim_end_matches(".app").to_string());
+    let version = info_string("CFBundleShortVersionString").context("Info.plist has no CFBundleShortVersionString")?;
+    let bundle_id = info_string("CFBundleIdentifier").context("Info.plist has no CFBundleIdentifier")?;
+    let archive_name = archive_path.file_name().unwrap().to_string_lossy().to_string();
+
+    let archive = fs::read(archive_path).context("Failed to read archive")?;
+    let sha256 = Sha256::digest(&archive)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    let url = args
+        .url
+        .replace("{name}", &name)
+        .replace("{archive}", &archive_name)
+        .replace("{bundle_id}", &bundle_id);
+    let token = args.token.clone().unwrap_or_else(|| cask_token(&name));
+    let cask = CaskInfo { token, name, app_file_name, version, bundle_id, sha256, url, archive_name };
+
+    let mut written = Vec::new();
+    let output = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(format!("{}.rb", cask.token)),
+    };
+    fs::write(&output, cask_ruby(&cask, args)).context(format!("Failed to write cask: {}", output.display()))?;
+    written.push(output);
+
+    if let Some(script_path) = &args.install_script {
+        let script_path = PathBuf::from(script_path);
+        fs::write(&script_path, install_script(&cask))
+            .context(format!("Failed to write install script: {}", script_path.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755))
+                .context("Failed to set permissions on install script")?;
+        }
+        written.push(script_path);
+    }
+    Ok(written)
+}
+
+/// Homebrew style token: lowercase, alphanumerics separated by single dashes
+pub(crate) fn cask_token(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn ruby_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace("#{", "\\#{"))
+}
+
+fn cask_ruby(cask: &CaskInfo, args: &CaskArgs) -> String {
+    // The version is interpolated by Ruby so the url keeps working after `version` bumps
+    let url = ruby_string(&cask.url).replace("{version}", "#{version}");
+
+    let mut out = format!("cask {} do\n", ruby_string(&cask.token));
+    out.push_str(&format!("  version {}\n", ruby_string(&cask.version)));
+    out.push_str(&format!("  sha256 {}\n\n", ruby_string(&cask.sha256)));
+    out.push_str(&format!("  url {}\n", url));
+    out.push_str(&format!("  name {}\n", ruby_string(&cask.name)));
+    if let Some(desc) = &args.desc {
+        out.push_str(&format!("  desc {}\n", ruby_string(desc)));
+    }
+    if let Some(homepage) = &args.homepage {
+        out.push_str(&format!("  homepage {}\n", ruby_string(homepage)));
+    }
+    out.push_str(&format!("\n  app {}\n", ruby_string(&cask.app_file_name)));
+    out.push_str(&format!("\n  uninstall quit: {}\n", ruby_string(&cask.bundle_id)));
+    if !args.zap.is_empty() {
+        out.push_str("\n  zap trash: [\n");
+        for path in &args.zap {
+            out.push_str(&format!("    {},\n", ruby_string(&path.replace("{bundle_id}", &cask.bundle_id))));
+        }
+        out.push_str("  ]\n");
+    }
+    out.push_str("end\n");
+    out
+}
+
+fn install_script(cask: &CaskInfo) -> String {
+    let url = cask.url.replace("{version}", &cask.version);
+    let extract = if cask.archive_name.ends_with(".dmg") {
+        r#"MOUNT_POINT="$TMP_DIR/mount"
+mkdir -p "$MOUNT_POINT"
+hdiutil attach -nobrowse -readonly -mountpoint "$MOUNT_POINT" "$ARCHIVE" >/dev/null
+trap 'hdiutil detach "$MOUNT_POINT" >/dev/null 2>&1; rm -rf "$TMP_DIR"' EXIT
+SOURCE_DIR="$MOUNT_POINT""#
+    } else if cask.archive_name.ends_with(".tar.gz") || cask.archive_name.ends_with(".tgz") {
+        r#"tar -xzf "$ARCHIVE" -C "$TMP_DIR/extracted"
+SOURCE_DIR="$TMP_DIR/extracted""#
+    } else {
+        r#"ditto -x -k "$ARCHIVE" "$TMP_DIR/extracted"
+SOURCE_DIR="$TMP_DIR/extracted""#
+    };
+
+    format!(
+        r#"#!/bin/bash
+# Installs the app downloaded from URL, checking the archive against SHA256
+# Usage: curl -fsSL <this script url> | bash
+set -euo pipefail
+
+NAME={name}
+VERSION={version}
+URL={url}
+SHA256='{sha256}'
+APP_NAME={app_file_name}
+ARCHIVE_NAME={archive_name}
+INSTALL_DIR="${{INSTALL_DIR:-/Applications}}"
+
+TMP_DIR="$(mktemp -d)"
+trap 'rm -rf "$TMP_DIR"' EXIT
+ARCHIVE="$TMP_DIR/$ARCHIVE_NAME"
+mkdir -p "$TMP_DIR/extracted"
+
+echo "Downloading $URL"
+curl -fsSL "$URL" -o "$ARCHIVE"
+
+echo "Verifying checksum"
+if ! echo "$SHA256  $ARCHIVE" | shasum -a 256 -c - >/dev/null; then
+    echo "Error: checksum mismatch for $URL" >&2
+    exit 1
+fi
+
+{extract}
+
+APP_SOURCE="$(find "$SOURCE_DIR" -maxdepth 2 -name "$APP_NAME" -type d | head -n 1)"
+if [ -z "$APP_SOURCE" ]; then
+    echo "Error: $APP_NAME not found in the archive" >&2
+    exit 1
+fi
+
+echo "Installing $NAME $VERSION to $INSTALL_DIR"
+rm -rf "${{INSTALL_DIR:?}}/$APP_NAME"
+ditto "$APP_SOURCE" "$INSTALL_DIR/$APP_NAME"
+echo "Installed $INSTALL_DIR/$APP_NAME"
+"#,
+        name = crate::shell_quote(&cask.name),
+        version = crate::shell_quote(&cask.version),
+        url = crate::shell_quote(&url),
+        sha256 = cask.sha256,
+        app_file_name = crate::shell_quote(&cask.app_file_name),
+        archive_name = crate::shell_quote(&cask.archive_name),
+        extract = extract,
+    )
+}
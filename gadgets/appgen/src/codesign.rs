@@ -0,0 +1,822 @@
+//! Ad-hoc code signing that works without Apple's `codesign`.
+//!
+//! Signing a bundle seals its resources in `Contents/_CodeSignature/CodeResources`
+//! (SHA-1 and SHA-256 hashes with the standard rules) and embeds an ad-hoc signature
+//! (a SHA-256 CodeDirectory, an empty requirement set and an empty CMS wrapper) into
+//! every slice of the main executable. The CodeDirectory binds the Info.plist and the
+//! CodeResources file through its special slots. Nested Mach-O files and bundles (apps and
+//! frameworks) in the nested code locations are signed first and sealed by their cdhash. A
+//! versioned framework is signed through `Versions/Current`, with its own CodeResources in
+//! `Versions/A/_CodeSignature`.
+//!
+//! Entitlements and the hardened runtime flag only apply to the main executable of the
+//! signed bundle, nested code gets neither.
+
+mod entitlements;
+
+use anyhow::{Context, Result};
+use plist::Value;
+use regex::Regex;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::macho::{self, LC_CODE_SIGNATURE, MachO};
+
+const CSMAGIC_REQUIREMENTS: u32 = 0xfade_0c01;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_BLOBWRAPPER: u32 = 0xfade_0b01;
+
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CSSLOT_INFOSLOT: usize = 1;
+const CSSLOT_REQUIREMENTS: u32 = 2;
+const CSSLOT_RESOURCEDIR: usize = 3;
+const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+const CS_ADHOC: u32 = 0x2;
+const CS_RUNTIME: u32 = 0x10000;
+const CS_EXECSEG_MAIN_BINARY: u64 = 0x1;
+const CD_VERSION: u32 = 0x20400;
+const CD_HEADER_SIZE: usize = 88;
+const HASH_TYPE_SHA256: u8 = 2;
+const HASH_SIZE: usize = 32;
+const PAGE_SHIFT: u8 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_SHIFT;
+/// Page sizes accepted when verifying, from 512 bytes to 64 KiB
+const MIN_PAGE_SHIFT: u8 = 9;
+const MAX_PAGE_SHIFT: u8 = 16;
+
+/// Options of the `sign` subcommand
+#[derive(clap::Args, Debug)]
+pub struct SignArgs {
+    /// Path to the .app bundle to sign
+    #[arg(value_name = "APP")]
+    pub app: String,
+
+    #[command(flatten)]
+    pub signing: SigningArgs,
+}
+
+/// Entitlements and runtime options shared by `--sign` builds and the `sign` subcommand
+#[derive(clap::Args, Debug, Default)]
+pub struct SigningArgs {
+    /// Entitlements plist embedded in the signature of the main executable
+    #[arg(long, value_name = "PLIST")]
+    pub entitlements: Option<String>,
+
+    /// Enable the hardened runtime (required for notarization)
+    #[arg(long, default_value_t = false)]
+    hardened_runtime: bool,
+
+    /// Entitlement: allow outgoing network connections (com.apple.security.network.client)
+    #[arg(long, default_value_t = false)]
+    allow_network_client: bool,
+
+    /// Entitlement: allow camera access (com.apple.security.device.camera)
+    #[arg(long, default_value_t = false)]
+    allow_camera: bool,
+
+    /// Entitlement: allow microphone access (com.apple.security.device.audio-input)
+    #[arg(long, default_value_t = false)]
+    allow_microphone: bool,
+
+    /// Entitlement: allow sending Apple Events to other apps (com.apple.security.automation.apple-events)
+    #[arg(long, default_value_t = false)]
+    allow_apple_events: bool,
+
+    /// Entitlement: load libraries signed by other teams (com.apple.security.cs.disable-library-validation)
+    #[arg(long, default_value_t = false)]
+    disable_library_validation: bool,
+}
+
+impl SigningArgs {
+    /// Whether any signing option was given, which only makes sense when signing
+    pub fn is_set(&self) -> bool {
+        self.entitlements.is_some()
+            || self.hardened_runtime
+            || self.allow_network_client
+            || self.allow_camera
+            || self.allow_microphone
+            || self.allow_apple_events
+            || self.disable_library_validation
+    }
+
+    /// Loads and validates the entitlements, merging in the shorthand flags
+    pub fn resolve(&self) -> Result<SignOptions> {
+        let mut entitlements = match &self.entitlements {
+            Some(path) => Value::from_file(path)
+                .context(format!("Failed to read entitlements: {}", path))?
+                .into_dictionary()
+                .context(format!("Entitlements file {} is not a dictionary", path))?,
+            None => plist::Dictionary::new(),
+        };
+        for (enabled, key) in [
+            (self.allow_network_client, "com.apple.security.network.client"),
+            (self.allow_camera, "com.apple.security.device.camera"),
+            (self.allow_microphone, "com.apple.security.device.audio-input"),
+            (self.allow_apple_events, "com.apple.security.automation.apple-events"),
+            (self.disable_library_validation, "com.apple.security.cs.disable-library-validation"),
+        ] {
+            if enabled {
+                entitlements.insert(key.to_string(), Value::Boolean(true));
+            }
+        }
+        entitlements::validate(&entitlements, self.hardened_runtime)?;
+        Ok(SignOptions {
+            entitlements: (!entitlements.is_empty()).then_some(entitlements),
+            hardened_runtime: self.hardened_runtime,
+        })
+    }
+}
+
+/// Resolved signing options
+#[derive(Debug, Clone, Default)]
+pub struct SignOptions {
+    pub entitlements: Option<plist::Dictionary>,
+    pub hardened_runtime: bool,
+}
+
+/// Options of the `verify` subcommand
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    /// Path to the .app bundle to verify
+    #[arg(value_name = "APP")]
+    pub app: String,
+}
+
+/// A resource rule: files matching `pattern` with the highest `weight` decide the handling
+struct Rule {
+    pattern: &'static str,
+    weight: u32,
+    omit: bool,
+    optional: bool,
+    nested: bool,
+}
+
+const fn rule(pattern: &'static str, weight: u32, omit: bool, optional: bool, nested: bool) -> Rule {
+    Rule { pattern, weight, omit, optional, nested }
+}
+
+/// The `rules` (version 1) codesign writes for bundles
+const RULES: &[Rule] = &[
+    rule("^version.plist$", 1, false, false, false),
+    rule("^Resources/", 1, false, false, false),
+    rule("^Resources/.*\\.lproj/", 1000, false, true, false),
+    rule("^Resources/.*\\.lproj/locversion.plist$", 1100, true, false, false),
+    rule("^Resources/Base\\.lproj/", 1010, false, false, false),
+];
+
+/// The `rules2` codesign writes for bundles
+const RULES2: &[Rule] = &[
+    rule(".*\\.dSYM($|/)", 11, false, false, false),
+    rule("^(.*/)?\\.DS_Store$", 2000, true, false, false),
+    rule(
+        "^(Frameworks|SharedFrameworks|PlugIns|Plug-ins|XPCServices|Helpers|MacOS|Library/(Automator|Spotlight|LoginItems))/",
+        10,
+        false,
+        false,
+        true,
+    ),
+    rule("^.*", 1, false, false, false),
+    rule("^Info\\.plist$", 20, true, false, false),
+    rule("^PkgInfo$", 20, true, false, false),
+    rule("^Resources/", 20, false, false, false),
+    rule("^Resources/.*\\.lproj/", 1000, false, true, false),
+    rule("^Resources/.*\\.lproj/locversion.plist$", 1100, true, false, false),
+    rule("^Resources/Base\\.lproj/", 1010, false, false, false),
+    rule("^[^/]+$", 10, false, false, true),
+    rule("^embedded\\.provisionprofile$", 20, false, false, false),
+    rule("^version\\.plist$", 20, false, false, false),
+];
+
+/// The patterns of `RULES` and `RULES2`, compiled once for all files
+static PATTERNS: LazyLock<HashMap<&'static str, Regex>> = LazyLock::new(|| {
+    RULES
+        .iter()
+        .chain(RULES2)
+        .map(|r| (r.pattern, Regex::new(r.pattern).expect("invalid resource rule pattern")))
+        .collect()
+});
+
+/// Finds the rule deciding how `path` (relative to Contents) is sealed
+fn match_rule<'a>(rules: &'a [Rule], path: &str) -> Option<&'a Rule> {
+    rules
+        .iter()
+        .filter(|r| PATTERNS[r.pattern].is_match(path))
+        .max_by_key(|r| r.weight)
+}
+
+fn rules_plist(rules: &[Rule]) -> Value {
+    let mut dict = plist::Dictionary::new();
+    for rule in rules {
+        let value = if rule.weight == 1 && !rule.omit && !rule.optional && !rule.nested {
+            Value::Boolean(true)
+        } else {
+            let mut options = plist::Dictionary::new();
+            if rule.nested {
+                options.insert("nested".to_string(), Value::Boolean(true));
+            }
+            if rule.omit {
+                options.insert("omit".to_string(), Value::Boolean(true));
+            }
+            if rule.optional {
+                options.insert("optional".to_string(), Value::Boolean(true));
+            }
+            options.insert("weight".to_string(), Value::Real(rule.weight as f64));
+            Value::Dictionary(options)
+        };
+        dict.insert(rule.pattern.to_string(), value);
+    }
+    Value::Dictionary(dict)
+}
+
+/// The parts of an app or framework bundle signing reads and writes
+struct Bundle {
+    /// Directory sealed by CodeResources: Contents of an app, the current version of a framework
+    root: PathBuf,
+    info_plist: PathBuf,
+    identifier: String,
+    /// Main executable, relative to `root`
+    executable: String,
+}
+
+impl Bundle {
+    fn open(path: &Path) -> Result<Bundle> {
+        let (root, info_plist) = if is_framework(path) {
+            // Versioned frameworks are signed through Versions/Current, shallow ones at the top
+            let current = path.join("Versions").join("Current");
+            let root = if current.is_dir() { current } else { path.to_path_buf() };
+            let info_plist = root.join("Resources").join("Info.plist");
+            let info_plist = if info_plist.is_file() { info_plist } else { root.join("Info.plist") };
+            (root, info_plist)
+        } else {
+            let root = path.join("Contents");
+            let info_plist = root.join("Info.plist");
+            (root, info_plist)
+        };
+        let info = Value::from_file(&info_plist)
+            .context(format!("Failed to read {}", info_plist.display()))?
+            .into_dictionary()
+            .context(format!("{} is not a dictionary", info_plist.display()))?;
+        let info_string = |key: &str| info.get(key).and_then(|v| v.as_string()).map(str::to_string);
+        let identifier = info_string("CFBundleIdentifier")
+            .context(format!("{} has no CFBundleIdentifier", info_plist.display()))?;
+        let executable_name = info_string("CFBundleExecutable")
+            .context(format!("{} has no CFBundleExecutable", info_plist.display()))?;
+        let executable = if is_framework(path) {
+            executable_name
+        } else {
+            format!("MacOS/{}", executable_name)
+        };
+        Ok(Bundle {
+            root,
+            info_plist,
+            identifier,
+            executable,
+        })
+    }
+}
+
+/// Signs an app or framework bundle in place
+pub fn sign_bundle(app_path: &Path, options: &SignOptions) -> Result<()> {
+    let bundle = Bundle::open(app_path)?;
+    let executable = bundle.root.join(&bundle.executable);
+
+    let resources = build_code_resources(&bundle)?;
+    let signature_dir = bundle.root.join("_CodeSignature");
+    fs::create_dir_all(&signature_dir).context("Failed to create _CodeSignature directory")?;
+    let resources_path = signature_dir.join("CodeResources");
+    plist::to_file_xml(&resources_path, &resources).context("Failed to write CodeResources")?;
+
+    let executable_data = fs::read(&executable)
+        .context(format!("Failed to read main executable {}", executable.display()))?;
+    if !macho::is_macho(&executable_data) {
+        println!(
+            "Warning: {} is not a Mach-O binary, only its resources were sealed. Script executables keep their signature in extended attributes, which requires codesign on macOS",
+            executable.display()
+        );
+        if options.entitlements.is_some() || options.hardened_runtime {
+            println!("Warning: entitlements and the hardened runtime were not applied to the script executable");
+        }
+        return Ok(());
+    }
+
+    let mut special = BTreeMap::new();
+    special.insert(CSSLOT_INFOSLOT, Sha256::digest(fs::read(&bundle.info_plist)?).to_vec());
+    special.insert(CSSLOT_RESOURCEDIR, Sha256::digest(fs::read(&resources_path)?).to_vec());
+    let signed = sign_macho(&executable_data, &bundle.identifier, &special, options, true)?;
+    write_preserving_permissions(&executable, &signed)?;
+    Ok(())
+}
+
+/// Computes the CodeResources dictionary of a bundle
+fn build_code_resources(bundle: &Bundle) -> Result<Value> {
+    // Entitlements and the hardened runtime belong to the main executable only
+    let nested_options = SignOptions { entitlements: None, hardened_runtime: false };
+    let mut files = plist::Dictionary::new();
+    let mut files2 = plist::Dictionary::new();
+
+    let mut entries = Vec::new();
+    collect_files(&bundle.root, &bundle.root, &mut entries)?;
+
+    for (relative, path) in entries {
+        if relative == bundle.executable || relative.starts_with("_CodeSignature/") {
+            continue;
+        }
+        let Some(rule2) = match_rule(RULES2, &relative) else {
+            continue;
+        };
+        if rule2.omit {
+            continue;
+        }
+
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(&path)?.to_string_lossy().to_string();
+            files2.insert(
+                relative.clone(),
+                Value::Dictionary(plist::Dictionary::from_iter([(
+                    "symlink".to_string(),
+                    Value::String(target.clone()),
+                )])),
+            );
+            if let Some(rule) = match_rule(RULES, &relative).filter(|r| !r.omit) {
+                files.insert(relative, legacy_entry(Sha1::digest(target.as_bytes()).to_vec(), rule.optional));
+            }
+            continue;
+        }
+
+        // Nested bundles are signed on their own and sealed by their code directory hash
+        if metadata.is_dir() {
+            if rule2.nested && is_bundle_dir(&path) {
+                sign_bundle(&path, &nested_options)?;
+                let nested = Bundle::open(&path)?;
+                let data = fs::read(nested.root.join(&nested.executable))?;
+                if let Some(entry) = nested_code_entry(&data)? {
+                    files2.insert(relative, entry);
+                }
+            }
+            continue;
+        }
+
+        let mut data = fs::read(&path).context(format!("Failed to read {}", path.display()))?;
+        if rule2.nested && macho::is_macho(&data) {
+            let identifier = path.file_stem().unwrap().to_string_lossy().to_string();
+            data = sign_macho(&data, &identifier, &BTreeMap::new(), &nested_options, false)?;
+            write_preserving_permissions(&path, &data)?;
+            if let Some(entry) = nested_code_entry(&data)? {
+                files2.insert(relative, entry);
+            }
+            continue;
+        }
+
+        let mut entry = plist::Dictionary::new();
+        entry.insert("hash".to_string(), Value::Data(Sha1::digest(&data).to_vec()));
+        entry.insert("hash2".to_string(), Value::Data(Sha256::digest(&data).to_vec()));
+        if rule2.optional {
+            entry.insert("optional".to_string(), Value::Boolean(true));
+        }
+        files2.insert(relative.clone(), Value::Dictionary(entry));
+
+        if let Some(rule) = match_rule(RULES, &relative).filter(|r| !r.omit) {
+            files.insert(relative, legacy_entry(Sha1::digest(&data).to_vec(), rule.optional));
+        }
+    }
+
+    Ok(Value::Dictionary(plist::Dictionary::from_iter([
+        ("files".to_string(), Value::Dictionary(files)),
+        ("files2".to_string(), Value::Dictionary(files2)),
+        ("rules".to_string(), rules_plist(RULES)),
+        ("rules2".to_string(), rules_plist(RULES2)),
+    ])))
+}
+
+fn legacy_entry(hash: Vec<u8>, optional: bool) -> Value {
+    if optional {
+        Value::Dictionary(plist::Dictionary::from_iter([
+            ("hash".to_string(), Value::Data(hash)),
+            ("optional".to_string(), Value::Boolean(true)),
+        ]))
+    } else {
+        Value::Data(hash)
+    }
+}
+
+/// files2 entry of signed nested code: its cdhash and the matching ad-hoc requirement
+fn nested_code_entry(data: &[u8]) -> Result<Option<Value>> {
+    let (_, slices) = macho::split_slices(data)?;
+    let Some(slice) = slices.first() else {
+        return Ok(None);
+    };
+    let Some(code_directory) = find_code_directory(&MachO::parse(slice.data.clone())?)? else {
+        return Ok(None);
+    };
+    let cdhash = Sha256::digest(&code_directory)[..20].to_vec();
+    let hex: String = cdhash.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(Some(Value::Dictionary(plist::Dictionary::from_iter([
+        ("cdhash".to_string(), Value::Data(cdhash)),
+        ("requirement".to_string(), Value::String(format!("cdhash H\"{}\"", hex))),
+    ]))))
+}
+
+/// Recursively lists the entries below `dir` as `(path relative to root, path)`, without
+/// descending into bundles or symlinked directories
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("Failed to read directory {}", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if is_bundle_dir(&path) {
+                out.push((relative, path));
+            } else {
+                collect_files(root, &path, out)?;
+            }
+        } else {
+            out.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+fn is_bundle_dir(path: &Path) -> bool {
+    (path.extension().is_some_and(|e| e == "app") && path.join("Contents").is_dir()) || is_framework(path)
+}
+
+fn is_framework(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "framework") && path.is_dir()
+}
+
+fn write_preserving_permissions(path: &Path, data: &[u8]) -> Result<()> {
+    let permissions = fs::metadata(path)?.permissions();
+    fs::write(path, data).context(format!("Failed to write {}", path.display()))?;
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+/// Embeds an ad-hoc signature into every slice of a Mach-O file
+pub fn sign_macho(
+    data: &[u8],
+    identifier: &str,
+    special_slots: &BTreeMap<usize, Vec<u8>>,
+    options: &SignOptions,
+    main_binary: bool,
+) -> Result<Vec<u8>> {
+    // Blobs besides the code directory, indexed by their slot
+    let mut blobs = vec![(CSSLOT_REQUIREMENTS, requirements_blob())];
+    if let Some(entitlements) = &options.entitlements {
+        blobs.push((entitlements::CSSLOT_ENTITLEMENTS, entitlements::xml_blob(entitlements)?));
+        blobs.push((entitlements::CSSLOT_DER_ENTITLEMENTS, entitlements::der_blob(entitlements)?));
+    }
+    let mut slots = special_slots.clone();
+    for (slot, blob) in &blobs {
+        slots.insert(*slot as usize, Sha256::digest(blob).to_vec());
+    }
+    blobs.push((CSSLOT_SIGNATURESLOT, blob_header(CSMAGIC_BLOBWRAPPER, 8)));
+
+    let mut flags = CS_ADHOC;
+    if options.hardened_runtime {
+        flags |= CS_RUNTIME;
+    }
+    let (fat, slices) = macho::split_slices(data)?;
+    let mut signed = Vec::with_capacity(slices.len());
+    for mut slice in slices {
+        slice.data = sign_slice(MachO::parse(slice.data)?, identifier, &slots, &blobs, flags, main_binary)?;
+        signed.push(slice);
+    }
+    Ok(macho::join_slices(fat, signed))
+}
+
+fn blob_header(magic: u32, length: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(length);
+    out.extend_from_slice(&magic.to_be_bytes());
+    out.extend_from_slice(&(length as u32).to_be_bytes());
+    out
+}
+
+/// Empty requirement set, which is what ad-hoc signatures carry
+fn requirements_blob() -> Vec<u8> {
+    let mut blob = blob_header(CSMAGIC_REQUIREMENTS, 12);
+    blob.extend_from_slice(&0u32.to_be_bytes());
+    blob
+}
+
+fn sign_slice(
+    mut macho: MachO,
+    identifier: &str,
+    slots: &BTreeMap<usize, Vec<u8>>,
+    blobs: &[SlotBlob],
+    flags: u32,
+    main_binary: bool,
+) -> Result<Vec<u8>> {
+    let linkedit = macho
+        .segment("__LINKEDIT")
+        .context("Mach-O image has no __LINKEDIT segment")?;
+
+    // Drop an existing signature, it always sits at the end of __LINKEDIT
+    let command_offset = match macho.code_signature() {
+        Some((offset, dataoff, _)) => {
+            macho.data.truncate(dataoff as usize);
+            offset
+        }
+        None => {
+            let mut command = Vec::with_capacity(16);
+            command.extend_from_slice(&LC_CODE_SIGNATURE.to_le_bytes());
+            command.extend_from_slice(&16u32.to_le_bytes());
+            command.extend_from_slice(&[0; 8]);
+            macho.add_load_command(&command)?
+        }
+    };
+    let code_limit = macho.data.len().div_ceil(16) * 16;
+    macho.data.resize(code_limit, 0);
+
+    let special_count = slots.keys().max().copied().unwrap_or_default();
+    let code_slots = code_limit.div_ceil(PAGE_SIZE);
+    let code_directory_size = CD_HEADER_SIZE + identifier.len() + 1 + (special_count + code_slots) * HASH_SIZE;
+    let blobs_size = code_directory_size + blobs.iter().map(|(_, blob)| blob.len()).sum::<usize>();
+    let superblob_size = 12 + (blobs.len() + 1) * 8 + blobs_size;
+    let signature_size = superblob_size.div_ceil(16) * 16;
+
+    // Point LC_CODE_SIGNATURE and __LINKEDIT at the new signature before hashing the pages,
+    // the load commands are part of the signed code
+    macho.set_u32(command_offset + 8, code_limit as u32);
+    macho.set_u32(command_offset + 12, signature_size as u32);
+    let linkedit_size = (code_limit + signature_size) as u64 - linkedit.fileoff;
+    macho.set_segment_size(&linkedit, linkedit_size, linkedit_size.div_ceil(0x4000) * 0x4000);
+
+    let (exec_base, exec_limit) = macho
+        .segment("__TEXT")
+        .map(|s| (s.fileoff, s.filesize))
+        .unwrap_or_default();
+
+    let mut cd = blob_header(CSMAGIC_CODEDIRECTORY, code_directory_size);
+    let ident_offset = CD_HEADER_SIZE;
+    let hash_offset = ident_offset + identifier.len() + 1 + special_count * HASH_SIZE;
+    for value in [
+        CD_VERSION,
+        flags,
+        hash_offset as u32,
+        ident_offset as u32,
+        special_count as u32,
+        code_slots as u32,
+        code_limit as u32,
+    ] {
+        cd.extend_from_slice(&value.to_be_bytes());
+    }
+    cd.extend_from_slice(&[HASH_SIZE as u8, HASH_TYPE_SHA256, 0, PAGE_SHIFT]);
+    cd.extend_from_slice(&[0; 4]); // spare2
+    cd.extend_from_slice(&[0; 4]); // scatterOffset
+    cd.extend_from_slice(&[0; 4]); // teamOffset
+    cd.extend_from_slice(&[0; 4]); // spare3
+    cd.extend_from_slice(&[0; 8]); // codeLimit64
+    cd.extend_from_slice(&exec_base.to_be_bytes());
+    cd.extend_from_slice(&exec_limit.to_be_bytes());
+    let exec_flags = if main_binary { CS_EXECSEG_MAIN_BINARY } else { 0 };
+    cd.extend_from_slice(&exec_flags.to_be_bytes());
+    cd.extend_from_slice(identifier.as_bytes());
+    cd.push(0);
+    // Special slots are stored in reverse order in front of the code slots
+    for slot in (1..=special_count).rev() {
+        match slots.get(&slot) {
+            Some(hash) => cd.extend_from_slice(hash),
+            None => cd.extend_from_slice(&[0; HASH_SIZE]),
+        }
+    }
+    for page in macho.data.chunks(PAGE_SIZE) {
+        cd.extend_from_slice(&Sha256::digest(page));
+    }
+    debug_assert_eq!(cd.len(), code_directory_size);
+
+    let mut superblob = blob_header(CSMAGIC_EMBEDDED_SIGNATURE, superblob_size);
+    superblob.extend_from_slice(&((blobs.len() + 1) as u32).to_be_bytes());
+    let mut offset = 12 + (blobs.len() + 1) * 8;
+    let all_blobs = std::iter::once((CSSLOT_CODEDIRECTORY, &cd)).chain(blobs.iter().map(|(slot, blob)| (*slot, blob)));
+    for (slot, blob) in all_blobs.clone() {
+        superblob.extend_from_slice(&slot.to_be_bytes());
+        superblob.extend_from_slice(&(offset as u32).to_be_bytes());
+        offset += blob.len();
+    }
+    for (_, blob) in all_blobs {
+        superblob.extend_from_slice(blob);
+    }
+    superblob.resize(signature_size, 0);
+
+    macho.data.extend_from_slice(&superblob);
+    Ok(macho.data)
+}
+
+/// A blob of an embedded signature and the slot it is indexed under
+type SlotBlob = (u32, Vec<u8>);
+
+/// Embedded signature blobs of a slice
+fn signature_blobs(macho: &MachO) -> Result<Option<Vec<SlotBlob>>> {
+    let Some((_, dataoff, datasize)) = macho.code_signature() else {
+        return Ok(None);
+    };
+    let superblob = macho
+        .data
+        .get(dataoff as usize..dataoff as usize + datasize as usize)
+        .context("Code signature out of bounds")?;
+    let be32 = |data: &[u8], at: usize| -> Result<u32> {
+        Ok(u32::from_be_bytes(
+            data.get(at..at + 4).context("Truncated code signature")?.try_into().unwrap(),
+        ))
+    };
+    if be32(superblob, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+        anyhow::bail!("Invalid embedded signature magic");
+    }
+    let mut blobs = Vec::new();
+    for i in 0..be32(superblob, 8)? as usize {
+        let slot = be32(superblob, 12 + i * 8)?;
+        let offset = be32(superblob, 16 + i * 8)? as usize;
+        let length = be32(superblob, offset + 4)? as usize;
+        let blob = superblob.get(offset..offset + length).context("Truncated signature blob")?;
+        blobs.push((slot, blob.to_vec()));
+    }
+    Ok(Some(blobs))
+}
+
+fn find_code_directory(macho: &MachO) -> Result<Option<Vec<u8>>> {
+    Ok(signature_blobs(macho)?.and_then(|blobs| {
+        blobs
+            .into_iter()
+            .find(|(slot, _)| *slot == CSSLOT_CODEDIRECTORY)
+            .map(|(_, blob)| blob)
+    }))
+}
+
+/// Verifies a Mach-O signature, returning the problems found
+fn verify_macho(data: &[u8], name: &str, special_slots: &BTreeMap<usize, Vec<u8>>) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let (_, slices) = macho::split_slices(data)?;
+    for slice in slices {
+        let arch = macho::arch_name(slice.cputype);
+        let macho = MachO::parse(slice.data)?;
+        let Some(blobs) = signature_blobs(&macho)? else {
+            problems.push(format!("{} ({}): code object is not signed at all", name, arch));
+            continue;
+        };
+        let Some((_, cd)) = blobs.iter().find(|(slot, _)| *slot == CSSLOT_CODEDIRECTORY) else {
+            problems.push(format!("{} ({}): signature has no code directory", name, arch));
+            continue;
+        };
+        // The fixed fields up to the page size are present in every code directory version
+        if cd.len() < 44 {
+            problems.push(format!("{} ({}): code directory is truncated", name, arch));
+            continue;
+        }
+        let be32 = |at: usize| u32::from_be_bytes(cd[at..at + 4].try_into().unwrap()) as usize;
+        let hash_offset = be32(16);
+        let special_count = be32(24);
+        let code_slots = be32(28);
+        let code_limit = be32(32);
+        if cd[36] as usize != HASH_SIZE || cd[37] != HASH_TYPE_SHA256 {
+            problems.push(format!("{} ({}): unsupported code directory hash type", name, arch));
+            continue;
+        }
+        if !(MIN_PAGE_SHIFT..=MAX_PAGE_SHIFT).contains(&cd[39]) {
+            problems.push(format!("{} ({}): unsupported code directory page size", name, arch));
+            continue;
+        }
+        let page_size = 1usize << cd[39];
+        let hashes_in_bounds = hash_offset
+            .checked_sub(special_count * HASH_SIZE)
+            .is_some_and(|start| start >= 44 && hash_offset + code_slots * HASH_SIZE <= cd.len());
+        if !hashes_in_bounds {
+            problems.push(format!("{} ({}): code directory hashes are out of bounds", name, arch));
+            continue;
+        }
+        let slot_hash = |index: isize| {
+            let at = (hash_offset as isize + index * HASH_SIZE as isize) as usize;
+            &cd[at..at + HASH_SIZE]
+        };
+
+        let code = &macho.data[..code_limit.min(macho.data.len())];
+        if code.chunks(page_size).count() != code_slots {
+            problems.push(format!("{} ({}): code slot count mismatch", name, arch));
+            continue;
+        }
+        if let Some(page) = code
+            .chunks(page_size)
+            .enumerate()
+            .find(|(i, page)| Sha256::digest(page).as_slice() != slot_hash(*i as isize))
+            .map(|(i, _)| i)
+        {
+            problems.push(format!("{} ({}): code page {} was modified", name, arch, page));
+        }
+
+        let mut expected = special_slots.clone();
+        for (slot, blob) in &blobs {
+            if *slot != CSSLOT_CODEDIRECTORY && (*slot as usize) < 0x1000 && *slot != CSSLOT_SIGNATURESLOT {
+                expected.insert(*slot as usize, Sha256::digest(blob).to_vec());
+            }
+        }
+        for (slot, hash) in expected {
+            if slot > special_count {
+                problems.push(format!("{} ({}): special slot {} is not sealed", name, arch, slot));
+            } else if slot_hash(-(slot as isize)) != hash.as_slice() {
+                problems.push(format!("{} ({}): special slot {} hash mismatch", name, arch, slot));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Verifies the signature and sealed resources of an app or framework bundle, returning the
+/// problems found
+pub fn verify_bundle(app_path: &Path) -> Result<Vec<String>> {
+    let bundle = Bundle::open(app_path)?;
+    let resources_path = bundle.root.join("_CodeSignature").join("CodeResources");
+    let resources_data = fs::read(&resources_path).context("Bundle has no _CodeSignature/CodeResources")?;
+    let resources = Value::from_reader(std::io::Cursor::new(&resources_data))
+        .context("Invalid CodeResources")?;
+    let files2 = resources
+        .as_dictionary()
+        .and_then(|d| d.get("files2"))
+        .and_then(Value::as_dictionary)
+        .context("CodeResources has no files2")?;
+
+    let mut problems = Vec::new();
+    let executable = bundle.root.join(&bundle.executable);
+    let executable_data = fs::read(&executable).context(format!("Failed to read {}", executable.display()))?;
+    if macho::is_macho(&executable_data) {
+        let mut special = BTreeMap::new();
+        special.insert(CSSLOT_INFOSLOT, Sha256::digest(fs::read(&bundle.info_plist)?).to_vec());
+        special.insert(CSSLOT_RESOURCEDIR, Sha256::digest(&resources_data).to_vec());
+        problems.extend(verify_macho(&executable_data, &bundle.executable, &special)?);
+    } else {
+        println!("Warning: main executable is not a Mach-O binary, only resources were checked");
+    }
+
+    let mut entries = Vec::new();
+    collect_files(&bundle.root, &bundle.root, &mut entries)?;
+    let mut seen = std::collections::HashSet::new();
+    for (relative, path) in entries {
+        if relative == bundle.executable || relative.starts_with("_CodeSignature/") {
+            continue;
+        }
+        let omitted = match_rule(RULES2, &relative).is_none_or(|r| r.omit);
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.is_dir() && !is_bundle_dir(&path) {
+            continue;
+        }
+        let Some(sealed) = files2.get(&relative).and_then(Value::as_dictionary) else {
+            if !omitted {
+                problems.push(format!("{}: file added after signing", relative));
+            }
+            continue;
+        };
+        seen.insert(relative.clone());
+
+        if let Some(target) = sealed.get("symlink").and_then(Value::as_string) {
+            let actual = fs::read_link(&path).map(|t| t.to_string_lossy().to_string()).unwrap_or_default();
+            if actual != target {
+                problems.push(format!("{}: symlink target changed", relative));
+            }
+        } else if let Some(cdhash) = sealed.get("cdhash").and_then(Value::as_data) {
+            let data = if metadata.is_dir() {
+                problems.extend(
+                    verify_bundle(&path)?
+                        .into_iter()
+                        .map(|p| format!("{}: {}", relative, p)),
+                );
+                let nested = Bundle::open(&path)?;
+                fs::read(nested.root.join(&nested.executable))?
+            } else {
+                let data = fs::read(&path)?;
+                problems.extend(verify_macho(&data, &relative, &BTreeMap::new())?);
+                data
+            };
+            let actual = nested_code_entry(&data)?
+                .and_then(|v| v.as_dictionary().and_then(|d| d.get("cdhash")).cloned())
+                .and_then(|v| v.into_data());
+            if actual.as_deref() != Some(cdhash) {
+                problems.push(format!("{}: nested code was modified", relative));
+            }
+        } else if let Some(hash2) = sealed.get("hash2").and_then(Value::as_data) {
+            let data = fs::read(&path)?;
+            if Sha256::digest(&data).as_slice() != hash2 {
+                problems.push(format!("{}: file modified", relative));
+            }
+        }
+    }
+    for (relative, sealed) in files2 {
+        let optional = sealed
+            .as_dictionary()
+            .and_then(|d| d.get("optional"))
+            .and_then(Value::as_boolean)
+            .unwrap_or(false);
+        if !seen.contains(relative) && !optional {
+            problems.push(format!("{}: file missing", relative));
+        }
+    }
+    Ok(problems)
+}
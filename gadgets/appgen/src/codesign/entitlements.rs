@@ -0,0 +1,193 @@
+//! Entitlements validation and the blobs they are embedded as.
+//!
+//! Signatures carry the entitlements twice: as the XML plist (slot 5) and in the
+//! DER encoding newer systems prefer (slot 7).
+
+use anyhow::Result;
+use plist::{Dictionary, Value};
+
+const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade_7171;
+const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade_7172;
+
+pub const CSSLOT_ENTITLEMENTS: u32 = 5;
+pub const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+
+/// Value type an entitlement must have
+#[derive(Clone, Copy)]
+enum Kind {
+    Boolean,
+    String,
+    StringArray,
+    StringOrArray,
+}
+
+impl Kind {
+    fn matches(self, value: &Value) -> bool {
+        let string_array =
+            |v: &Value| v.as_array().is_some_and(|items| items.iter().all(|i| i.as_string().is_some()));
+        match self {
+            Kind::Boolean => value.as_boolean().is_some(),
+            Kind::String => value.as_string().is_some(),
+            Kind::StringArray => string_array(value),
+            Kind::StringOrArray => value.as_string().is_some() || string_array(value),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::Boolean => "a boolean",
+            Kind::String => "a string",
+            Kind::StringArray => "an array of strings",
+            Kind::StringOrArray => "a string or an array of strings",
+        }
+    }
+}
+
+/// Entitlements appgen knows about, with the type of their value
+const KNOWN_ENTITLEMENTS: &[(&str, Kind)] = &[
+    ("com.apple.security.app-sandbox", Kind::Boolean),
+    ("com.apple.security.inherit", Kind::Boolean),
+    ("com.apple.security.get-task-allow", Kind::Boolean),
+    ("com.apple.security.network.client", Kind::Boolean),
+    ("com.apple.security.network.server", Kind::Boolean),
+    ("com.apple.security.device.camera", Kind::Boolean),
+    ("com.apple.security.device.audio-input", Kind::Boolean),
+    ("com.apple.security.device.microphone", Kind::Boolean),
+    ("com.apple.security.device.usb", Kind::Boolean),
+    ("com.apple.security.device.bluetooth", Kind::Boolean),
+    ("com.apple.security.device.serial", Kind::Boolean),
+    ("com.apple.security.print", Kind::Boolean),
+    ("com.apple.security.personal-information.addressbook", Kind::Boolean),
+    ("com.apple.security.personal-information.calendars", Kind::Boolean),
+    ("com.apple.security.personal-information.location", Kind::Boolean),
+    ("com.apple.security.personal-information.photos-library", Kind::Boolean),
+    ("com.apple.security.files.user-selected.read-only", Kind::Boolean),
+    ("com.apple.security.files.user-selected.read-write", Kind::Boolean),
+    ("com.apple.security.files.user-selected.executable", Kind::Boolean),
+    ("com.apple.security.files.downloads.read-only", Kind::Boolean),
+    ("com.apple.security.files.downloads.read-write", Kind::Boolean),
+    ("com.apple.security.files.bookmarks.app-scope", Kind::Boolean),
+    ("com.apple.security.files.bookmarks.document-scope", Kind::Boolean),
+    ("com.apple.security.assets.pictures.read-only", Kind::Boolean),
+    ("com.apple.security.assets.pictures.read-write", Kind::Boolean),
+    ("com.apple.security.assets.music.read-only", Kind::Boolean),
+    ("com.apple.security.assets.music.read-write", Kind::Boolean),
+    ("com.apple.security.assets.movies.read-only", Kind::Boolean),
+    ("com.apple.security.assets.movies.read-write", Kind::Boolean),
+    ("com.apple.security.automation.apple-events", Kind::Boolean),
+    ("com.apple.security.cs.allow-jit", Kind::Boolean),
+    ("com.apple.security.cs.allow-unsigned-executable-memory", Kind::Boolean),
+    ("com.apple.security.cs.allow-dyld-environment-variables", Kind::Boolean),
+    ("com.apple.security.cs.disable-library-validation", Kind::Boolean),
+    ("com.apple.security.cs.disable-executable-page-protection", Kind::Boolean),
+    ("com.apple.security.cs.debugger", Kind::Boolean),
+    ("com.apple.security.application-groups", Kind::StringArray),
+    ("com.apple.security.temporary-exception.apple-events", Kind::StringOrArray),
+    ("com.apple.security.temporary-exception.files.absolute-path.read-only", Kind::StringArray),
+    ("com.apple.security.temporary-exception.files.absolute-path.read-write", Kind::StringArray),
+    ("com.apple.security.temporary-exception.files.home-relative-path.read-only", Kind::StringArray),
+    ("com.apple.security.temporary-exception.files.home-relative-path.read-write", Kind::StringArray),
+    ("com.apple.security.temporary-exception.mach-lookup.global-name", Kind::StringArray),
+    ("keychain-access-groups", Kind::StringArray),
+    ("com.apple.application-identifier", Kind::String),
+    ("com.apple.developer.team-identifier", Kind::String),
+    ("com.apple.developer.associated-domains", Kind::StringArray),
+];
+
+/// Checks the entitlements against the known keys, printing warnings for unknown
+/// keys and failing on values of the wrong type
+pub fn validate(entitlements: &Dictionary, hardened_runtime: bool) -> Result<()> {
+    let mut errors = Vec::new();
+    for (key, value) in entitlements {
+        match KNOWN_ENTITLEMENTS.iter().find(|(known, _)| known == key) {
+            Some((_, kind)) if !kind.matches(value) => {
+                errors.push(format!("Entitlement {} must be {}", key, kind.describe()));
+            }
+            Some(_) => {}
+            None => println!("Warning: unknown entitlement {}", key),
+        }
+        if key.starts_with("com.apple.security.cs.") && !hardened_runtime {
+            println!("Warning: {} only has an effect with --hardened-runtime", key);
+        }
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("Invalid entitlements:\n  {}", errors.join("\n  "));
+    }
+    Ok(())
+}
+
+/// The XML entitlements blob
+pub fn xml_blob(entitlements: &Dictionary) -> Result<Vec<u8>> {
+    let mut xml = Vec::new();
+    plist::to_writer_xml(&mut xml, &Value::Dictionary(entitlements.clone()))?;
+    let mut blob = Vec::with_capacity(8 + xml.len());
+    blob.extend_from_slice(&CSMAGIC_EMBEDDED_ENTITLEMENTS.to_be_bytes());
+    blob.extend_from_slice(&((8 + xml.len()) as u32).to_be_bytes());
+    blob.extend_from_slice(&xml);
+    Ok(blob)
+}
+
+/// The DER entitlements blob: `[APPLICATION 16] { INTEGER 1, [16] { dictionary } }`
+pub fn der_blob(entitlements: &Dictionary) -> Result<Vec<u8>> {
+    let mut body = der(0x02, &[1]);
+    body.extend(der(0xb0, &der_value(&Value::Dictionary(entitlements.clone()))?));
+    let encoded = der(0x70, &body);
+    let mut blob = Vec::with_capacity(8 + encoded.len());
+    blob.extend_from_slice(&CSMAGIC_EMBEDDED_DER_ENTITLEMENTS.to_be_bytes());
+    blob.extend_from_slice(&((8 + encoded.len()) as u32).to_be_bytes());
+    blob.extend_from_slice(&encoded);
+    Ok(blob)
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let length = content.len();
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes: Vec<u8> = length.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn der_value(value: &Value) -> Result<Vec<u8>> {
+    Ok(match value {
+        Value::Boolean(b) => der(0x01, &[if *b { 0xff } else { 0 }]),
+        Value::String(s) => der(0x0c, s.as_bytes()),
+        Value::Integer(i) => {
+            let n = i.as_signed().unwrap_or(i64::MAX);
+            let bytes = n.to_be_bytes();
+            // Minimal two's complement encoding
+            let mut start = 0;
+            while start < 7
+                && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+                    || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+            {
+                start += 1;
+            }
+            der(0x02, &bytes[start..])
+        }
+        Value::Array(items) => {
+            let mut content = Vec::new();
+            for item in items {
+                content.extend(der_value(item)?);
+            }
+            der(0x30, &content)
+        }
+        Value::Dictionary(dict) => {
+            let mut entries: Vec<_> = dict.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let mut content = Vec::new();
+            for (key, value) in entries {
+                let mut pair = der(0x0c, key.as_bytes());
+                pair.extend(der_value(value)?);
+                content.extend(der(0x30, &pair));
+            }
+            der(0x31, &content)
+        }
+        _ => anyhow::bail!("Entitlement values must be booleans, strings, integers, arrays or dictionaries"),
+    })
+}
@@ -0,0 +1,66 @@
+//! Shell completions and the man page, generated from the clap definition of the CLI.
+
+use anyhow::{Context, Result};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Options of the `completions` subcommand
+#[derive(clap::Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+/// Options of the `man` subcommand
+#[derive(clap::Args, Debug)]
+pub struct ManArgs {
+    /// Directory receiving appgen.1 and one appgen-<subcommand>.1 page per subcommand,
+    /// instead of printing appgen.1 to standard output
+    #[arg(short, long, value_name = "DIR")]
+    output: Option<String>,
+}
+
+/// Prints the completion script of a shell
+pub fn print_completions(args: &CompletionsArgs) -> Result<()> {
+    let mut command = crate::Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut std::io::stdout());
+    Ok(())
+}
+
+/// Prints or writes the roff man pages
+pub fn write_man(args: &ManArgs) -> Result<()> {
+    let command = crate::Cli::command();
+    let Some(output) = &args.output else {
+        std::io::stdout().write_all(&render(command)?)?;
+        return Ok(());
+    };
+    let dir = Path::new(output);
+    fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    let name = command.get_name().to_string();
+    let version = command.get_version().unwrap_or_default().to_string();
+    let mut pages = vec![(format!("{}.1", name), command.clone())];
+    for subcommand in command.get_subcommands().filter(|s| s.get_name() != "help") {
+        // Named like git's pages so `man appgen-dmg` finds them
+        let page_name = format!("{}-{}", name, subcommand.get_name());
+        pages.push((format!("{}.1", page_name), subcommand.clone().name(page_name).version(version.clone())));
+    }
+    for (file_name, page) in pages {
+        let path = dir.join(file_name);
+        fs::write(&path, render(page)?).context(format!("Failed to write man page: {}", path.display()))?;
+        println!("Successfully wrote man page at: {}", path.display());
+    }
+    Ok(())
+}
+
+fn render(command: clap::Command) -> Result<Vec<u8>> {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(command)
+        .render(&mut page)
+        .context("Failed to render man page")?;
+    Ok(page)
+}
@@ -0,0 +1,346 @@
+//! Comparing two bundles.
+//!
+//! Reports top level Info.plist keys that were added, removed or changed, files that were
+//! added, removed or changed (by SHA-256, symlink target or kind), permission changes and the
+//! architectures of Mach-O files present in both bundles.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Map, Value as Json, json};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::macho;
+
+/// Options of the `diff` subcommand
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// The bundle to compare from
+    #[arg(value_name = "OLD_APP")]
+    old: String,
+
+    /// The bundle to compare to
+    #[arg(value_name = "NEW_APP")]
+    new: String,
+
+    /// Print the differences as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+/// A file system entry of a bundle
+enum Entry {
+    File {
+        sha256: String,
+        size: u64,
+        mode: u32,
+        /// Architectures of a Mach-O file, empty for other files
+        archs: Vec<String>,
+    },
+    Directory {
+        mode: u32,
+    },
+    Symlink {
+        target: String,
+    },
+}
+
+impl Entry {
+    fn mode(&self) -> Option<u32> {
+        match self {
+            Entry::File { mode, .. } | Entry::Directory { mode } => Some(*mode),
+            Entry::Symlink { .. } => None,
+        }
+    }
+
+    /// Whether the contents differ, ignoring permissions
+    fn differs(&self, other: &Entry) -> bool {
+        match (self, other) {
+            (Entry::File { sha256: a, .. }, Entry::File { sha256: b, .. }) => a != b,
+            (Entry::Directory { .. }, Entry::Directory { .. }) => false,
+            (Entry::Symlink { target: a }, Entry::Symlink { target: b }) => a != b,
+            _ => true,
+        }
+    }
+
+    fn json(&self) -> Json {
+        match self {
+            Entry::File { sha256, size, .. } => json!({ "kind": "file", "sha256": sha256, "size": size }),
+            Entry::Directory { .. } => json!({ "kind": "directory" }),
+            Entry::Symlink { target } => json!({ "kind": "symlink", "target": target }),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Entry::File { sha256, size, .. } => format!("sha256 {}, {} bytes", &sha256[..12], size),
+            Entry::Directory { .. } => "directory".to_string(),
+            Entry::Symlink { target } => format!("symlink to {}", target),
+        }
+    }
+}
+
+/// Differences between two bundles
+#[derive(Default)]
+struct BundleDiff {
+    plist_added: Vec<(String, plist::Value)>,
+    plist_removed: Vec<(String, plist::Value)>,
+    plist_changed: Vec<(String, plist::Value, plist::Value)>,
+    files_added: Vec<(String, Entry)>,
+    files_removed: Vec<(String, Entry)>,
+    files_changed: Vec<(String, Entry, Entry)>,
+    permissions: Vec<(String, u32, u32)>,
+    architectures: Vec<(String, Vec<String>, Vec<String>)>,
+}
+
+impl BundleDiff {
+    fn is_empty(&self) -> bool {
+        self.plist_added.is_empty()
+            && self.plist_removed.is_empty()
+            && self.plist_changed.is_empty()
+            && self.files_added.is_empty()
+            && self.files_removed.is_empty()
+            && self.files_changed.is_empty()
+            && self.permissions.is_empty()
+            && self.architectures.is_empty()
+    }
+}
+
+/// Compares two bundles and prints the differences
+pub fn diff_bundles(args: &DiffArgs) -> Result<()> {
+    let (old, new) = (Path::new(&args.old), Path::new(&args.new));
+    let mut diff = BundleDiff::default();
+
+    let old_info = crate::read_info_plist(old)?;
+    let new_info = crate::read_info_plist(new)?;
+    for (key, value) in &old_info {
+        match new_info.get(key) {
+            None => diff.plist_removed.push((key.clone(), value.clone())),
+            Some(new_value) if new_value != value => {
+                diff.plist_changed.push((key.clone(), value.clone(), new_value.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, value) in &new_info {
+        if !old_info.contains_key(key) {
+            diff.plist_added.push((key.clone(), value.clone()));
+        }
+    }
+
+    let old_entries = read_entries(old)?;
+    let mut new_entries = read_entries(new)?;
+    for (path, old_entry) in old_entries {
+        let Some(new_entry) = new_entries.remove(&path) else {
+            diff.files_removed.push((path, old_entry));
+            continue;
+        };
+        if let (Some(old_mode), Some(new_mode)) = (old_entry.mode(), new_entry.mode())
+            && old_mode != new_mode
+            && std::mem::discriminant(&old_entry) == std::mem::discriminant(&new_entry)
+        {
+            diff.permissions.push((path.clone(), old_mode, new_mode));
+        }
+        if let (Entry::File { archs: old_archs, .. }, Entry::File { archs: new_archs, .. }) = (&old_entry, &new_entry)
+            && old_archs != new_archs
+        {
+            diff.architectures
+                .push((path.clone(), old_archs.clone(), new_archs.clone()));
+        }
+        if old_entry.differs(&new_entry) {
+            diff.files_changed.push((path, old_entry, new_entry));
+        }
+    }
+    diff.files_added.extend(new_entries);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff_json(args, &diff))?);
+    } else {
+        print_diff(args, &diff);
+    }
+    Ok(())
+}
+
+/// Entries of a bundle keyed by their path relative to it
+fn read_entries(app_path: &Path) -> Result<BTreeMap<String, Entry>> {
+    fn walk(root: &Path, dir: &Path, entries: &mut BTreeMap<String, Entry>) -> Result<()> {
+        let mut children = fs::read_dir(dir)
+            .context(format!("Failed to read directory: {}", dir.display()))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        children.sort_by_key(|e| e.file_name());
+        for child in children {
+            let path = child.path();
+            let relative = path.strip_prefix(root).unwrap().to_string_lossy().to_string();
+            let metadata = fs::symlink_metadata(&path)?;
+            let entry = if metadata.is_symlink() {
+                Entry::Symlink {
+                    target: fs::read_link(&path)?.to_string_lossy().to_string(),
+                }
+            } else if metadata.is_dir() {
+                walk(root, &path, entries)?;
+                Entry::Directory { mode: mode(&metadata) }
+            } else {
+                let data = fs::read(&path).context(format!("Failed to read {}", path.display()))?;
+                Entry::File {
+                    sha256: hex(&Sha256::digest(&data)),
+                    size: data.len() as u64,
+                    mode: mode(&metadata),
+                    archs: architectures(&data).context(format!("Invalid Mach-O file: {}", path.display()))?,
+                }
+            };
+            entries.insert(relative, entry);
+        }
+        Ok(())
+    }
+    if !app_path.is_dir() {
+        anyhow::bail!("App bundle not found: {}", app_path.display());
+    }
+    let mut entries = BTreeMap::new();
+    walk(app_path, app_path, &mut entries)?;
+    Ok(entries)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+/// Architectures of a Mach-O file, empty for other files
+fn architectures(data: &[u8]) -> Result<Vec<String>> {
+    if !macho::is_macho(data) {
+        return Ok(Vec::new());
+    }
+    let (_, slices) = macho::split_slices(data)?;
+    Ok(slices.iter().map(|slice| macho::arch_name(slice.cputype)).collect())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Plist value as JSON, dates as ISO 8601 and data as base64
+fn plist_json(value: &plist::Value) -> Json {
+    match value {
+        plist::Value::Array(items) => Json::Array(items.iter().map(plist_json).collect()),
+        plist::Value::Dictionary(dict) => Json::Object(dict.iter().map(|(k, v)| (k.clone(), plist_json(v))).collect()),
+        plist::Value::Boolean(b) => json!(b),
+        plist::Value::Integer(i) => match i.as_signed() {
+            Some(i) => json!(i),
+            None => json!(i.as_unsigned()),
+        },
+        plist::Value::Real(r) => json!(r),
+        plist::Value::String(s) => json!(s),
+        plist::Value::Date(date) => json!(date.to_xml_format()),
+        plist::Value::Data(data) => json!(BASE64.encode(data)),
+        plist::Value::Uid(uid) => json!(uid.get()),
+        _ => Json::Null,
+    }
+}
+
+fn diff_json(args: &DiffArgs, diff: &BundleDiff) -> Json {
+    let values = |entries: &[(String, plist::Value)]| -> Map<String, Json> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.clone(), plist_json(value)))
+            .collect()
+    };
+    let entries = |entries: &[(String, Entry)]| -> Vec<Json> {
+        entries
+            .iter()
+            .map(|(path, entry)| {
+                let mut object = entry.json();
+                object["path"] = json!(path);
+                object
+            })
+            .collect()
+    };
+    json!({
+        "old": args.old,
+        "new": args.new,
+        "info_plist": {
+            "added": values(&diff.plist_added),
+            "removed": values(&diff.plist_removed),
+            "changed": diff.plist_changed.iter().map(|(key, old, new)| {
+                (key.clone(), json!({ "old": plist_json(old), "new": plist_json(new) }))
+            }).collect::<Map<String, Json>>(),
+        },
+        "files": {
+            "added": entries(&diff.files_added),
+            "removed": entries(&diff.files_removed),
+            "changed": diff.files_changed.iter().map(|(path, old, new)| {
+                json!({ "path": path, "old": old.json(), "new": new.json() })
+            }).collect::<Vec<_>>(),
+        },
+        "permissions": diff.permissions.iter().map(|(path, old, new)| {
+            json!({ "path": path, "old": format!("{:o}", old), "new": format!("{:o}", new) })
+        }).collect::<Vec<_>>(),
+        "architectures": diff.architectures.iter().map(|(path, old, new)| {
+            json!({ "path": path, "old": old, "new": new })
+        }).collect::<Vec<_>>(),
+    })
+}
+
+fn print_diff(args: &DiffArgs, diff: &BundleDiff) {
+    if diff.is_empty() {
+        println!("{} and {} are identical", args.old, args.new);
+        return;
+    }
+    let text = |value: &plist::Value| plist_json(value).to_string();
+    let archs = |archs: &[String]| {
+        if archs.is_empty() {
+            "not Mach-O".to_string()
+        } else {
+            archs.join(", ")
+        }
+    };
+
+    if !(diff.plist_added.is_empty() && diff.plist_removed.is_empty() && diff.plist_changed.is_empty()) {
+        println!("Info.plist:");
+        for (key, value) in &diff.plist_added {
+            println!("  + {} = {}", key, text(value));
+        }
+        for (key, value) in &diff.plist_removed {
+            println!("  - {} = {}", key, text(value));
+        }
+        for (key, old, new) in &diff.plist_changed {
+            println!("  ~ {}: {} -> {}", key, text(old), text(new));
+        }
+    }
+    if !(diff.files_added.is_empty() && diff.files_removed.is_empty() && diff.files_changed.is_empty()) {
+        println!("Files:");
+        for (path, entry) in &diff.files_added {
+            println!("  + {} ({})", path, entry.describe());
+        }
+        for (path, entry) in &diff.files_removed {
+            println!("  - {} ({})", path, entry.describe());
+        }
+        for (path, old, new) in &diff.files_changed {
+            println!("  ~ {} ({} -> {})", path, old.describe(), new.describe());
+        }
+    }
+    if !diff.permissions.is_empty() {
+        println!("Permissions:");
+        for (path, old, new) in &diff.permissions {
+            println!("  ~ {}: {:o} -> {:o}", path, old, new);
+        }
+    }
+    if !diff.architectures.is_empty() {
+        println!("Architectures:");
+        for (path, old, new) in &diff.architectures {
+            println!("  ~ {}: {} -> {}", path, archs(old), archs(new));
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Pure Rust DMG creation, so that disk images can be built without `hdiutil`.
+//!
+//! The image is an HFSX volume (see [`hfs`]) holding the app bundle, a symlink to
+//! `/Applications`, an optional hidden background image and a `.DS_Store` describing
+//! the Finder window (see [`ds_store`]), wrapped into a UDIF container (see [`udif`]).
+
+mod ds_store;
+mod hfs;
+mod udif;
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ds_store::{Record, RecordValue};
+use hfs::HfsBuilder;
+
+/// Name of the hidden folder holding the background image inside the volume
+const BACKGROUND_DIR: &str = ".background";
+
+/// Options of the `dmg` subcommand
+#[derive(clap::Args, Debug)]
+pub struct DmgArgs {
+    /// Path to the .app bundle to pack
+    #[arg(value_name = "APP")]
+    app: String,
+
+    /// Output path of the disk image (defaults to {app name}.dmg next to the bundle)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Volume name shown in Finder (defaults to the app name)
+    #[arg(long)]
+    volume_name: Option<String>,
+
+    /// Optional background image of the Finder window (PNG recommended)
+    #[arg(long)]
+    background: Option<String>,
+
+    /// Finder window bounds when the volume is opened
+    /// Format: X,Y,WIDTH,HEIGHT
+    #[arg(long, default_value = "200,120,640,400")]
+    window: String,
+
+    /// Size of the icons in the Finder window
+    #[arg(long, default_value_t = 128)]
+    icon_size: u16,
+
+    /// Position of the app icon in the Finder window
+    /// Format: X,Y
+    #[arg(long, default_value = "160,190")]
+    app_position: String,
+
+    /// Position of the /Applications symlink in the Finder window
+    /// Format: X,Y
+    #[arg(long, default_value = "480,190")]
+    applications_position: String,
+
+    /// Store the image uncompressed (UDRO) instead of zlib compressed (UDZO)
+    #[arg(long, default_value_t = false)]
+    no_compress: bool,
+}
+
+/// Creates the disk image described by `args`, returning its path
+pub fn create_dmg(args: &DmgArgs) -> Result<PathBuf> {
+    let app_path = Path::new(&args.app);
+    if !app_path.is_dir() {
+        anyhow::bail!("App bundle not found: {}", args.app);
+    }
+    let app_file_name = app_path
+        .file_name()
+        .context("Invalid app bundle path")?
+        .to_string_lossy()
+        .to_string();
+    let app_name = app_file_name
+        .strip_suffix(".app")
+        .unwrap_or(&app_file_name)
+        .to_string();
+
+    let [win_x, win_y, win_w, win_h] = parse_numbers::<4>(&args.window, "--window")?;
+    let app_pos = parse_numbers::<2>(&args.app_position, "--app-position")?;
+    let apps_pos = parse_numbers::<2>(&args.applications_position, "--applications-position")?;
+
+    let volume_name = args.volume_name.clone().unwrap_or_else(|| app_name.clone());
+    let mut volume = HfsBuilder::new(&volume_name);
+    let root = volume.root();
+
+    volume
+        .add_tree(root, &app_file_name, app_path)
+        .context("Failed to add app bundle to the volume")?;
+    volume.add_symlink(root, "Applications", "/Applications");
+
+    let background_alias = match &args.background {
+        Some(background) => {
+            let source = Path::new(background);
+            if !source.is_file() {
+                anyhow::bail!("Background image not found: {}", background);
+            }
+            let file_name = source.file_name().unwrap().to_string_lossy().to_string();
+            let data = fs::read(source).context("Failed to read background image")?;
+            let dir_id = volume.add_folder(root, BACKGROUND_DIR, 0o755);
+            volume.set_invisible(dir_id);
+            let file_id = volume.add_file(dir_id, &file_name, data, 0o644);
+            Some(ds_store::alias_record(
+                &volume_name,
+                volume.create_date(),
+                dir_id,
+                BACKGROUND_DIR,
+                file_id,
+                &file_name,
+            ))
+        }
+        None => None,
+    };
+
+    let window_bounds = format!("{{{{{}, {}}}, {{{}, {}}}}}", win_x, win_y, win_w, win_h);
+    let records = vec![
+        Record::new(".", b"bwsp", RecordValue::Blob(ds_store::browser_window_settings(&window_bounds)?)),
+        Record::new(
+            ".",
+            b"icvp",
+            RecordValue::Blob(ds_store::icon_view_settings(args.icon_size, background_alias)?),
+        ),
+        Record::new(".", b"vSrn", RecordValue::Long(1)),
+        Record::new(".", b"vstl", RecordValue::Type(*b"icnv")),
+        Record::new(&app_file_name, b"Iloc", RecordValue::Blob(ds_store::icon_location(app_pos))),
+        Record::new("Applications", b"Iloc", RecordValue::Blob(ds_store::icon_location(apps_pos))),
+    ];
+    volume.add_file(root, ".DS_Store", ds_store::write_ds_store(records)?, 0o644);
+
+    let output = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => app_path.with_file_name(format!("{}.dmg", app_name)),
+    };
+    let image = volume.build()?;
+    let file = fs::File::create(&output)
+        .context(format!("Failed to create disk image: {}", output.display()))?;
+    udif::write_udif(&image, !args.no_compress, std::io::BufWriter::new(file))
+        .context("Failed to write disk image")?;
+
+    Ok(output)
+}
+
+/// Parses `N` comma separated numbers, such as `X,Y` positions
+fn parse_numbers<const N: usize>(value: &str, option: &str) -> Result<[u32; N]> {
+    let numbers = value
+        .split(',')
+        .map(|part| part.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!("Invalid number in {} value: {}", option, value))?;
+    numbers
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("{} expects {} comma separated numbers, got: {}", option, N, value))
+}
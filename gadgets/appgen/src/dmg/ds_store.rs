@@ -0,0 +1,290 @@
+//! `.DS_Store` writer for the Finder window layout of the disk image.
+//!
+//! The file is a buddy allocated store holding a single `DSDB` B-tree. Only a tree with
+//! one leaf node is written, which is plenty for the handful of records a DMG needs:
+//! window bounds (`bwsp`), icon view options (`icvp`), view style and icon positions.
+//! The background image is referenced through a classic (version 2) alias record.
+
+use anyhow::{Context, Result};
+use plist::Value;
+
+use super::hfs::Writer;
+
+const PAGE_SIZE: usize = 0x1000;
+/// Header bytes following the root block offsets, as written by Finder
+const HEADER_TRAILER: [u8; 16] = [
+    0x00, 0x00, 0x10, 0x0c, 0x00, 0x00, 0x00, 0x87, 0x00, 0x00, 0x20, 0x0b, 0x00, 0x00, 0x00, 0x00,
+];
+
+pub enum RecordValue {
+    Long(u32),
+    Type([u8; 4]),
+    Blob(Vec<u8>),
+}
+
+/// A property `code` of the item `file_name` (`.` being the folder itself)
+pub struct Record {
+    file_name: String,
+    code: [u8; 4],
+    value: RecordValue,
+}
+
+impl Record {
+    pub fn new(file_name: &str, code: &[u8; 4], value: RecordValue) -> Self {
+        Record { file_name: file_name.to_string(), code: *code, value }
+    }
+
+    fn write(&self, w: &mut Writer) {
+        let name: Vec<u16> = self.file_name.encode_utf16().collect();
+        w.u32(name.len() as u32);
+        for unit in name {
+            w.u16(unit);
+        }
+        w.bytes(&self.code);
+        match &self.value {
+            RecordValue::Long(v) => {
+                w.bytes(b"long");
+                w.u32(*v);
+            }
+            RecordValue::Type(v) => {
+                w.bytes(b"type");
+                w.bytes(v);
+            }
+            RecordValue::Blob(v) => {
+                w.bytes(b"blob");
+                w.u32(v.len() as u32);
+                w.bytes(v);
+            }
+        }
+    }
+}
+
+/// A simple buddy allocator over the 2^31 byte address space of the store
+struct Buddy {
+    free: Vec<Vec<u32>>,
+}
+
+impl Buddy {
+    fn new() -> Self {
+        let mut free = vec![Vec::new(); 32];
+        free[31].push(0);
+        Buddy { free }
+    }
+
+    /// Allocates a block, returning its address (offset | log2 size)
+    fn alloc(&mut self, size: usize) -> u32 {
+        let width = size.next_power_of_two().trailing_zeros().max(5) as usize;
+        let mut level = width;
+        while self.free[level].is_empty() {
+            level += 1;
+        }
+        self.free[level].sort_unstable();
+        let offset = self.free[level].remove(0);
+        while level > width {
+            level -= 1;
+            self.free[level].push(offset + (1 << level));
+        }
+        offset | width as u32
+    }
+}
+
+/// Serializes the records into a `.DS_Store` file
+pub fn write_ds_store(mut records: Vec<Record>) -> Result<Vec<u8>> {
+    records.sort_by(|a, b| {
+        a.file_name
+            .to_lowercase()
+            .cmp(&b.file_name.to_lowercase())
+            .then_with(|| a.code.cmp(&b.code))
+    });
+
+    let mut leaf = Writer::default();
+    leaf.u32(0); // no children, this is a leaf
+    leaf.u32(records.len() as u32);
+    for record in &records {
+        record.write(&mut leaf);
+    }
+    if leaf.0.len() > PAGE_SIZE {
+        anyhow::bail!("Too many .DS_Store records for a single B-tree node");
+    }
+
+    let mut buddy = Buddy::new();
+    buddy.alloc(32); // file header
+    let root = buddy.alloc(2048);
+    let dsdb = buddy.alloc(32);
+    let node = buddy.alloc(PAGE_SIZE);
+    let addresses = [root, dsdb, node];
+
+    let mut db = Writer::default();
+    db.u32(2); // block number of the root node
+    db.u32(0); // levels
+    db.u32(records.len() as u32);
+    db.u32(1); // nodes
+    db.u32(PAGE_SIZE as u32);
+
+    let mut bookkeeping = Writer::default();
+    bookkeeping.u32(addresses.len() as u32);
+    bookkeeping.u32(0);
+    for i in 0..256 {
+        bookkeeping.u32(addresses.get(i).copied().unwrap_or_default());
+    }
+    bookkeeping.u32(1); // table of contents
+    bookkeeping.u8(4);
+    bookkeeping.bytes(b"DSDB");
+    bookkeeping.u32(1);
+    for free in &buddy.free {
+        bookkeeping.u32(free.len() as u32);
+        let mut free = free.clone();
+        free.sort_unstable();
+        for offset in free {
+            bookkeeping.u32(offset);
+        }
+    }
+    if bookkeeping.0.len() > 2048 {
+        anyhow::bail!("Unexpected .DS_Store allocator state");
+    }
+
+    let block_offset = |address: u32| (address & !0x1f) as usize;
+    let block_size = |address: u32| 1usize << (address & 0x1f);
+    let end = addresses.iter().map(|a| block_offset(*a) + block_size(*a)).max().unwrap();
+
+    // Offsets inside the store are relative to the 4 byte magic at the start of the file
+    let mut store = vec![0u8; 4 + end];
+    store[..4].copy_from_slice(&1u32.to_be_bytes());
+    let mut file_header = Writer::default();
+    file_header.bytes(b"Bud1");
+    file_header.u32(block_offset(root) as u32);
+    file_header.u32(block_size(root) as u32);
+    file_header.u32(block_offset(root) as u32);
+    file_header.bytes(&HEADER_TRAILER);
+    store[4..4 + file_header.0.len()].copy_from_slice(&file_header.0);
+    for (address, data) in [(root, &bookkeeping.0), (dsdb, &db.0), (node, &leaf.0)] {
+        let at = 4 + block_offset(address);
+        store[at..at + data.len()].copy_from_slice(data);
+    }
+    Ok(store)
+}
+
+/// Browser window settings (`bwsp`), `bounds` being formatted as `{{x, y}, {w, h}}`
+pub fn browser_window_settings(bounds: &str) -> Result<Vec<u8>> {
+    let settings = plist::Dictionary::from_iter([
+        ("ContainerShowSidebar".to_string(), Value::Boolean(false)),
+        ("ShowPathbar".to_string(), Value::Boolean(false)),
+        ("ShowSidebar".to_string(), Value::Boolean(false)),
+        ("ShowStatusBar".to_string(), Value::Boolean(false)),
+        ("ShowTabView".to_string(), Value::Boolean(false)),
+        ("ShowToolbar".to_string(), Value::Boolean(false)),
+        ("SidebarWidth".to_string(), Value::Integer(180.into())),
+        ("WindowBounds".to_string(), Value::String(bounds.to_string())),
+    ]);
+    binary_plist(settings)
+}
+
+/// Icon view settings (`icvp`), optionally with a background image alias
+pub fn icon_view_settings(icon_size: u16, background_alias: Option<Vec<u8>>) -> Result<Vec<u8>> {
+    let mut settings = plist::Dictionary::from_iter([
+        ("arrangeBy".to_string(), Value::String("none".to_string())),
+        ("backgroundColorBlue".to_string(), Value::Real(1.0)),
+        ("backgroundColorGreen".to_string(), Value::Real(1.0)),
+        ("backgroundColorRed".to_string(), Value::Real(1.0)),
+        ("backgroundType".to_string(), Value::Integer(0.into())),
+        ("gridOffsetX".to_string(), Value::Real(0.0)),
+        ("gridOffsetY".to_string(), Value::Real(0.0)),
+        ("gridSpacing".to_string(), Value::Real(100.0)),
+        ("iconSize".to_string(), Value::Real(icon_size as f64)),
+        ("labelOnBottom".to_string(), Value::Boolean(true)),
+        ("showIconPreview".to_string(), Value::Boolean(true)),
+        ("showItemInfo".to_string(), Value::Boolean(false)),
+        ("textSize".to_string(), Value::Real(12.0)),
+        ("viewOptionsVersion".to_string(), Value::Integer(1.into())),
+    ]);
+    if let Some(alias) = background_alias {
+        settings.insert("backgroundType".to_string(), Value::Integer(2.into()));
+        settings.insert("backgroundImageAlias".to_string(), Value::Data(alias));
+    }
+    binary_plist(settings)
+}
+
+/// Icon location (`Iloc`) of an item in the window
+pub fn icon_location([x, y]: [u32; 2]) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.u32(x);
+    w.u32(y);
+    w.bytes(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00]);
+    w.0
+}
+
+/// Builds a version 2 alias record for `/{dir_name}/{file_name}` on the volume
+pub fn alias_record(
+    volume_name: &str,
+    volume_date: u32,
+    dir_id: u32,
+    dir_name: &str,
+    file_id: u32,
+    file_name: &str,
+) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.zeros(4); // application creator
+    w.u16(0); // record size, patched below
+    w.u16(2); // version
+    w.u16(0); // kind: file
+    pascal_string(&mut w, volume_name, 27);
+    w.u32(volume_date);
+    w.bytes(b"H+");
+    w.u16(5); // disk type: ejectable
+    w.u32(dir_id);
+    pascal_string(&mut w, file_name, 63);
+    w.u32(file_id);
+    w.u32(volume_date); // file creation date
+    w.zeros(8); // file type and creator
+    w.u16(0xffff); // levels from
+    w.u16(0xffff); // levels to
+    w.u32(0); // volume attributes
+    w.u16(0); // volume file system id
+    w.zeros(10);
+
+    tagged(&mut w, 0, dir_name.as_bytes());
+    tagged(&mut w, 1, &dir_id.to_be_bytes());
+    tagged(&mut w, 14, &unicode_name(file_name));
+    tagged(&mut w, 15, &unicode_name(volume_name));
+    tagged(&mut w, 18, format!("/{}/{}", dir_name, file_name).as_bytes());
+    tagged(&mut w, 19, format!("/Volumes/{}", volume_name).as_bytes());
+    w.u16(0xffff);
+    w.u16(0);
+
+    let size = w.0.len() as u16;
+    w.0[4..6].copy_from_slice(&size.to_be_bytes());
+    w.0
+}
+
+fn pascal_string(w: &mut Writer, s: &str, capacity: usize) {
+    let bytes: Vec<u8> = s.bytes().take(capacity).collect();
+    w.u8(bytes.len() as u8);
+    w.bytes(&bytes);
+    w.zeros(capacity - bytes.len());
+}
+
+fn unicode_name(s: &str) -> Vec<u8> {
+    let units: Vec<u16> = s.encode_utf16().collect();
+    let mut w = Writer::default();
+    w.u16(units.len() as u16);
+    for unit in units {
+        w.u16(unit);
+    }
+    w.0
+}
+
+fn tagged(w: &mut Writer, tag: u16, data: &[u8]) {
+    w.u16(tag);
+    w.u16(data.len() as u16);
+    w.bytes(data);
+    if data.len() % 2 == 1 {
+        w.u8(0);
+    }
+}
+
+fn binary_plist(dict: plist::Dictionary) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    plist::to_writer_binary(&mut out, &Value::Dictionary(dict))
+        .context("Failed to serialize .DS_Store property list")?;
+    Ok(out)
+}
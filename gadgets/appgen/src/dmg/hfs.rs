@@ -0,0 +1,610 @@
+//! A minimal HFSX (case-sensitive HFS+) volume writer.
+//!
+//! Only the subset needed for a read-only distribution image is produced (Apple TN1150):
+//! - a single volume header plus its alternate copy, 4 KiB allocation blocks
+//! - an allocation bitmap, an empty extents overflow B-tree and a catalog B-tree
+//!   using binary key comparison (HFSX), so no Unicode case folding tables are needed
+//! - folders, regular files and symlinks, each file stored in one contiguous extent
+//!
+//! There is no journal, attributes file (extended attributes) or resource fork support.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BLOCK_SIZE: u64 = 4096;
+const CATALOG_NODE_SIZE: usize = 8192;
+const EXTENTS_NODE_SIZE: usize = 4096;
+/// Seconds between the HFS epoch (1904-01-01) and the Unix epoch
+const HFS_EPOCH_OFFSET: u64 = 2_082_844_800;
+
+const ROOT_PARENT_ID: u32 = 1;
+const ROOT_FOLDER_ID: u32 = 2;
+const FIRST_USER_CATALOG_ID: u32 = 16;
+
+const FOLDER_RECORD: u16 = 1;
+const FILE_RECORD: u16 = 2;
+const FOLDER_THREAD_RECORD: u16 = 3;
+const FILE_THREAD_RECORD: u16 = 4;
+
+const S_IFDIR: u16 = 0o040000;
+const S_IFREG: u16 = 0o100000;
+const S_IFLNK: u16 = 0o120000;
+/// Finder flag marking an item invisible
+const FINDER_IS_INVISIBLE: u16 = 0x4000;
+/// Owner/group 99 is treated as "unknown" and mapped to the current user when mounted
+const UNKNOWN_OWNER: u32 = 99;
+
+enum NodeKind {
+    Folder,
+    File(Vec<u8>),
+    Symlink(String),
+}
+
+struct Node {
+    id: u32,
+    parent: u32,
+    name: String,
+    mode: u16,
+    finder_flags: u16,
+    kind: NodeKind,
+}
+
+/// Builds an HFSX volume image in memory
+pub struct HfsBuilder {
+    volume_name: String,
+    create_date: u32,
+    nodes: Vec<Node>,
+    next_id: u32,
+}
+
+impl HfsBuilder {
+    pub fn new(volume_name: &str) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let root = Node {
+            id: ROOT_FOLDER_ID,
+            parent: ROOT_PARENT_ID,
+            name: volume_name.to_string(),
+            mode: S_IFDIR | 0o755,
+            finder_flags: 0,
+            kind: NodeKind::Folder,
+        };
+        HfsBuilder {
+            volume_name: volume_name.to_string(),
+            create_date: (now + HFS_EPOCH_OFFSET) as u32,
+            nodes: vec![root],
+            next_id: FIRST_USER_CATALOG_ID,
+        }
+    }
+
+    /// Catalog node id of the root folder
+    pub fn root(&self) -> u32 {
+        ROOT_FOLDER_ID
+    }
+
+    /// Volume creation date in HFS time, aliases pointing into the volume must match it
+    pub fn create_date(&self) -> u32 {
+        self.create_date
+    }
+
+    fn push(&mut self, parent: u32, name: &str, mode: u16, kind: NodeKind) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.nodes.push(Node {
+            id,
+            parent,
+            // HFS+ stores the POSIX ':' as '/'
+            name: name.replace(':', "/"),
+            mode,
+            finder_flags: 0,
+            kind,
+        });
+        id
+    }
+
+    pub fn add_folder(&mut self, parent: u32, name: &str, permissions: u16) -> u32 {
+        self.push(parent, name, S_IFDIR | (permissions & 0o7777), NodeKind::Folder)
+    }
+
+    pub fn add_file(&mut self, parent: u32, name: &str, data: Vec<u8>, permissions: u16) -> u32 {
+        self.push(parent, name, S_IFREG | (permissions & 0o7777), NodeKind::File(data))
+    }
+
+    pub fn add_symlink(&mut self, parent: u32, name: &str, target: &str) -> u32 {
+        self.push(parent, name, S_IFLNK | 0o755, NodeKind::Symlink(target.to_string()))
+    }
+
+    /// Hides an item from Finder
+    pub fn set_invisible(&mut self, id: u32) {
+        if let Some(node) = self.nodes.iter_mut().find(|n| n.id == id) {
+            node.finder_flags |= FINDER_IS_INVISIBLE;
+        }
+    }
+
+    /// Recursively adds a file, symlink or directory from the host file system as `name`
+    pub fn add_tree(&mut self, parent: u32, name: &str, source: &Path) -> Result<u32> {
+        let metadata = fs::symlink_metadata(source)
+            .context(format!("Failed to get metadata for {}", source.display()))?;
+        let permissions = permissions_of(&metadata);
+
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(source)
+                .context(format!("Failed to read symlink {}", source.display()))?;
+            return Ok(self.add_symlink(parent, name, &target.to_string_lossy()));
+        }
+        if metadata.is_file() {
+            let data = fs::read(source).context(format!("Failed to read {}", source.display()))?;
+            return Ok(self.add_file(parent, name, data, permissions));
+        }
+
+        let id = self.add_folder(parent, name, permissions);
+        let mut entries = fs::read_dir(source)
+            .context(format!("Failed to read directory {}", source.display()))?
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            self.add_tree(id, &entry.file_name().to_string_lossy(), &entry.path())?;
+        }
+        Ok(id)
+    }
+
+    /// Lays out and serializes the volume
+    pub fn build(self) -> Result<Vec<u8>> {
+        let data_blocks: u64 = self.nodes.iter().map(|n| blocks_for(fork_data(n).len())).sum();
+
+        // The record sizes do not depend on where the data ends up, so the catalog can
+        // be sized before the data is placed.
+        let catalog_nodes = self.catalog_tree(&[]).node_count();
+        let catalog_blocks = blocks_for(catalog_nodes * CATALOG_NODE_SIZE);
+        let extents_blocks = blocks_for(EXTENTS_NODE_SIZE);
+
+        let used = 1 + extents_blocks + catalog_blocks + data_blocks + 1;
+        let free = (used / 10).max(256);
+        let mut bitmap_blocks = 1;
+        while bitmap_blocks * BLOCK_SIZE * 8 < used + free + bitmap_blocks {
+            bitmap_blocks += 1;
+        }
+        let total_blocks = used + free + bitmap_blocks;
+        // Block numbers and counts are 32-bit, which limits a volume to 16 TiB of 4 KiB blocks
+        if total_blocks > u32::MAX as u64 {
+            anyhow::bail!(
+                "The volume needs {} allocation blocks, more than the {} HFS+ supports",
+                total_blocks,
+                u32::MAX
+            );
+        }
+        let image_size = usize::try_from(total_blocks * BLOCK_SIZE).context("The volume does not fit in memory")?;
+
+        let bitmap_start = 1;
+        let extents_start = bitmap_start + bitmap_blocks;
+        let catalog_start = extents_start + extents_blocks;
+        let mut next_block = catalog_start + catalog_blocks;
+        let mut data_starts = vec![0; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            data_starts[i] = next_block;
+            next_block += blocks_for(fork_data(node).len());
+        }
+        let data_end = next_block;
+
+        let mut image = vec![0u8; image_size];
+
+        // Allocation bitmap: everything up to the file data plus the last block, which
+        // holds the alternate volume header
+        let bitmap = &mut image[block_range(bitmap_start, bitmap_blocks)];
+        for block in (0..data_end).chain([total_blocks - 1]) {
+            bitmap[(block / 8) as usize] |= 0x80 >> (block % 8);
+        }
+
+        let extents = BTree {
+            node_size: EXTENTS_NODE_SIZE,
+            max_key_length: 10,
+            key_compare_type: 0,
+            attributes: BT_BIG_KEYS,
+            leaves: vec![],
+            index_levels: vec![],
+        };
+        let extents_bytes = extents.serialize(block_range(0, extents_blocks).len());
+        image[block_range(extents_start, extents_blocks)].copy_from_slice(&extents_bytes);
+
+        let catalog_bytes = self
+            .catalog_tree(&data_starts)
+            .serialize(block_range(0, catalog_blocks).len());
+        image[block_range(catalog_start, catalog_blocks)].copy_from_slice(&catalog_bytes);
+
+        for (node, start) in self.nodes.iter().zip(&data_starts) {
+            let data = fork_data(node);
+            let offset = block_range(*start, 0).start;
+            image[offset..offset + data.len()].copy_from_slice(data);
+        }
+
+        let file_count = self.nodes.iter().filter(|n| !matches!(n.kind, NodeKind::Folder)).count();
+        let folder_count = self.nodes.len() - file_count - 1;
+        let mut header = Writer::default();
+        header.u16(0x4858); // 'HX'
+        header.u16(5);
+        header.u32(1 << 8); // kHFSVolumeUnmountedBit
+        header.bytes(b"10.0");
+        header.u32(0); // journalInfoBlock
+        header.u32(self.create_date);
+        header.u32(self.create_date); // modifyDate
+        header.u32(0); // backupDate
+        header.u32(self.create_date); // checkedDate
+        header.u32(file_count as u32);
+        header.u32(folder_count as u32);
+        header.u32(BLOCK_SIZE as u32);
+        header.u32(total_blocks as u32);
+        header.u32((total_blocks - data_end - 1) as u32);
+        header.u32(data_end as u32); // nextAllocation
+        header.u32(BLOCK_SIZE as u32); // rsrcClumpSize
+        header.u32(BLOCK_SIZE as u32); // dataClumpSize
+        header.u32(self.next_id);
+        header.u32(1); // writeCount
+        header.u64(1); // encodingsBitmap: MacRoman
+        // finderInfo: [2] is the folder Finder opens on mount, [6..8] the volume UUID
+        let uuid = volume_uuid(&self.volume_name, self.create_date);
+        for value in [0, 0, ROOT_FOLDER_ID, 0, 0, 0, (uuid >> 32) as u32, uuid as u32] {
+            header.u32(value);
+        }
+        header.fork(bitmap_blocks * BLOCK_SIZE, bitmap_start, bitmap_blocks);
+        header.fork(extents_blocks * BLOCK_SIZE, extents_start, extents_blocks);
+        header.fork(catalog_blocks * BLOCK_SIZE, catalog_start, catalog_blocks);
+        header.fork(0, 0, 0); // attributesFile
+        header.fork(0, 0, 0); // startupFile
+        let header = header.0;
+
+        image[1024..1024 + header.len()].copy_from_slice(&header);
+        let alternate = image.len() - 1024;
+        image[alternate..alternate + header.len()].copy_from_slice(&header);
+        Ok(image)
+    }
+
+    /// Builds the catalog B-tree, `data_starts` maps node indices to their first data block
+    fn catalog_tree(&self, data_starts: &[u64]) -> BTree {
+        let mut records: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let start = data_starts.get(i).copied().unwrap_or_default();
+            records.push((catalog_key(node.parent, &node.name), self.catalog_record(node, start)));
+
+            let mut thread = Writer::default();
+            thread.u16(if matches!(node.kind, NodeKind::Folder) {
+                FOLDER_THREAD_RECORD
+            } else {
+                FILE_THREAD_RECORD
+            });
+            thread.u16(0);
+            thread.u32(node.parent);
+            thread.unistr(&node.name);
+            records.push((catalog_key(node.id, ""), thread.0));
+        }
+        records.sort_by(|a, b| compare_catalog_keys(&a.0, &b.0));
+        BTree::build(records, CATALOG_NODE_SIZE, 516, 0xBC, BT_BIG_KEYS | BT_VARIABLE_INDEX_KEYS)
+    }
+
+    fn catalog_record(&self, node: &Node, data_start: u64) -> Vec<u8> {
+        let mut w = Writer::default();
+        match &node.kind {
+            NodeKind::Folder => {
+                let valence = self.nodes.iter().filter(|n| n.parent == node.id && n.id != node.id).count();
+                w.u16(FOLDER_RECORD);
+                w.u16(0); // flags
+                w.u32(valence as u32);
+                w.u32(node.id);
+                w.dates(self.create_date);
+                w.bsd_info(node.mode);
+                // FolderInfo: windowBounds, finderFlags, location, reserved
+                w.zeros(8);
+                w.u16(node.finder_flags);
+                w.zeros(6);
+                w.zeros(16); // ExtendedFolderInfo
+                w.u32(0); // textEncoding
+                w.u32(0); // folderCount
+            }
+            NodeKind::File(_) | NodeKind::Symlink(_) => {
+                let size = fork_data(node).len();
+                w.u16(FILE_RECORD);
+                w.u16(0x0002); // kHFSThreadExistsMask
+                w.u32(0);
+                w.u32(node.id);
+                w.dates(self.create_date);
+                w.bsd_info(node.mode);
+                // FileInfo: fileType, fileCreator, finderFlags, location, reserved
+                if matches!(node.kind, NodeKind::Symlink(_)) {
+                    w.bytes(b"slnkrhap");
+                } else {
+                    w.zeros(8);
+                }
+                w.u16(node.finder_flags);
+                w.zeros(6);
+                w.zeros(16); // ExtendedFileInfo
+                w.u32(0); // textEncoding
+                w.u32(0);
+                let blocks = blocks_for(size);
+                w.fork(size as u64, if blocks > 0 { data_start } else { 0 }, blocks);
+                w.fork(0, 0, 0); // resource fork
+            }
+        }
+        w.0
+    }
+}
+
+/// Contents of the data fork of a catalog node
+fn fork_data(node: &Node) -> &[u8] {
+    match &node.kind {
+        NodeKind::Folder => &[],
+        NodeKind::File(data) => data,
+        NodeKind::Symlink(target) => target.as_bytes(),
+    }
+}
+
+fn blocks_for(size: usize) -> u64 {
+    (size as u64).div_ceil(BLOCK_SIZE)
+}
+
+/// Byte range of blocks in the image, which `build` checked to fit in memory
+fn block_range(start: u64, count: u64) -> std::ops::Range<usize> {
+    (start * BLOCK_SIZE) as usize..((start + count) * BLOCK_SIZE) as usize
+}
+
+#[cfg(unix)]
+fn permissions_of(metadata: &fs::Metadata) -> u16 {
+    use std::os::unix::fs::PermissionsExt;
+    (metadata.permissions().mode() & 0o7777) as u16
+}
+
+#[cfg(not(unix))]
+fn permissions_of(metadata: &fs::Metadata) -> u16 {
+    if metadata.is_dir() { 0o755 } else { 0o644 }
+}
+
+fn volume_uuid(volume_name: &str, create_date: u32) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (volume_name, create_date).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn catalog_key(parent: u32, name: &str) -> Vec<u8> {
+    let mut key = Writer::default();
+    let units: Vec<u16> = name.encode_utf16().collect();
+    key.u16(6 + 2 * units.len() as u16);
+    key.u32(parent);
+    key.unistr(name);
+    key.0
+}
+
+/// HFSX binary key order: parent id first, then the raw UTF-16 code units of the name
+fn compare_catalog_keys(a: &[u8], b: &[u8]) -> std::cmp::Ordering {
+    a[2..6].cmp(&b[2..6]).then_with(|| a[8..].cmp(&b[8..]))
+}
+
+const BT_BIG_KEYS: u32 = 2;
+const BT_VARIABLE_INDEX_KEYS: u32 = 4;
+const NODE_DESCRIPTOR_SIZE: usize = 14;
+
+struct BTreeNode {
+    records: Vec<Vec<u8>>,
+    /// First key of the node, used by its parent index record
+    first_key: Vec<u8>,
+}
+
+/// A B-tree laid out as header node, leaf nodes and then index nodes bottom up
+struct BTree {
+    node_size: usize,
+    max_key_length: u16,
+    key_compare_type: u8,
+    attributes: u32,
+    leaves: Vec<BTreeNode>,
+    index_levels: Vec<Vec<BTreeNode>>,
+}
+
+impl BTree {
+    /// Packs the sorted `(key, data)` records into leaves and builds the index levels above
+    fn build(
+        records: Vec<(Vec<u8>, Vec<u8>)>,
+        node_size: usize,
+        max_key_length: u16,
+        key_compare_type: u8,
+        attributes: u32,
+    ) -> Self {
+        let leaves = pack_nodes(
+            records.into_iter().map(|(key, data)| (key.clone(), [key, data].concat())),
+            node_size,
+        );
+        let mut index_levels: Vec<Vec<BTreeNode>> = Vec::new();
+        let mut level_len = leaves.len();
+        let mut first_node = 1;
+        while level_len > 1 {
+            let children = index_levels.last().unwrap_or(&leaves);
+            let level = pack_nodes(
+                children.iter().enumerate().map(|(i, child)| {
+                    let mut record = child.first_key.clone();
+                    record.extend_from_slice(&((first_node + i) as u32).to_be_bytes());
+                    (child.first_key.clone(), record)
+                }),
+                node_size,
+            );
+            first_node += level_len;
+            level_len = level.len();
+            index_levels.push(level);
+        }
+        BTree { node_size, max_key_length, key_compare_type, attributes, leaves, index_levels }
+    }
+
+    fn node_count(&self) -> usize {
+        1 + self.leaves.len() + self.index_levels.iter().map(Vec::len).sum::<usize>()
+    }
+
+    fn serialize(&self, file_size: usize) -> Vec<u8> {
+        let total_nodes = file_size / self.node_size;
+        let used_nodes = self.node_count();
+        let leaf_records: usize = self.leaves.iter().map(|n| n.records.len()).sum();
+        let depth = if self.leaves.is_empty() { 0 } else { 1 + self.index_levels.len() };
+        let root = match depth {
+            0 => 0,
+            1 => 1,
+            _ => used_nodes - 1,
+        };
+
+        let mut header = Writer::default();
+        header.u16(depth as u16);
+        header.u32(root as u32);
+        header.u32(leaf_records as u32);
+        header.u32(if self.leaves.is_empty() { 0 } else { 1 });
+        header.u32(self.leaves.len() as u32);
+        header.u16(self.node_size as u16);
+        header.u16(self.max_key_length);
+        header.u32(total_nodes as u32);
+        header.u32((total_nodes - used_nodes) as u32);
+        header.u16(0);
+        header.u32(self.node_size as u32); // clumpSize
+        header.u8(0); // btreeType
+        header.u8(self.key_compare_type);
+        header.u32(self.attributes);
+        header.zeros(64);
+
+        let map_size = self.node_size - NODE_DESCRIPTOR_SIZE - 106 - 128 - 2 * 4;
+        let mut map = vec![0u8; map_size];
+        for node in 0..used_nodes {
+            map[node / 8] |= 0x80 >> (node % 8);
+        }
+
+        let mut out = Vec::with_capacity(file_size);
+        out.extend(node_bytes(self.node_size, 0, 0, 1, 0, &[header.0, vec![0; 128], map]));
+
+        let leaf_count = self.leaves.len();
+        for (i, leaf) in self.leaves.iter().enumerate() {
+            let next = if i + 1 < leaf_count { i + 2 } else { 0 };
+            let prev = if i > 0 { i } else { 0 };
+            out.extend(node_bytes(self.node_size, next as u32, prev as u32, 0xFF, 1, &leaf.records));
+        }
+        let mut first_node = 1 + leaf_count;
+        for (level, nodes) in self.index_levels.iter().enumerate() {
+            for (i, node) in nodes.iter().enumerate() {
+                let next = if i + 1 < nodes.len() { first_node + i + 1 } else { 0 };
+                let prev = if i > 0 { first_node + i - 1 } else { 0 };
+                out.extend(node_bytes(
+                    self.node_size,
+                    next as u32,
+                    prev as u32,
+                    0,
+                    (level + 2) as u8,
+                    &node.records,
+                ));
+            }
+            first_node += nodes.len();
+        }
+        out.resize(file_size, 0);
+        out
+    }
+}
+
+/// Greedily fills nodes with `(first key, record)` pairs
+fn pack_nodes(records: impl Iterator<Item = (Vec<u8>, Vec<u8>)>, node_size: usize) -> Vec<BTreeNode> {
+    let mut nodes: Vec<BTreeNode> = Vec::new();
+    let mut used = node_size;
+    for (key, record) in records {
+        // Each record also needs a 2 byte offset, plus the free space offset of the node
+        let needed = record.len() + 2;
+        if used + needed + 2 > node_size {
+            nodes.push(BTreeNode { records: Vec::new(), first_key: key });
+            used = NODE_DESCRIPTOR_SIZE;
+        }
+        used += needed;
+        nodes.last_mut().unwrap().records.push(record);
+    }
+    nodes
+}
+
+fn node_bytes(node_size: usize, next: u32, prev: u32, kind: u8, height: u8, records: &[Vec<u8>]) -> Vec<u8> {
+    let mut node = Writer::default();
+    node.u32(next);
+    node.u32(prev);
+    node.u8(kind);
+    node.u8(height);
+    node.u16(records.len() as u16);
+    node.u16(0);
+    let mut offsets = Vec::with_capacity(records.len() + 1);
+    for record in records {
+        offsets.push(node.0.len() as u16);
+        node.bytes(record);
+    }
+    offsets.push(node.0.len() as u16);
+    let mut bytes = node.0;
+    bytes.resize(node_size, 0);
+    for (i, offset) in offsets.iter().enumerate() {
+        let at = node_size - 2 * (i + 1);
+        bytes[at..at + 2].copy_from_slice(&offset.to_be_bytes());
+    }
+    bytes
+}
+
+/// Big endian serialization helper
+#[derive(Default)]
+pub(super) struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.0.extend_from_slice(v);
+    }
+
+    pub fn zeros(&mut self, n: usize) {
+        self.0.resize(self.0.len() + n, 0);
+    }
+
+    /// HFSUniStr255: length followed by UTF-16 code units
+    fn unistr(&mut self, s: &str) {
+        let units: Vec<u16> = s.encode_utf16().collect();
+        self.u16(units.len() as u16);
+        for unit in units {
+            self.u16(unit);
+        }
+    }
+
+    /// createDate, contentModDate, attributeModDate, accessDate, backupDate
+    fn dates(&mut self, date: u32) {
+        for value in [date, date, date, date, 0] {
+            self.u32(value);
+        }
+    }
+
+    /// HFSPlusBSDInfo
+    fn bsd_info(&mut self, mode: u16) {
+        self.u32(UNKNOWN_OWNER);
+        self.u32(UNKNOWN_OWNER);
+        self.u8(0); // adminFlags
+        self.u8(0); // ownerFlags
+        self.u16(mode);
+        self.u32(1); // link count
+    }
+
+    /// HFSPlusForkData with a single extent, block numbers fit in 32 bits once `build` checked
+    /// the volume size
+    fn fork(&mut self, logical_size: u64, start_block: u64, block_count: u64) {
+        self.u64(logical_size);
+        self.u32(if block_count > 0 { BLOCK_SIZE as u32 } else { 0 });
+        self.u32(block_count as u32);
+        self.u32(start_block as u32);
+        self.u32(block_count as u32);
+        self.zeros(7 * 8);
+    }
+}
@@ -0,0 +1,155 @@
+//! UDIF (Universal Disk Image Format) container writer.
+//!
+//! The raw volume is split into 1 MiB chunks which are stored zlib compressed (UDZO),
+//! raw (UDRO) or as zero fill. The chunk table is a single `mish` block in the XML
+//! property list, and the image ends with the 512 byte `koly` trailer. There is no
+//! partition map, the whole disk is the HFS volume (like `hdiutil create -layout NONE`).
+
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use plist::Value;
+use std::io::Write;
+
+use super::hfs::Writer;
+
+const SECTOR_SIZE: usize = 512;
+const CHUNK_SECTORS: usize = 2048;
+
+const CHUNK_ZERO_FILL: u32 = 0x0000_0002;
+const CHUNK_RAW: u32 = 0x0000_0001;
+const CHUNK_ZLIB: u32 = 0x8000_0005;
+const CHUNK_TERMINATOR: u32 = 0xFFFF_FFFF;
+const CHECKSUM_CRC32: u32 = 2;
+
+struct Chunk {
+    kind: u32,
+    sector: u64,
+    sector_count: u64,
+    offset: u64,
+    length: u64,
+}
+
+/// Writes `volume` (a whole disk, sized in whole sectors) as a UDIF image to `out`
+pub fn write_udif(volume: &[u8], compress: bool, mut out: impl Write) -> Result<()> {
+    let mut data_fork = Vec::new();
+    let mut chunks = Vec::new();
+    for (i, raw) in volume.chunks(CHUNK_SECTORS * SECTOR_SIZE).enumerate() {
+        let (kind, stored) = if raw.iter().all(|b| *b == 0) {
+            (CHUNK_ZERO_FILL, Vec::new())
+        } else if compress {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+            encoder.write_all(raw)?;
+            (CHUNK_ZLIB, encoder.finish()?)
+        } else {
+            (CHUNK_RAW, raw.to_vec())
+        };
+        chunks.push(Chunk {
+            kind,
+            sector: (i * CHUNK_SECTORS) as u64,
+            sector_count: raw.len().div_ceil(SECTOR_SIZE) as u64,
+            offset: data_fork.len() as u64,
+            length: stored.len() as u64,
+        });
+        data_fork.extend_from_slice(&stored);
+    }
+    let sector_count = volume.len().div_ceil(SECTOR_SIZE) as u64;
+    chunks.push(Chunk {
+        kind: CHUNK_TERMINATOR,
+        sector: sector_count,
+        sector_count: 0,
+        offset: data_fork.len() as u64,
+        length: 0,
+    });
+
+    let volume_crc = crc32fast::hash(volume);
+    let mut mish = Writer::default();
+    mish.bytes(b"mish");
+    mish.u32(1); // version
+    mish.u64(0); // first sector
+    mish.u64(sector_count);
+    mish.u64(0); // data offset
+    mish.u32((CHUNK_SECTORS + 8) as u32); // buffers needed
+    mish.u32(0); // block descriptors
+    mish.zeros(24);
+    checksum(&mut mish, volume_crc);
+    mish.u32(chunks.len() as u32);
+    for chunk in &chunks {
+        mish.u32(chunk.kind);
+        mish.u32(0); // comment
+        mish.u64(chunk.sector);
+        mish.u64(chunk.sector_count);
+        mish.u64(chunk.offset);
+        mish.u64(chunk.length);
+    }
+
+    let name = "whole disk (Apple_HFS : 0)";
+    let blkx = plist::Dictionary::from_iter([
+        ("Attributes".to_string(), Value::String("0x0050".to_string())),
+        ("CFName".to_string(), Value::String(name.to_string())),
+        ("Data".to_string(), Value::Data(mish.0)),
+        ("ID".to_string(), Value::String("0".to_string())),
+        ("Name".to_string(), Value::String(name.to_string())),
+    ]);
+    let resource_fork = plist::Dictionary::from_iter([(
+        "blkx".to_string(),
+        Value::Array(vec![Value::Dictionary(blkx)]),
+    )]);
+    let resources = plist::Dictionary::from_iter([(
+        "resource-fork".to_string(),
+        Value::Dictionary(resource_fork),
+    )]);
+    let mut xml = Vec::new();
+    plist::to_writer_xml(&mut xml, &Value::Dictionary(resources))
+        .context("Failed to serialize UDIF resources")?;
+
+    // The master checksum covers the checksums of every blkx entry
+    let master_crc = crc32fast::hash(&volume_crc.to_be_bytes());
+
+    let mut koly = Writer::default();
+    koly.bytes(b"koly");
+    koly.u32(4); // version
+    koly.u32(512); // header size
+    koly.u32(1); // flags: flattened
+    koly.u64(0); // running data fork offset
+    koly.u64(0); // data fork offset
+    koly.u64(data_fork.len() as u64);
+    koly.u64(0); // resource fork offset
+    koly.u64(0); // resource fork length
+    koly.u32(1); // segment number
+    koly.u32(1); // segment count
+    koly.bytes(&segment_id(volume_crc, data_fork.len()));
+    checksum(&mut koly, crc32fast::hash(&data_fork));
+    koly.u64(data_fork.len() as u64); // xml offset
+    koly.u64(xml.len() as u64);
+    koly.zeros(120);
+    checksum(&mut koly, master_crc);
+    koly.u32(1); // image variant: device image
+    koly.u64(sector_count);
+    koly.zeros(12);
+
+    out.write_all(&data_fork)?;
+    out.write_all(&xml)?;
+    out.write_all(&koly.0)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// UDIFChecksum holding a single CRC32
+fn checksum(w: &mut Writer, crc: u32) {
+    w.u32(CHECKSUM_CRC32);
+    w.u32(32);
+    w.u32(crc);
+    w.zeros(31 * 4);
+}
+
+fn segment_id(crc: u32, len: usize) -> [u8; 16] {
+    use std::hash::{Hash, Hasher};
+    let mut id = [0u8; 16];
+    for (i, part) in id.chunks_mut(8).enumerate() {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (crc, len, i).hash(&mut hasher);
+        part.copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+    id
+}
@@ -0,0 +1,142 @@
+//! Embedding frameworks.
+//!
+//! `--framework` copies a framework into `Contents/Frameworks` recreating its symlinks: a macOS
+//! framework keeps its files in `Versions/A` and links `Versions/Current` and its top-level
+//! entries to them, and a copy with the links followed neither matches the framework's signature
+//! nor loads as a framework. The main executable gets an `@executable_path/../Frameworks` runpath
+//! (LC_RPATH) so the `@rpath/X.framework/...` install names resolve, and every architecture of
+//! the executable has to be present in the framework binaries.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::macho::{self, MachO};
+
+/// Runpath added to the main executable
+const RPATH: &str = "@executable_path/../Frameworks";
+
+/// Copies the `--framework` bundles into Contents/Frameworks and links the main executable to them
+pub fn embed(args: &crate::Args, app_path: &Path) -> Result<()> {
+    if args.frameworks.is_empty() {
+        return Ok(());
+    }
+    let frameworks_dir = app_path.join("Contents").join("Frameworks");
+    fs::create_dir_all(&frameworks_dir).context("Failed to create Frameworks directory")?;
+    for framework in &args.frameworks {
+        let source = Path::new(framework);
+        binary(source)?;
+        let name = source
+            .file_name()
+            .context(format!("Invalid framework path {}", framework))?;
+        let target = frameworks_dir.join(name);
+        if fs::symlink_metadata(&target).is_ok() {
+            anyhow::bail!("{} is already in the bundle", target.display());
+        }
+        crate::copy_tree(source, &target)?;
+        println!("Added framework {} to {}", framework, target.display());
+    }
+    link_executable(args, app_path)
+}
+
+/// Checks the architectures of the embedded frameworks against the main executable and adds the
+/// Frameworks runpath to it
+pub fn link_executable(args: &crate::Args, app_path: &Path) -> Result<()> {
+    if args.frameworks.is_empty() {
+        return Ok(());
+    }
+    let Some(executable) = main_executable(args, app_path) else {
+        println!("Note: the executable is not a Mach-O binary, no runpath added for the frameworks");
+        return Ok(());
+    };
+    let data = fs::read(&executable).context(format!("Failed to read {}", executable.display()))?;
+    let (fat, mut slices) = macho::split_slices(&data).context(format!("Failed to parse {}", executable.display()))?;
+
+    let frameworks_dir = app_path.join("Contents").join("Frameworks");
+    for framework in &args.frameworks {
+        let name = Path::new(framework)
+            .file_name()
+            .context(format!("Invalid framework path {}", framework))?
+            .to_string_lossy();
+        let binary = binary(&frameworks_dir.join(name.as_ref()))?;
+        let data = fs::read(&binary).context(format!("Failed to read {}", binary.display()))?;
+        if !macho::is_macho(&data) {
+            anyhow::bail!("{} is not a Mach-O binary", binary.display());
+        }
+        let (_, framework_slices) =
+            macho::split_slices(&data).context(format!("Failed to parse {}", binary.display()))?;
+        let missing: Vec<String> = slices
+            .iter()
+            .filter(|slice| !framework_slices.iter().any(|s| s.cputype == slice.cputype))
+            .map(|slice| macho::arch_name(slice.cputype))
+            .collect();
+        if !missing.is_empty() {
+            let available: Vec<String> = framework_slices.iter().map(|s| macho::arch_name(s.cputype)).collect();
+            anyhow::bail!(
+                "{} has no {} slice for the app executable (it contains {})",
+                name,
+                missing.join(", "),
+                available.join(", ")
+            );
+        }
+    }
+
+    let mut changed = false;
+    let mut was_signed = false;
+    for slice in &mut slices {
+        let mut image = MachO::parse(std::mem::take(&mut slice.data))?;
+        was_signed |= image.code_signature().is_some();
+        if !image.rpaths().iter().any(|path| path == RPATH) {
+            image.add_rpath(RPATH).context(format!(
+                "Failed to add a runpath to {} ({})",
+                executable.display(),
+                macho::arch_name(slice.cputype)
+            ))?;
+            changed = true;
+        }
+        slice.data = image.data;
+    }
+    if !changed {
+        return Ok(());
+    }
+    // Writing over the file keeps its permissions
+    fs::write(&executable, macho::join_slices(fat, slices))
+        .context(format!("Failed to write {}", executable.display()))?;
+    println!("Added runpath {} to {}", RPATH, executable.display());
+    if was_signed && !args.sign {
+        println!(
+            "Warning: the signature of {} no longer matches, add --sign",
+            executable.display()
+        );
+    }
+    Ok(())
+}
+
+/// Binary of a framework, `X.framework/X` (a symlink into `Versions/Current` on macOS)
+fn binary(framework: &Path) -> Result<PathBuf> {
+    if framework.extension().is_none_or(|ext| ext != "framework") || !framework.is_dir() {
+        anyhow::bail!(
+            "Framework not found: {} (expected a .framework directory)",
+            framework.display()
+        );
+    }
+    let name = framework
+        .file_stem()
+        .context(format!("Invalid framework path {}", framework.display()))?;
+    let binary = framework.join(name);
+    if !binary.is_file() {
+        anyhow::bail!("{} has no binary {}", framework.display(), name.to_string_lossy());
+    }
+    Ok(binary)
+}
+
+/// The Mach-O main executable, which --single-instance and --run-in-terminal move to
+/// `{name}_original`; None for scripts
+fn main_executable(args: &crate::Args, app_path: &Path) -> Option<PathBuf> {
+    let macos_dir = app_path.join("Contents").join("MacOS");
+    let name = Path::new(&args.executable).file_name()?.to_string_lossy().to_string();
+    [format!("{}_original", name), name]
+        .into_iter()
+        .map(|file| macos_dir.join(file))
+        .find(|path| fs::read(path).is_ok_and(|data| macho::is_macho(&data)))
+}
@@ -0,0 +1,258 @@
+//! Icon conversion.
+//!
+//! `--icon` takes an .icns file, which is copied as is, or an SVG, ICO or PNG file, a directory of
+//! PNG files or an `.iconset` folder, which are converted to ICNS. SVG icons are rendered at every
+//! size. For the other formats each size is scaled down from the smallest source image at least
+//! as large, or scaled up from the largest one with a warning when none is.
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{ImageFormat, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// ICNS element types and their pixel sizes, as written by `iconutil`
+const ICNS_ELEMENTS: &[(&[u8; 4], u32)] = &[
+    (b"icp4", 16),
+    (b"ic11", 32),
+    (b"icp5", 32),
+    (b"ic12", 64),
+    (b"ic07", 128),
+    (b"ic13", 256),
+    (b"ic08", 256),
+    (b"ic14", 512),
+    (b"ic09", 512),
+    (b"ic10", 1024),
+];
+
+/// Images an icon can be made from
+enum Source {
+    Svg(Box<usvg::Tree>),
+    /// Square images sorted by size
+    Images(Vec<RgbaImage>),
+}
+
+/// Whether the icon is copied as is rather than converted
+pub fn is_icns(icon: &Path) -> bool {
+    icon.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("icns"))
+}
+
+/// File name of the icon in Contents/Resources (CFBundleIconFile), named after the icon set for
+/// asset catalogs
+pub fn bundle_file_name(icon: &str, icon_name: &str) -> Result<String> {
+    let path = Path::new(icon.trim_end_matches('/'));
+    if crate::assets::is_asset_catalog(path) {
+        return Ok(format!("{}.icns", icon_name));
+    }
+    // `.`, `..` and `/` have no file name to name the icon after
+    let invalid = || format!("Invalid icon path: {} (expected a file or directory name)", icon);
+    if is_icns(path) {
+        Ok(path.file_name().with_context(invalid)?.to_string_lossy().to_string())
+    } else {
+        Ok(format!("{}.icns", path.file_stem().with_context(invalid)?.to_string_lossy()))
+    }
+}
+
+/// Converts an SVG, ICO or PNG file, a directory of PNG files or an .iconset folder to ICNS
+pub fn to_icns(icon: &Path) -> Result<Vec<u8>> {
+    encode_icns(&load(icon)?)
+}
+
+/// Converts PNG files, such as the images of an asset catalog icon set, to ICNS
+pub fn png_files_to_icns(label: &Path, files: &[PathBuf]) -> Result<Vec<u8>> {
+    encode_icns(&load_pngs(label, files)?)
+}
+
+fn encode_icns(source: &Source) -> Result<Vec<u8>> {
+    let mut pngs: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+    let mut upscaled: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for &(_, size) in ICNS_ELEMENTS {
+        if pngs.contains_key(&size) {
+            continue;
+        }
+        let image = match source {
+            Source::Svg(tree) => render_svg(tree, size)?,
+            Source::Images(images) => {
+                let image = best_image(images, size);
+                if image.width() < size {
+                    upscaled.entry(image.width()).or_default().push(size);
+                }
+                if image.width() == size {
+                    image.clone()
+                } else {
+                    image::imageops::resize(image, size, size, FilterType::Lanczos3)
+                }
+            }
+        };
+        pngs.insert(size, encode_png(&image)?);
+    }
+    for (source_size, sizes) in upscaled {
+        let sizes: Vec<String> = sizes.iter().map(|s| format!("{}x{}", s, s)).collect();
+        println!(
+            "Warning: upscaling the {}x{} icon image to {}, provide a larger image for a sharp icon",
+            source_size,
+            source_size,
+            sizes.join(", ")
+        );
+    }
+
+    let mut icns = Vec::new();
+    for &(ostype, size) in ICNS_ELEMENTS {
+        let png = &pngs[&size];
+        icns.extend_from_slice(ostype);
+        icns.extend_from_slice(&(png.len() as u32 + 8).to_be_bytes());
+        icns.extend_from_slice(png);
+    }
+    let mut data = b"icns".to_vec();
+    data.extend_from_slice(&(icns.len() as u32 + 8).to_be_bytes());
+    data.extend_from_slice(&icns);
+    Ok(data)
+}
+
+fn load(icon: &Path) -> Result<Source> {
+    if icon.is_dir() {
+        return load_png_dir(icon);
+    }
+    let data = fs::read(icon).context(format!("Failed to read icon: {}", icon.display()))?;
+    let extension = icon
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let images = match extension.as_str() {
+        "svg" | "svgz" => {
+            let tree = usvg::Tree::from_data(&data, &usvg::Options::default())
+                .context(format!("Invalid SVG icon: {}", icon.display()))?;
+            return Ok(Source::Svg(Box::new(tree)));
+        }
+        "ico" => load_ico(&data).context(format!("Invalid ICO icon: {}", icon.display()))?,
+        "png" => vec![decode(&data, ImageFormat::Png).context(format!("Invalid PNG icon: {}", icon.display()))?],
+        _ => anyhow::bail!(
+            "Unsupported icon format: {} (expected .icns, .svg, .ico, .png, a directory of PNG files or an .iconset folder)",
+            icon.display()
+        ),
+    };
+    images_source(icon, images)
+}
+
+/// PNG files of a directory, such as the icon_16x16.png ... icon_512x512@2x.png of an .iconset
+fn load_png_dir(dir: &Path) -> Result<Source> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).context(format!("Failed to read directory: {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        anyhow::bail!("No PNG images found in icon directory: {}", dir.display());
+    }
+    load_pngs(dir, &files)
+}
+
+fn load_pngs(label: &Path, files: &[PathBuf]) -> Result<Source> {
+    let mut images = Vec::new();
+    for path in files {
+        images.push(load_png(path)?);
+    }
+    images_source(label, images)
+}
+
+/// Decodes a PNG file to RGBA
+pub fn load_png(path: &Path) -> Result<RgbaImage> {
+    let data = fs::read(path).context(format!("Failed to read icon: {}", path.display()))?;
+    decode(&data, ImageFormat::Png).context(format!("Invalid PNG icon: {}", path.display()))
+}
+
+/// Every image of an ICO file, keeping the one with the most bits per pixel of each size
+fn load_ico(data: &[u8]) -> Result<Vec<RgbaImage>> {
+    let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    if data.len() < 6 || u16_at(0) != 0 || u16_at(2) != 1 {
+        anyhow::bail!("Not an ICO file");
+    }
+    let count = u16_at(4) as usize;
+    let mut images: BTreeMap<(u32, u16), RgbaImage> = BTreeMap::new();
+    for index in 0..count {
+        let entry = 6 + index * 16;
+        if data.len() < entry + 16 {
+            anyhow::bail!("Truncated ICO directory");
+        }
+        let bits_per_pixel = u16_at(entry + 6);
+        let (size, offset) = (u32_at(entry + 8) as usize, u32_at(entry + 12) as usize);
+        let payload = data
+            .get(offset..offset + size)
+            .context(format!("ICO image {} is out of bounds", index))?;
+        // A single image ICO lets the image crate decode both PNG and BMP payloads
+        let mut single = vec![0, 0, 1, 0, 1, 0];
+        single.extend_from_slice(&data[entry..entry + 12]);
+        single.extend_from_slice(&22u32.to_le_bytes());
+        single.extend_from_slice(payload);
+        let image = decode(&single, ImageFormat::Ico).context(format!("Failed to decode ICO image {}", index))?;
+        images.insert((image.width(), bits_per_pixel), image);
+    }
+    let mut by_size: BTreeMap<u32, RgbaImage> = BTreeMap::new();
+    for ((width, _), image) in images {
+        by_size.insert(width, image);
+    }
+    Ok(by_size.into_values().collect())
+}
+
+fn images_source(icon: &Path, mut images: Vec<RgbaImage>) -> Result<Source> {
+    for image in &mut images {
+        if image.width() != image.height() {
+            println!(
+                "Warning: icon image of {}x{} in {} is not square and will be stretched",
+                image.width(),
+                image.height(),
+                icon.display()
+            );
+            let size = image.width().max(image.height());
+            *image = image::imageops::resize(image, size, size, FilterType::Lanczos3);
+        }
+    }
+    images.sort_by_key(|image| image.width());
+    Ok(Source::Images(images))
+}
+
+/// The smallest image at least `size` pixels wide, or the largest one
+fn best_image(images: &[RgbaImage], size: u32) -> &RgbaImage {
+    images
+        .iter()
+        .find(|image| image.width() >= size)
+        .unwrap_or_else(|| images.last().unwrap())
+}
+
+fn decode(data: &[u8], format: ImageFormat) -> Result<RgbaImage> {
+    Ok(image::load_from_memory_with_format(data, format)?.into_rgba8())
+}
+
+fn render_svg(tree: &usvg::Tree, size: u32) -> Result<RgbaImage> {
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).context("Invalid icon size")?;
+    let svg_size = tree.size();
+    // Fit the drawing into the square, centered
+    let scale = size as f32 / svg_size.width().max(svg_size.height());
+    let dx = (size as f32 - svg_size.width() * scale) / 2.0;
+    let dy = (size as f32 - svg_size.height() * scale) / 2.0;
+    let transform = tiny_skia::Transform::from_row(scale, 0.0, 0.0, scale, dx, dy);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    Ok(RgbaImage::from_raw(size, size, pixels).unwrap())
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut data = Cursor::new(Vec::new());
+    image
+        .write_to(&mut data, ImageFormat::Png)
+        .context("Failed to encode icon image")?;
+    Ok(data.into_inner())
+}
@@ -0,0 +1,227 @@
+//! Importing existing bundles into a manifest.
+//!
+//! `appgen import` copies the files of a bundle next to a new manifest so the bundle can be
+//! rebuilt with appgen. Info.plist keys with a matching option become manifest keys, the others
+//! are written to `Info.merge.plist` and merged back with `merge-plist`. Every other item of
+//! `Contents` becomes an `additional-file` entry placed at the same location.
+
+use anyhow::{Context, Result};
+use plist::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Options of the `import` subcommand
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// Path to the .app bundle to import
+    #[arg(value_name = "APP")]
+    app: String,
+
+    /// Directory receiving the manifest and the bundle's files (defaults to the app name)
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+const MANIFEST_NAME: &str = "appgen.toml";
+const MERGE_PLIST_NAME: &str = "Info.merge.plist";
+
+/// Items of Contents appgen writes itself
+const GENERATED_ITEMS: &[&str] = &["Info.plist", "PkgInfo", "_CodeSignature"];
+
+/// Info.plist keys appgen always writes, with the value it writes
+fn generated_value(key: &str) -> Option<Value> {
+    match key {
+        "CFBundlePackageType" => Some(Value::String("APPL".to_string())),
+        "CFBundleSignature" => Some(Value::String("????".to_string())),
+        "LSMinimumSystemVersion" => Some(Value::String("10.10.0".to_string())),
+        "NSHighResolutionCapable" => Some(Value::Boolean(true)),
+        _ => None,
+    }
+}
+
+/// Writes a manifest and the files of a bundle into the output directory, returning the
+/// manifest path
+pub fn import_app(args: &ImportArgs) -> Result<PathBuf> {
+    let app_path = Path::new(&args.app);
+    let contents = app_path.join("Contents");
+    let info = crate::read_info_plist(app_path)?;
+    let name = app_path
+        .file_stem()
+        .context(format!("Invalid app path: {}", args.app))?
+        .to_string_lossy()
+        .to_string();
+    let output = PathBuf::from(args.output.clone().unwrap_or_else(|| name.clone()));
+    if output.exists() && fs::read_dir(&output)?.next().is_some() {
+        anyhow::bail!("{} already exists and is not empty", output.display());
+    }
+    fs::create_dir_all(&output).context(format!("Failed to create {}", output.display()))?;
+
+    let mut manifest = toml::Table::new();
+    let mut handled: HashSet<&str> = HashSet::new();
+    let mut skipped: HashSet<PathBuf> = GENERATED_ITEMS.iter().map(PathBuf::from).collect();
+    let info_string = |key: &str| info.get(key).and_then(Value::as_string);
+    let info_bool = |key: &str| info.get(key).and_then(Value::as_boolean);
+
+    manifest.insert("name".to_string(), name.clone().into());
+    for key in ["CFBundleName", "CFBundleDisplayName"] {
+        if info_string(key) == Some(&name) {
+            handled.insert(key);
+        }
+    }
+
+    let executable = info_string("CFBundleExecutable").context("Info.plist has no CFBundleExecutable")?;
+    let executable = Path::new("MacOS").join(executable);
+    copy_item(&contents, &executable, &output)?;
+    manifest.insert("executable".to_string(), path_value(&executable));
+    skipped.insert(executable);
+    handled.insert("CFBundleExecutable");
+
+    if let Some(bundle_id) = info_string("CFBundleIdentifier") {
+        manifest.insert("bundle-id".to_string(), bundle_id.into());
+        handled.insert("CFBundleIdentifier");
+    }
+    let short_version = info_string("CFBundleShortVersionString");
+    let version = info_string("CFBundleVersion");
+    if let Some(app_version) = short_version.or(version) {
+        manifest.insert("app-version".to_string(), app_version.into());
+        handled.extend(["CFBundleShortVersionString", "CFBundleVersion"]);
+        if let Some(build_number) = version.filter(|v| *v != app_version) {
+            manifest.insert("build-number".to_string(), build_number.into());
+        }
+    }
+
+    match info_string("CFBundleIconFile") {
+        Some("") => {
+            handled.insert("CFBundleIconFile");
+        }
+        Some(icon) => {
+            let file_name = if Path::new(icon).extension().is_some() {
+                icon.to_string()
+            } else {
+                format!("{}.icns", icon)
+            };
+            let icon_path = Path::new("Resources").join(&file_name);
+            // appgen names the icon after the file, so only a matching name can be rebuilt
+            if file_name == icon && contents.join(&icon_path).is_file() {
+                copy_item(&contents, &icon_path, &output)?;
+                manifest.insert("icon".to_string(), path_value(&icon_path));
+                skipped.insert(icon_path);
+                handled.insert("CFBundleIconFile");
+            }
+        }
+        None => {}
+    }
+
+    // appgen hides the app from the Dock unless the terminal is shown
+    let agent = info_bool("LSUIElement");
+    if info.get("LSUIElement").is_none() || agent.is_some() {
+        manifest.insert("show-terminal".to_string(), (!agent.unwrap_or(false)).into());
+        handled.insert("LSUIElement");
+    }
+    if let Some(category) = info_string("LSApplicationCategoryType") {
+        manifest.insert("category".to_string(), category.into());
+        handled.insert("LSApplicationCategoryType");
+    }
+    if let Some(background_only) = info_bool("LSBackgroundOnly") {
+        manifest.insert("background-only".to_string(), background_only.into());
+        handled.insert("LSBackgroundOnly");
+    }
+
+    if fs::read(contents.join("Info.plist"))?.starts_with(b"bplist") {
+        manifest.insert("plist-format".to_string(), "binary".into());
+    }
+
+    let mut merge = plist::Dictionary::new();
+    for (key, value) in &info {
+        if handled.contains(key.as_str()) || generated_value(key).as_ref() == Some(value) {
+            continue;
+        }
+        merge.insert(key.clone(), value.clone());
+    }
+    if !merge.is_empty() {
+        let merge_path = output.join(MERGE_PLIST_NAME);
+        plist::to_file_xml(&merge_path, &Value::Dictionary(merge.clone()))
+            .context(format!("Failed to write {}", merge_path.display()))?;
+        manifest.insert("merge-plist".to_string(), MERGE_PLIST_NAME.into());
+        println!("Kept {} Info.plist key(s) in {}", merge.len(), merge_path.display());
+    }
+
+    let mut additional_files = Vec::new();
+    for item in contents_items(&contents)? {
+        if skipped.contains(&item) {
+            continue;
+        }
+        copy_item(&contents, &item, &output)?;
+        let path = item.to_string_lossy();
+        additional_files.push(toml::Value::String(format!("{}:{}", path, path)));
+    }
+    if !additional_files.is_empty() {
+        manifest.insert("additional-file".to_string(), additional_files.into());
+    }
+
+    if contents.join("_CodeSignature").exists() {
+        println!("Note: the existing signature was not imported, add `sign = true` for an ad-hoc signature");
+    }
+
+    let manifest_path = output.join(MANIFEST_NAME);
+    let text = format!(
+        "# Imported from {} with `appgen import`\n{}",
+        app_path.display(),
+        toml::to_string(&manifest).context("Failed to serialize manifest")?
+    );
+    fs::write(&manifest_path, text).context(format!("Failed to write {}", manifest_path.display()))?;
+    Ok(manifest_path)
+}
+
+/// Items of Contents relative to it, listing MacOS and Resources entry by entry
+fn contents_items(contents: &Path) -> Result<Vec<PathBuf>> {
+    let mut items = Vec::new();
+    for entry in read_dir_sorted(contents)? {
+        let file_name = PathBuf::from(entry.file_name().unwrap());
+        if (file_name == Path::new("MacOS") || file_name == Path::new("Resources")) && entry.is_dir() {
+            for child in read_dir_sorted(&entry)? {
+                items.push(file_name.join(child.file_name().unwrap()));
+            }
+        } else {
+            items.push(file_name);
+        }
+    }
+    Ok(items)
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)
+        .context(format!("Failed to read directory: {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Copies `Contents/{item}` to `{output}/{item}`
+fn copy_item(contents: &Path, item: &Path, output: &Path) -> Result<()> {
+    let source = contents.join(item);
+    let target = output.join(item);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    let metadata = fs::symlink_metadata(&source).context(format!("Failed to read {}", source.display()))?;
+    if metadata.is_symlink() {
+        let link = fs::read_link(&source)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&link, &target)
+            .context(format!("Failed to create symlink: {}", target.display()))?;
+        #[cfg(not(unix))]
+        fs::copy(&source, &target).context(format!("Failed to copy {}", source.display()))?;
+    } else if metadata.is_dir() {
+        crate::copy_tree(&source, &target)?;
+    } else {
+        fs::copy(&source, &target).context(format!("Failed to copy {}", source.display()))?;
+    }
+    Ok(())
+}
+
+fn path_value(path: &Path) -> toml::Value {
+    toml::Value::String(path.to_string_lossy().to_string())
+}
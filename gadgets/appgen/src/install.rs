@@ -0,0 +1,275 @@
+//! Installing and uninstalling bundles.
+//!
+//! `appgen install` copies a bundle into an applications folder, moving any copy it replaces to a
+//! backup folder outside the places Launch Services scans, so the old version is not offered when
+//! opening documents. `appgen uninstall` finds installed bundles by identifier and removes them,
+//! optionally with the data the app left in `~/Library` and its LaunchAgents.
+//!
+//! Launch Services is told about added and removed bundles with `lsregister` when it exists, so
+//! both subcommands also work (and are tested) on systems without it.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const LSREGISTER: &str =
+    "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister";
+const LAUNCHCTL: &str = "/bin/launchctl";
+
+/// Folders of `~/Library` holding app data in a `<bundle-id>` entry
+const DATA_FOLDERS: &[&str] = &["Application Support", "Caches", "Logs", "Preferences"];
+
+/// Options of the `install` subcommand
+#[derive(clap::Args, Debug)]
+pub struct InstallArgs {
+    /// Path to the .app bundle to install
+    #[arg(value_name = "APP")]
+    app: String,
+
+    /// Applications folder to install into
+    #[arg(long, value_name = "DIR", default_value = "/Applications")]
+    to: String,
+
+    /// Folder receiving the replaced copy
+    /// (defaults to ~/Library/Application Support/appgen/Backups)
+    #[arg(long, value_name = "DIR")]
+    backup_dir: Option<String>,
+}
+
+/// Options of the `uninstall` subcommand
+#[derive(clap::Args, Debug)]
+pub struct UninstallArgs {
+    /// Bundle identifier (CFBundleIdentifier) of the app to remove
+    #[arg(value_name = "BUNDLE_ID")]
+    bundle_id: String,
+
+    /// Applications folder to search, can be repeated (defaults to /Applications and ~/Applications)
+    #[arg(long = "from", value_name = "DIR")]
+    from: Vec<String>,
+
+    /// Also remove ~/Library/{Application Support,Caches,Logs,Preferences}/<bundle-id>
+    #[arg(long, default_value_t = false)]
+    data: bool,
+
+    /// Also unload and remove the LaunchAgents of ~/Library/LaunchAgents starting the app
+    #[arg(long, default_value_t = false)]
+    launch_agents: bool,
+
+    /// Home folder whose Library is cleaned (defaults to $HOME)
+    #[arg(long, value_name = "DIR")]
+    home: Option<String>,
+
+    /// List what would be removed without removing anything
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+}
+
+/// Copies a bundle into the applications folder, returning the installed path
+pub fn install(args: &InstallArgs) -> Result<PathBuf> {
+    let app_path = Path::new(&args.app);
+    let info = crate::read_info_plist(app_path)?;
+    let bundle_id = info
+        .get("CFBundleIdentifier")
+        .and_then(plist::Value::as_string)
+        .context(format!("{} has no CFBundleIdentifier", app_path.display()))?;
+    let file_name = app_path
+        .file_name()
+        .context(format!("Invalid app path: {}", args.app))?
+        .to_string_lossy()
+        .to_string();
+    let to = Path::new(&args.to);
+    fs::create_dir_all(to).context(format!("Failed to create {}", to.display()))?;
+    let target = to.join(&file_name);
+    if fs::canonicalize(app_path).ok() == fs::canonicalize(&target).ok() {
+        anyhow::bail!("{} is already installed at {}", args.app, target.display());
+    }
+
+    // Copy next to the target first so a failed copy leaves the installed version alone
+    let staging = to.join(format!(".{}.appgen-install", file_name));
+    if staging.exists() {
+        fs::remove_dir_all(&staging).context(format!("Failed to remove {}", staging.display()))?;
+    }
+    crate::copy_tree(app_path, &staging)?;
+
+    if fs::symlink_metadata(&target).is_ok() {
+        let backup_dir = match &args.backup_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => home()?.join("Library/Application Support/appgen/Backups"),
+        };
+        let backup_dir = backup_dir.join(bundle_id);
+        fs::create_dir_all(&backup_dir).context(format!("Failed to create {}", backup_dir.display()))?;
+        let stem = Path::new(&file_name).file_stem().unwrap().to_string_lossy();
+        let backup = backup_dir.join(format!(
+            "{} {}.app",
+            stem,
+            chrono::Local::now().format("%Y-%m-%d %H.%M.%S")
+        ));
+        lsregister(&["-u"], &target);
+        move_dir(&target, &backup)?;
+        println!("Moved the existing copy to: {}", backup.display());
+    }
+    fs::rename(&staging, &target).context(format!("Failed to move {} to {}", staging.display(), target.display()))?;
+    lsregister(&["-f"], &target);
+    Ok(target)
+}
+
+/// Removes installed copies of an app and optionally its data and LaunchAgents
+pub fn uninstall(args: &UninstallArgs) -> Result<()> {
+    let home = match &args.home {
+        Some(home) => PathBuf::from(home),
+        None => home()?,
+    };
+    let folders = if args.from.is_empty() {
+        vec![PathBuf::from("/Applications"), home.join("Applications")]
+    } else {
+        args.from.iter().map(PathBuf::from).collect()
+    };
+
+    let apps = find_apps(&folders, &args.bundle_id)?;
+    let mut data = Vec::new();
+    if args.data {
+        let library = home.join("Library");
+        for folder in DATA_FOLDERS {
+            data.push(library.join(folder).join(&args.bundle_id));
+        }
+        data.push(library.join("Preferences").join(format!("{}.plist", args.bundle_id)));
+        data.retain(|path| fs::symlink_metadata(path).is_ok());
+    }
+    let agents = if args.launch_agents {
+        find_launch_agents(&home.join("Library/LaunchAgents"), &args.bundle_id, &apps)?
+    } else {
+        Vec::new()
+    };
+
+    if apps.is_empty() && data.is_empty() && agents.is_empty() {
+        let searched: Vec<String> = folders.iter().map(|f| f.display().to_string()).collect();
+        anyhow::bail!(
+            "No app with bundle id {} found in {}",
+            args.bundle_id,
+            searched.join(", ")
+        );
+    }
+    if apps.is_empty() {
+        println!("No app with bundle id {} is installed", args.bundle_id);
+    }
+
+    for agent in &agents {
+        if args.dry_run {
+            println!("Would unload and remove LaunchAgent: {}", agent.display());
+            continue;
+        }
+        if Path::new(LAUNCHCTL).exists() {
+            // Fails for agents that are not loaded, which is fine
+            let _ = Command::new(LAUNCHCTL).arg("unload").arg(agent).output();
+        }
+        fs::remove_file(agent).context(format!("Failed to remove {}", agent.display()))?;
+        println!("Removed LaunchAgent: {}", agent.display());
+    }
+    for app in &apps {
+        if args.dry_run {
+            println!("Would remove app: {}", app.display());
+            continue;
+        }
+        lsregister(&["-u"], app);
+        fs::remove_dir_all(app).context(format!("Failed to remove {}", app.display()))?;
+        println!("Removed app: {}", app.display());
+    }
+    for path in &data {
+        if args.dry_run {
+            println!("Would remove data: {}", path.display());
+            continue;
+        }
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+        .context(format!("Failed to remove {}", path.display()))?;
+        println!("Removed data: {}", path.display());
+    }
+    Ok(())
+}
+
+fn home() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .context("HOME is not set")
+}
+
+/// Bundles of the folders with the bundle identifier
+fn find_apps(folders: &[PathBuf], bundle_id: &str) -> Result<Vec<PathBuf>> {
+    let mut apps = Vec::new();
+    for folder in folders.iter().filter(|folder| folder.is_dir()) {
+        let mut entries = fs::read_dir(folder)
+            .context(format!("Failed to read directory: {}", folder.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+        for path in entries {
+            if path.extension().is_none_or(|ext| ext != "app") {
+                continue;
+            }
+            let Ok(info) = crate::read_info_plist(&path) else {
+                continue;
+            };
+            if info.get("CFBundleIdentifier").and_then(plist::Value::as_string) == Some(bundle_id) {
+                apps.push(path);
+            }
+        }
+    }
+    Ok(apps)
+}
+
+/// LaunchAgents labelled with the bundle identifier (or `<bundle-id>.*`) or starting a program
+/// inside one of the apps
+fn find_launch_agents(folder: &Path, bundle_id: &str, apps: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    if !folder.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut agents = Vec::new();
+    for entry in fs::read_dir(folder).context(format!("Failed to read directory: {}", folder.display()))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "plist") {
+            continue;
+        }
+        let Some(agent) = plist::Value::from_file(&path).ok().and_then(|v| v.into_dictionary()) else {
+            continue;
+        };
+        let label = agent.get("Label").and_then(plist::Value::as_string).unwrap_or_default();
+        let program = agent.get("Program").and_then(plist::Value::as_string).or_else(|| {
+            agent
+                .get("ProgramArguments")
+                .and_then(plist::Value::as_array)
+                .and_then(|arguments| arguments.first())
+                .and_then(plist::Value::as_string)
+        });
+        let starts_app = program.is_some_and(|program| apps.iter().any(|app| Path::new(program).starts_with(app)));
+        if label == bundle_id || label.starts_with(&format!("{}.", bundle_id)) || starts_app {
+            agents.push(path);
+        }
+    }
+    agents.sort();
+    Ok(agents)
+}
+
+/// Moves a directory, copying it when it is on another volume
+fn move_dir(source: &Path, target: &Path) -> Result<()> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    crate::copy_tree(source, target)?;
+    fs::remove_dir_all(source).context(format!("Failed to remove {}", source.display()))
+}
+
+/// Registers (`-f`) or unregisters (`-u`) a bundle with Launch Services, where available
+fn lsregister(flags: &[&str], app: &Path) {
+    if !Path::new(LSREGISTER).exists() {
+        return;
+    }
+    match Command::new(LSREGISTER).args(flags).arg(app).status() {
+        Ok(status) if status.success() => {}
+        _ => println!("Warning: lsregister {} {} failed", flags.join(" "), app.display()),
+    }
+}
@@ -0,0 +1,151 @@
+//! Metadata linting run before anything is written.
+//!
+//! Problems that make the bundle unusable are errors. Questionable values are warnings,
+//! which `--strict` turns into errors.
+
+use anyhow::Result;
+
+/// Bundle id appgen falls back to when none is given
+const EXAMPLE_BUNDLE_ID: &str = "com.example.app";
+/// Longest bundle id Launch Services accepts
+const MAX_BUNDLE_ID_LENGTH: usize = 155;
+
+/// Metadata going into Info.plist
+pub struct Metadata<'a> {
+    pub name: &'a str,
+    pub bundle_id: &'a str,
+    /// CFBundleVersion
+    pub version: &'a str,
+    /// CFBundleShortVersionString
+    pub short_version: &'a str,
+}
+
+#[derive(Default)]
+struct Findings {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Findings {
+    fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+}
+
+/// Checks the metadata, printing warnings and failing on errors
+pub fn check(metadata: &Metadata, strict: bool) -> Result<()> {
+    let mut findings = Findings::default();
+    check_name(metadata.name, &mut findings);
+    check_bundle_id(metadata.bundle_id, &mut findings);
+    if !is_numeric_version(metadata.version) {
+        findings.error(format!(
+            "CFBundleVersion must be 1 to 3 dot separated integers such as 1.2.3, got \"{}\"",
+            metadata.version
+        ));
+    }
+    if metadata.short_version != metadata.version && !is_numeric_version(metadata.short_version) {
+        findings.warn(format!(
+            "CFBundleShortVersionString should be 1 to 3 dot separated integers such as 1.2.3, got \"{}\"",
+            metadata.short_version
+        ));
+    }
+
+    if strict {
+        findings.errors.append(&mut findings.warnings);
+    }
+    for warning in &findings.warnings {
+        println!("Warning: {}", warning);
+    }
+    if !findings.errors.is_empty() {
+        anyhow::bail!(
+            "Invalid metadata for {}:\n  {}",
+            metadata.name,
+            findings.errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Fails when a bundle id is not valid, leaving its warnings to the build that uses it
+pub fn check_bundle_id_format(bundle_id: &str) -> Result<()> {
+    let mut findings = Findings::default();
+    check_bundle_id(bundle_id, &mut findings);
+    if !findings.errors.is_empty() {
+        anyhow::bail!("{}", findings.errors.join("\n  "));
+    }
+    Ok(())
+}
+
+fn check_name(name: &str, findings: &mut Findings) {
+    if name.trim().is_empty() {
+        findings.error("App name must not be empty".to_string());
+        return;
+    }
+    if name.contains('/') {
+        findings.error(format!("App name \"{}\" must not contain '/'", name));
+    }
+    if name.contains(':') {
+        findings.warn(format!(
+            "App name \"{}\" contains ':', which Finder displays as '/'",
+            name
+        ));
+    }
+    if name.starts_with('.') {
+        findings.warn(format!(
+            "App name \"{}\" starts with '.', the bundle will be hidden",
+            name
+        ));
+    }
+    if name.ends_with(".app") {
+        findings.warn(format!("App name \"{}\" should not include the .app extension", name));
+    }
+    if name.trim() != name {
+        findings.warn(format!("App name \"{}\" has leading or trailing whitespace", name));
+    }
+}
+
+fn check_bundle_id(bundle_id: &str, findings: &mut Findings) {
+    if let Some(c) = bundle_id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '.'))
+    {
+        findings.error(format!(
+            "Bundle id \"{}\" contains '{}', only letters, digits, '-' and '.' are allowed",
+            bundle_id, c
+        ));
+        return;
+    }
+    let components: Vec<&str> = bundle_id.split('.').collect();
+    if components.len() < 2 || components.iter().any(|c| c.is_empty()) {
+        findings.error(format!(
+            "Bundle id \"{}\" must be in reverse-DNS form such as com.company.app",
+            bundle_id
+        ));
+        return;
+    }
+    if bundle_id.len() > MAX_BUNDLE_ID_LENGTH {
+        findings.warn(format!(
+            "Bundle id \"{}\" is longer than {} characters",
+            bundle_id, MAX_BUNDLE_ID_LENGTH
+        ));
+    }
+    if bundle_id == EXAMPLE_BUNDLE_ID || bundle_id.starts_with("com.example.") {
+        findings.warn(format!(
+            "Bundle id \"{}\" is the example id, set your own with --bundle-id",
+            bundle_id
+        ));
+    }
+}
+
+/// Whether a version is 1 to 3 dot separated integers
+pub fn is_numeric_version(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    (1..=3).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
@@ -4,7 +4,6 @@ use std::io::Write;
 
 fn main() {
     let mut f = fs::OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open("~/tmp/a.log")
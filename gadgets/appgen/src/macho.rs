@@ -0,0 +1,349 @@
+//! Minimal Mach-O reading and patching shared by the signing code.
+//!
+//! Only little-endian images (x86_64, arm64 and their 32-bit counterparts) are handled,
+//! thin or wrapped in a universal (fat) binary.
+
+use anyhow::{Context, Result};
+
+pub const MH_MAGIC: u32 = 0xfeed_face;
+pub const MH_MAGIC_64: u32 = 0xfeed_facf;
+const FAT_MAGIC: u32 = 0xcafe_babe;
+
+pub const LC_SEGMENT: u32 = 0x1;
+pub const LC_SYMTAB: u32 = 0x2;
+pub const LC_DYSYMTAB: u32 = 0xb;
+pub const LC_SEGMENT_64: u32 = 0x19;
+pub const LC_UUID: u32 = 0x1b;
+pub const LC_CODE_SIGNATURE: u32 = 0x1d;
+pub const LC_RPATH: u32 = 0x8000_001c;
+
+/// One architecture of a (possibly universal) binary
+pub struct Slice {
+    pub cputype: u32,
+    pub cpusubtype: u32,
+    /// Alignment of the slice inside a universal binary, as a power of two
+    pub align: u32,
+    pub data: Vec<u8>,
+}
+
+/// Whether the file starts with a Mach-O or universal binary magic
+pub fn is_macho(data: &[u8]) -> bool {
+    if data.len() < 8 {
+        return false;
+    }
+    let le = u32::from_le_bytes(data[..4].try_into().unwrap());
+    let be = u32::from_be_bytes(data[..4].try_into().unwrap());
+    // Java class files share the universal magic, but never have this few "architectures"
+    le == MH_MAGIC || le == MH_MAGIC_64 || (be == FAT_MAGIC && u32::from_be_bytes(data[4..8].try_into().unwrap()) < 20)
+}
+
+/// Splits a binary into its slices, returning whether it was a universal binary
+pub fn split_slices(data: &[u8]) -> Result<(bool, Vec<Slice>)> {
+    if data.len() >= 8 && u32::from_be_bytes(data[..4].try_into().unwrap()) == FAT_MAGIC {
+        let count = u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+        let mut slices = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = data
+                .get(8 + i * 20..8 + (i + 1) * 20)
+                .context("Truncated universal binary header")?;
+            let field = |n: usize| u32::from_be_bytes(entry[n * 4..n * 4 + 4].try_into().unwrap());
+            let (offset, size) = (field(2) as usize, field(3) as usize);
+            let slice = data
+                .get(offset..offset + size)
+                .context("Universal binary slice out of bounds")?;
+            slices.push(Slice {
+                cputype: field(0),
+                cpusubtype: field(1),
+                align: field(4),
+                data: slice.to_vec(),
+            });
+        }
+        return Ok((true, slices));
+    }
+    let macho = MachO::parse(data.to_vec())?;
+    Ok((
+        false,
+        vec![Slice {
+            cputype: macho.cputype(),
+            cpusubtype: macho.cpusubtype(),
+            align: 14,
+            data: macho.data,
+        }],
+    ))
+}
+
+/// Reassembles slices, as a universal binary if `fat` is set
+pub fn join_slices(fat: bool, slices: Vec<Slice>) -> Vec<u8> {
+    if !fat {
+        return slices.into_iter().next().map(|s| s.data).unwrap_or_default();
+    }
+    let mut offsets = Vec::with_capacity(slices.len());
+    let mut offset = 8 + 20 * slices.len();
+    for slice in &slices {
+        let align = 1usize << slice.align;
+        offset = offset.div_ceil(align) * align;
+        offsets.push(offset);
+        offset += slice.data.len();
+    }
+    let mut out = Vec::with_capacity(offset);
+    out.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+    out.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+    for (slice, offset) in slices.iter().zip(&offsets) {
+        for value in [
+            slice.cputype,
+            slice.cpusubtype,
+            *offset as u32,
+            slice.data.len() as u32,
+            slice.align,
+        ] {
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+    for (slice, offset) in slices.iter().zip(&offsets) {
+        out.resize(*offset, 0);
+        out.extend_from_slice(&slice.data);
+    }
+    out
+}
+
+/// Human readable name of a CPU type
+pub fn arch_name(cputype: u32) -> String {
+    match cputype {
+        7 => "i386".to_string(),
+        0x0100_0007 => "x86_64".to_string(),
+        12 => "arm".to_string(),
+        0x0100_000c => "arm64".to_string(),
+        0x0200_000c => "arm64_32".to_string(),
+        other => format!("cpu({:#x})", other),
+    }
+}
+
+/// A segment load command
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// Offset of the load command in the file
+    pub cmd_offset: usize,
+    pub name: String,
+    pub fileoff: u64,
+    pub filesize: u64,
+}
+
+/// A thin Mach-O image
+pub struct MachO {
+    pub data: Vec<u8>,
+    pub is_64: bool,
+}
+
+impl MachO {
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        if data.len() < 28 {
+            anyhow::bail!("File too small to be a Mach-O image");
+        }
+        let is_64 = match u32::from_le_bytes(data[..4].try_into().unwrap()) {
+            MH_MAGIC => false,
+            MH_MAGIC_64 => true,
+            _ => anyhow::bail!("Not a little-endian Mach-O image"),
+        };
+        let macho = MachO { data, is_64 };
+        if macho.header_size() + macho.sizeofcmds() as usize > macho.data.len() {
+            anyhow::bail!("Truncated Mach-O load commands");
+        }
+        Ok(macho)
+    }
+
+    pub fn u32_at(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.data[offset..offset + 4].try_into().unwrap())
+    }
+
+    pub fn u64_at(&self, offset: usize) -> u64 {
+        u64::from_le_bytes(self.data[offset..offset + 8].try_into().unwrap())
+    }
+
+    pub fn set_u32(&mut self, offset: usize, value: u32) {
+        self.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn set_u64(&mut self, offset: usize, value: u64) {
+        self.data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn cputype(&self) -> u32 {
+        self.u32_at(4)
+    }
+
+    pub fn cpusubtype(&self) -> u32 {
+        self.u32_at(8)
+    }
+
+    pub fn header_size(&self) -> usize {
+        if self.is_64 { 32 } else { 28 }
+    }
+
+    pub fn ncmds(&self) -> u32 {
+        self.u32_at(16)
+    }
+
+    pub fn sizeofcmds(&self) -> u32 {
+        self.u32_at(20)
+    }
+
+    /// `(cmd, offset, size)` of every load command
+    pub fn load_commands(&self) -> Vec<(u32, usize, usize)> {
+        let mut commands = Vec::new();
+        let mut offset = self.header_size();
+        let end = offset + self.sizeofcmds() as usize;
+        for _ in 0..self.ncmds() {
+            if offset + 8 > end {
+                break;
+            }
+            let cmd = self.u32_at(offset);
+            let size = self.u32_at(offset + 4) as usize;
+            if size < 8 || offset + size > end {
+                break;
+            }
+            commands.push((cmd, offset, size));
+            offset += size;
+        }
+        commands
+    }
+
+    pub fn segments(&self) -> Vec<Segment> {
+        self.load_commands()
+            .into_iter()
+            .filter_map(|(cmd, offset, _)| {
+                let name_bytes = &self.data[offset + 8..offset + 24];
+                let name = String::from_utf8_lossy(name_bytes).trim_end_matches('\0').to_string();
+                match cmd {
+                    LC_SEGMENT_64 => Some(Segment {
+                        cmd_offset: offset,
+                        name,
+                        fileoff: self.u64_at(offset + 40),
+                        filesize: self.u64_at(offset + 48),
+                    }),
+                    LC_SEGMENT => Some(Segment {
+                        cmd_offset: offset,
+                        name,
+                        fileoff: self.u32_at(offset + 32) as u64,
+                        filesize: self.u32_at(offset + 36) as u64,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    pub fn segment(&self, name: &str) -> Option<Segment> {
+        self.segments().into_iter().find(|s| s.name == name)
+    }
+
+    /// Updates the file and VM size of a segment load command
+    pub fn set_segment_size(&mut self, segment: &Segment, filesize: u64, vmsize: u64) {
+        if self.is_64 {
+            self.set_u64(segment.cmd_offset + 48, filesize);
+            self.set_u64(segment.cmd_offset + 32, vmsize);
+        } else {
+            self.set_u32(segment.cmd_offset + 36, filesize as u32);
+            self.set_u32(segment.cmd_offset + 28, vmsize as u32);
+        }
+    }
+
+    /// File offset of the first section contents, load commands must end before it
+    fn first_section_offset(&self) -> usize {
+        let mut first = self.data.len();
+        for (cmd, offset, _) in self.load_commands() {
+            let (nsects_at, sections_at, section_size, offset_field) = match cmd {
+                LC_SEGMENT_64 => (offset + 64, offset + 72, 80, 48),
+                LC_SEGMENT => (offset + 48, offset + 56, 68, 40),
+                _ => continue,
+            };
+            for i in 0..self.u32_at(nsects_at) as usize {
+                let section_offset = self.u32_at(sections_at + i * section_size + offset_field) as usize;
+                if section_offset != 0 {
+                    first = first.min(section_offset);
+                }
+            }
+        }
+        first
+    }
+
+    /// Appends a load command, failing if there is no padding left after the existing ones
+    pub fn add_load_command(&mut self, command: &[u8]) -> Result<usize> {
+        let offset = self.header_size() + self.sizeofcmds() as usize;
+        if offset + command.len() > self.first_section_offset() {
+            anyhow::bail!("Not enough space for a new load command in the Mach-O header");
+        }
+        self.data[offset..offset + command.len()].copy_from_slice(command);
+        let ncmds = self.ncmds();
+        let sizeofcmds = self.sizeofcmds();
+        self.set_u32(16, ncmds + 1);
+        self.set_u32(20, sizeofcmds + command.len() as u32);
+        Ok(offset)
+    }
+
+    /// Removes the load command at `offset`, moving the following ones up
+    pub fn remove_load_command(&mut self, offset: usize, size: usize) {
+        let end = self.header_size() + self.sizeofcmds() as usize;
+        self.data.copy_within(offset + size..end, offset);
+        self.data[end - size..end].fill(0);
+        let ncmds = self.ncmds();
+        let sizeofcmds = self.sizeofcmds();
+        self.set_u32(16, ncmds - 1);
+        self.set_u32(20, sizeofcmds - size as u32);
+    }
+
+    /// The LC_UUID, formatted as crash reports and `dwarfdump --uuid` print it
+    pub fn uuid(&self) -> Option<String> {
+        let (_, offset, _) = self
+            .load_commands()
+            .into_iter()
+            .find(|(cmd, _, size)| *cmd == LC_UUID && *size >= 24)?;
+        let hex: String = self.data[offset + 8..offset + 24]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        Some(format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        ))
+    }
+
+    /// Paths of the LC_RPATH commands
+    pub fn rpaths(&self) -> Vec<String> {
+        self.load_commands()
+            .into_iter()
+            .filter(|(cmd, _, _)| *cmd == LC_RPATH)
+            .filter_map(|(_, offset, size)| {
+                let path_offset = self.u32_at(offset + 8) as usize;
+                let bytes = self.data.get(offset + path_offset..offset + size)?;
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+            })
+            .collect()
+    }
+
+    /// Appends an LC_RPATH command, padded to the pointer size
+    pub fn add_rpath(&mut self, path: &str) -> Result<()> {
+        let align = if self.is_64 { 8 } else { 4 };
+        let size = (12 + path.len() + 1).div_ceil(align) * align;
+        let mut command = Vec::with_capacity(size);
+        command.extend_from_slice(&LC_RPATH.to_le_bytes());
+        command.extend_from_slice(&(size as u32).to_le_bytes());
+        command.extend_from_slice(&12u32.to_le_bytes());
+        command.extend_from_slice(path.as_bytes());
+        command.resize(size, 0);
+        self.add_load_command(&command)?;
+        Ok(())
+    }
+
+    /// `(command offset, dataoff, datasize)` of LC_CODE_SIGNATURE
+    pub fn code_signature(&self) -> Option<(usize, u32, u32)> {
+        self.load_commands()
+            .into_iter()
+            .find(|(cmd, _, _)| *cmd == LC_CODE_SIGNATURE)
+            .map(|(_, offset, _)| (offset, self.u32_at(offset + 8), self.u32_at(offset + 12)))
+    }
+}
@@ -1,11 +1,37 @@
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use plist::Value;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use fs_extra::dir::{self, CopyOptions};
 
+mod appcast;
+mod assets;
+mod cask;
+mod codesign;
+mod completions;
+mod diff;
+mod dmg;
+mod framework;
+mod icon;
+mod import;
+mod install;
+mod lint;
+mod macho;
+mod manifest;
+mod nested;
+mod pkg;
+mod privacy;
+mod script;
+mod size;
+mod strip;
+mod template;
+mod version;
+mod watch;
+mod workspace;
+
 #[derive(Debug, Clone, ValueEnum)]
 enum DefaultLocation {
     Resources,
@@ -13,9 +39,66 @@ enum DefaultLocation {
     Contents,
 }
 
+/// Bundle package type (CFBundlePackageType) of applications
+const PACKAGE_TYPE: &str = "APPL";
+/// Creator code (CFBundleSignature), `????` for apps without a registered one
+const BUNDLE_SIGNATURE: &str = "????";
+
+/// Serialization format of Info.plist
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PlistFormat {
+    Xml,
+    Binary,
+}
+
 /// A macOS app generator that packages an executable into an .app bundle
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, args_override_self = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    build: Option<Args>,
+
+    // Kept out of `Args`, clap cannot detect an optional flattened struct that nests another one
+    #[command(flatten)]
+    signing: codesign::SigningArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Pack an existing .app bundle into a compressed DMG disk image
+    Dmg(dmg::DmgArgs),
+    /// Build a flat .pkg installer from an existing .app bundle
+    Pkg(pkg::PkgArgs),
+    /// Generate a Homebrew cask (and optionally an install script) for a bundle
+    Cask(cask::CaskArgs),
+    /// Generate or update a Sparkle appcast from a directory of release archives
+    Appcast(appcast::AppcastArgs),
+    /// Ad-hoc sign an existing .app bundle and seal its resources
+    Sign(codesign::SignArgs),
+    /// Verify the ad-hoc signature and sealed resources of a bundle
+    Verify(codesign::VerifyArgs),
+    /// Write a manifest rebuilding an existing .app bundle, copying its files next to it
+    Import(import::ImportArgs),
+    /// Compare two .app bundles: Info.plist keys, files, permissions and architectures
+    Diff(diff::DiffArgs),
+    /// Copy a bundle into /Applications, keeping a backup of the copy it replaces
+    Install(install::InstallArgs),
+    /// Remove an installed app by bundle id, optionally with its data and LaunchAgents
+    Uninstall(install::UninstallArgs),
+    /// Break the size of a bundle down by directory and file type and find duplicate files
+    Size(size::SizeArgs),
+    /// Print the completion script of a shell
+    Completions(completions::CompletionsArgs),
+    /// Print or write the man page
+    Man(completions::ManArgs),
+}
+
+/// Options for building an .app bundle
+#[derive(clap::Args, Debug, Clone)]
 struct Args {
     /// Path to the executable file to package
     #[arg(short, long)]
@@ -25,14 +108,25 @@ struct Args {
     #[arg(short, long)]
     name: String,
 
-    /// Optional icon file path (.icns format)
+    /// Optional icon file path: .icns, or .svg, .ico, .png, a directory of PNG files or an
+    /// .iconset folder, converted to .icns, or an .xcassets asset catalog compiled to Assets.car
     #[arg(short, long)]
     icon: Option<String>,
 
-    /// Optional app version
+    /// Icon set of the asset catalog given to --icon (CFBundleIconName)
+    #[arg(long, value_name = "NAME", default_value = "AppIcon")]
+    icon_name: String,
+
+    /// Optional app version (CFBundleShortVersionString)
+    /// "from-git" uses the latest vX.Y.Z tag reachable from HEAD
     #[arg(short = 'v', long, default_value = "1.0.0")]
     app_version: String,
 
+    /// Build number (CFBundleVersion), defaults to the app version
+    /// "auto" uses the build number of the CI system, or the commit count outside CI
+    #[arg(long, value_name = "NUMBER")]
+    build_number: Option<String>,
+
     /// Optional bundle identifier
     #[arg(short, long, default_value = "com.example.app")]
     bundle_id: String,
@@ -56,35 +150,287 @@ struct Args {
     
     /// Show terminal window when the application runs
     /// By default, the terminal window is hidden
-    #[arg(short = 't', long = "show-terminal", default_value_t = false)]
+    #[arg(short = 't', long = "show-terminal", default_value_t = false, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
     show_terminal: bool,
     
     /// Enable single instance mode to ensure only one instance of the app runs per user
     /// This adds code to prevent multiple instances of the application from running simultaneously
-    #[arg(short = 's', long = "single-instance", default_value_t = false)]
+    #[arg(short = 's', long = "single-instance", default_value_t = false, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
     single_instance: bool,
+    
+    /// Preset options for a common app shape, options given later override the template
+    #[arg(long, value_enum)]
+    template: Option<template::Template>,
+    
+    /// App Store category (LSApplicationCategoryType)
+    /// Example: --category public.app-category.utilities
+    #[arg(long, value_name = "UTI")]
+    category: Option<String>,
+    
+    /// Run as a faceless background process (LSBackgroundOnly)
+    #[arg(long, default_value_t = false, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    background_only: bool,
+    
+    /// Open the executable in a Terminal window when the app is launched
+    #[arg(long, default_value_t = false, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    run_in_terminal: bool,
+    
+    /// File extension the app can open as a viewer (CFBundleDocumentTypes), can be repeated
+    #[arg(long = "document-type", value_name = "EXT")]
+    document_types: Vec<String>,
+
+    /// Ad-hoc sign the bundle after building it
+    /// Writes Contents/_CodeSignature/CodeResources and embeds a signature into the executable
+    #[arg(long, default_value_t = false)]
+    sign: bool,
+
+    /// Read build options from a TOML manifest, options given on the command line override it
+    /// Keys are the long option names, e.g. name = "My App" or additional-file = ["data.txt"]
+    #[arg(long, value_name = "TOML")]
+    manifest: Option<String>,
+
+    /// Helper executable copied into Contents/Helpers
+    #[arg(long = "helper", value_name = "PATH")]
+    helpers: Vec<String>,
+
+    /// Framework copied into Contents/Frameworks with its symlinks, can be repeated
+    /// Adds an @executable_path/../Frameworks runpath to the executable
+    #[arg(long = "framework", value_name = "PATH")]
+    frameworks: Vec<String>,
+
+    /// Manifest of a login item bundle built into Contents/Library/LoginItems
+    #[arg(long = "login-item", value_name = "TOML")]
+    login_items: Vec<String>,
+
+    /// Manifest of a helper app bundle built into Contents/Helpers
+    #[arg(long = "helper-app", value_name = "TOML")]
+    helper_apps: Vec<String>,
+
+    /// Virtualenv vendored into Contents/Resources/venv and used to run a Python script executable
+    #[arg(long, value_name = "DIR")]
+    python_venv: Option<String>,
+
+    /// node_modules directory vendored into Contents/Resources/node_modules for a Node script executable
+    #[arg(long, value_name = "DIR")]
+    node_modules: Option<String>,
+
+    /// Interpreter binary, or a directory with bin/<interpreter>, vendored into Contents/Resources/runtime
+    /// and used instead of the one named by the script's shebang
+    #[arg(long, value_name = "PATH")]
+    runtime: Option<String>,
+
+    /// Plist whose keys are added to the generated Info.plist, replacing generated keys of the same name
+    #[arg(long, value_name = "PLIST")]
+    merge_plist: Option<String>,
+
+    /// Format Info.plist is written in
+    #[arg(long, value_enum, default_value = "xml")]
+    plist_format: PlistFormat,
+
+    /// Protected resources the app uses, comma separated: adds their NS*UsageDescription keys
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "PERMISSION")]
+    privacy: Vec<privacy::Permission>,
+
+    /// Usage description of a permission instead of the default text, can be repeated
+    #[arg(long, value_name = "PERMISSION=TEXT", value_parser = privacy::parse_text)]
+    privacy_text: Vec<(privacy::Permission, String)>,
+
+    /// Replace files with identical contents by links to a single copy
+    #[arg(long, value_enum, value_name = "MODE")]
+    dedup: Option<size::DedupMode>,
+
+    /// Remove local symbols and debug sections from the Mach-O files in Contents/MacOS
+    /// The unstripped images are archived by LC_UUID in {output}/{name}.symbols.zip
+    #[arg(long, default_value_t = false)]
+    strip: bool,
+
+    /// Treat metadata warnings (such as the example bundle id) as errors
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Keep running and update the bundle whenever the executable, icon, additional files or manifest change
+    #[arg(long, default_value_t = false)]
+    watch: bool,
+
+    /// Restart the app after every rebuild in watch mode
+    #[arg(long, default_value_t = false, requires = "watch")]
+    relaunch: bool,
+
+    /// Nested bundles declared inline by the manifest
+    #[arg(skip)]
+    nested: Vec<nested::NestedBundle>,
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    // A manifest with [[app]] tables builds several apps instead of one
+    let raw_args: Vec<OsString> = std::env::args_os().collect();
+    if let Some(workspace_path) = workspace::find_workspace(&raw_args)? {
+        return workspace::build_all(&workspace_path, &raw_args);
+    }
+    
+    let cli = parse_cli()?;
+    
+    match (cli.command, cli.build) {
+        (Some(Commands::Dmg(dmg_args)), _) => {
+            let dmg_path = dmg::create_dmg(&dmg_args)?;
+            println!("Successfully created disk image at: {}", dmg_path.display());
+            Ok(())
+        }
+        (Some(Commands::Pkg(pkg_args)), _) => {
+            let pkg_path = pkg::create_pkg(&pkg_args)?;
+            println!("Successfully created installer package at: {}", pkg_path.display());
+            Ok(())
+        }
+        (Some(Commands::Cask(cask_args)), _) => {
+            for path in cask::create_cask(&cask_args)? {
+                println!("Successfully wrote: {}", path.display());
+            }
+            Ok(())
+        }
+        (Some(Commands::Appcast(appcast_args)), _) => {
+            let appcast_path = appcast::create_appcast(&appcast_args)?;
+            println!("Successfully wrote appcast at: {}", appcast_path.display());
+            Ok(())
+        }
+        (Some(Commands::Sign(sign_args)), _) => {
+            codesign::sign_bundle(Path::new(&sign_args.app), &sign_args.signing.resolve()?)?;
+            println!("Successfully signed: {}", sign_args.app);
+            Ok(())
+        }
+        (Some(Commands::Verify(verify_args)), _) => {
+            let problems = codesign::verify_bundle(Path::new(&verify_args.app))?;
+            if !problems.is_empty() {
+                for problem in &problems {
+                    eprintln!("{}", problem);
+                }
+                anyhow::bail!("{} failed verification with {} problem(s)", verify_args.app, problems.len());
+            }
+            println!("{}: valid on disk", verify_args.app);
+            Ok(())
+        }
+        (Some(Commands::Import(import_args)), _) => {
+            let manifest_path = import::import_app(&import_args)?;
+            println!("Successfully wrote manifest at: {}", manifest_path.display());
+            println!("Rebuild the bundle with: appgen --manifest {}", manifest_path.display());
+            Ok(())
+        }
+        (Some(Commands::Diff(diff_args)), _) => diff::diff_bundles(&diff_args),
+        (Some(Commands::Install(install_args)), _) => {
+            let installed = install::install(&install_args)?;
+            println!("Successfully installed app at: {}", installed.display());
+            Ok(())
+        }
+        (Some(Commands::Uninstall(uninstall_args)), _) => install::uninstall(&uninstall_args),
+        (Some(Commands::Size(size_args)), _) => size::report(&size_args),
+        (Some(Commands::Completions(completions_args)), _) => completions::print_completions(&completions_args),
+        (Some(Commands::Man(man_args)), _) => completions::write_man(&man_args),
+        (None, Some(args)) if args.watch => watch::run(args, cli.signing),
+        (None, Some(args)) => build_app(&args, &cli.signing),
+        (None, None) => {
+            use clap::CommandFactory;
+            Cli::command().print_help()?;
+            Ok(())
+        }
+    }
+}
+
+/// Parses the command line, exiting with clap's usage message on invalid options
+fn parse_cli() -> Result<Cli> {
+    parse_cli_from(std::env::args_os().collect()).map_err(|e| match e.downcast::<clap::Error>() {
+        Ok(clap_error) => clap_error.exit(),
+        Err(e) => e,
+    })
+}
+
+/// Parses raw arguments, expanding `--manifest` in front of the explicit options
+fn parse_cli_from(mut args: Vec<OsString>) -> Result<Cli> {
+    use clap::CommandFactory;
+    let mut nested = Vec::new();
+    if let Some(manifest_path) = manifest::find_manifest_arg(Cli::command(), &args) {
+        let manifest = manifest::load(&manifest_path, &Cli::command())?;
+        args.splice(1..1, manifest.args);
+        nested = manifest.nested;
+    }
+    // The template may come from the manifest, so it is expanded last, in front of everything
+    template::expand(Cli::command(), &mut args, 1)?;
+    let mut cli = Cli::try_parse_from(args)?;
+    if let Some(build) = cli.build.as_mut().filter(|b| b.manifest.is_some()) {
+        build.nested.extend(nested);
+    }
+    Ok(cli)
+}
+
+/// Runs the whole bundle generation pipeline
+fn build_app(args: &Args, signing: &codesign::SigningArgs) -> Result<()> {
+    // Validate the signing options up front so bad entitlements fail before anything is written
+    let sign_options = match (args.sign, signing.is_set()) {
+        (true, _) => Some(signing.resolve()?),
+        (false, true) => anyhow::bail!("Entitlements and --hardened-runtime only apply when signing, add --sign"),
+        (false, false) => None,
+    };
+    let args = &version::resolve(args)?;
+    lint::check(
+        &lint::Metadata {
+            name: &args.name,
+            bundle_id: &args.bundle_id,
+            version: args.build_number.as_ref().unwrap_or(&args.app_version),
+            short_version: &args.app_version,
+        },
+        args.strict,
+    )?;
+    let nested_bundles = nested::collect(args)?;
+    
+    if args.single_instance && args.run_in_terminal {
+        anyhow::bail!("--single-instance cannot be combined with --run-in-terminal, use --single-instance=false");
+    }
+    if matches!(args.template, Some(template::Template::DocumentViewer)) && args.document_types.is_empty() {
+        println!("Warning: the document-viewer template registers no document types without --document-type");
+    }
+    
+    // Check the executable path before the existing bundle is removed
+    executable_name(args)?;
     
     // Create the app bundle structure
-    let app_path = create_app_structure(&args)?;
+    let app_path = create_app_structure(args)?;
     
     // Copy the executable
-    copy_executable(&args, &app_path)?;
+    copy_executable(args, &app_path)?;
     
     // Create Info.plist
-    create_info_plist(&args, &app_path)?;
+    create_info_plist(args, &app_path)?;
     
     // Copy icon if provided
     if let Some(icon_path) = &args.icon {
-        copy_icon(icon_path, &app_path)?;
+        copy_icon(icon_path, &args.icon_name, &app_path)?;
     }
     
     // Copy additional files if specified
     if !args.additional_files.is_empty() {
-        copy_additional_files(&args, &app_path)?;
+        copy_additional_files(args, &app_path)?;
+    }
+    
+    // Copy frameworks and point the executable's runpath at them
+    framework::embed(args, &app_path)?;
+    
+    // Strip the executables once they are all in place
+    if args.strip {
+        strip::strip_bundle(args, &app_path)?;
+    }
+    
+    // Build nested bundles and copy helper executables
+    nested::embed(args, &nested_bundles, &app_path)?;
+    
+    // Link identical files together once everything is in place
+    if let Some(mode) = args.dedup {
+        size::dedup(&app_path, mode)?;
+    }
+    
+    // Scripts using protected resources need their usage descriptions
+    privacy::check_payloads(&app_path, args.strict)?;
+    
+    // Sign last, the signature seals everything written before
+    if let Some(sign_options) = &sign_options {
+        codesign::sign_bundle(&app_path, sign_options)?;
     }
     
     println!("Successfully created app bundle at: {}", app_path.display());
@@ -115,10 +461,17 @@ fn create_app_structure(args: &Args) -> Result<PathBuf> {
     Ok(app_path)
 }
 
+/// File name of the executable in Contents/MacOS
+fn executable_name(args: &Args) -> Result<&OsStr> {
+    Path::new(&args.executable)
+        .file_name()
+        .context(format!("Invalid executable path: {} (expected a file name)", args.executable))
+}
+
 /// Copies the executable to the app bundle
 fn copy_executable(args: &Args, app_path: &Path) -> Result<()> {
     let source_path = Path::new(&args.executable);
-    let executable_name = source_path.file_name().unwrap();
+    let executable_name = executable_name(args)?;
     let macos_dir = app_path.join("Contents").join("MacOS");
     let target_path = macos_dir.join(executable_name);
     
@@ -133,10 +486,13 @@ fn copy_executable(args: &Args, app_path: &Path) -> Result<()> {
     
     // Set executable permissions
     Command::new("chmod")
-        .args(&["+x", &target_path.to_string_lossy()])
+        .args(["+x", &target_path.to_string_lossy()])
         .output()
         .context("Failed to set executable permissions")?;
     
+    // Scripts are started by a launcher setting up their interpreter and search paths
+    script::package_script(args, app_path, &target_path)?;
+    
     // If single instance mode is enabled, create a wrapper script
     if args.single_instance {
         // Rename the original executable
@@ -180,44 +536,67 @@ trap 'rm -f "$LOCK_FILE" "$PID_FILE"' EXIT
             .context("Failed to write single instance wrapper script")?;
         
         Command::new("chmod")
-            .args(&["+x", &target_path.to_string_lossy()])
+            .args(["+x", &target_path.to_string_lossy()])
             .output()
             .context("Failed to set permissions on wrapper script")?;
     }
     
+    // If run in terminal is enabled, create a launcher opening the executable in Terminal
+    if args.run_in_terminal {
+        let original_exec_path = macos_dir.join(format!("{}_original", executable_name.to_string_lossy()));
+        fs::rename(&target_path, &original_exec_path)
+            .context("Failed to rename original executable")?;
+        
+        let launcher_script = format!(r#"#!/bin/bash
+
+# Open the command line program in a new Terminal window
+SCRIPT_DIR="$( cd "$( dirname "${{BASH_SOURCE[0]}}" )" && pwd )"
+exec open -a Terminal "$SCRIPT_DIR/{original_name}_original"
+"#, original_name = executable_name.to_string_lossy());
+        
+        fs::write(&target_path, launcher_script)
+            .context("Failed to write terminal launcher script")?;
+        
+        Command::new("chmod")
+            .args(["+x", &target_path.to_string_lossy()])
+            .output()
+            .context("Failed to set permissions on terminal launcher script")?;
+    }
+    
     Ok(())
 }
 
 /// Creates the Info.plist file for the app bundle
 fn create_info_plist(args: &Args, app_path: &Path) -> Result<()> {
-    let executable_name = Path::new(&args.executable)
-        .file_name()
-        .unwrap()
-        .to_string_lossy();
+    let executable_name = executable_name(args)?.to_string_lossy();
     
     // Create initial plist entries
     let mut plist_entries = vec![
         ("CFBundleName".to_string(), Value::String(args.name.clone())),
         ("CFBundleDisplayName".to_string(), Value::String(args.name.clone())),
         ("CFBundleIdentifier".to_string(), Value::String(args.bundle_id.clone())),
-        ("CFBundleVersion".to_string(), Value::String(args.app_version.clone())),
+        ("CFBundleVersion".to_string(), Value::String(args.build_number.clone().unwrap_or_else(|| args.app_version.clone()))),
         ("CFBundleShortVersionString".to_string(), Value::String(args.app_version.clone())),
         ("CFBundleExecutable".to_string(), Value::String(executable_name.to_string())),
-        ("CFBundleIconFile".to_string(), Value::String(if args.icon.is_some() {
-            Path::new(args.icon.as_ref().unwrap())
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .to_string()
-        } else {
-            "".to_string()
+        ("CFBundleIconFile".to_string(), Value::String(match &args.icon {
+            Some(icon) => icon::bundle_file_name(icon, &args.icon_name)?,
+            None => "".to_string(),
         })),
-        ("CFBundlePackageType".to_string(), Value::String("APPL".to_string())),
+        ("CFBundlePackageType".to_string(), Value::String(PACKAGE_TYPE.to_string())),
+        ("CFBundleSignature".to_string(), Value::String(BUNDLE_SIGNATURE.to_string())),
         ("LSMinimumSystemVersion".to_string(), Value::String("10.10.0".to_string())),
         ("LSUIElement".to_string(), Value::Boolean(!args.show_terminal)), // Controls terminal window visibility
         ("NSHighResolutionCapable".to_string(), Value::Boolean(true)),
     ];
     
+    // Newer macOS versions take the icon from the asset catalog
+    if args.icon.as_deref().is_some_and(|icon| assets::is_asset_catalog(Path::new(icon))) {
+        plist_entries.push(("CFBundleIconName".to_string(), Value::String(args.icon_name.clone())));
+    }
+    
+    // Usage descriptions of protected resources
+    plist_entries.extend(privacy::usage_descriptions(args));
+    
     // Add single instance configuration if enabled
     if args.single_instance {
         plist_entries.push(("JVFApplicationLaunchOnlyIfForeground".to_string(), Value::Boolean(false)));
@@ -225,109 +604,237 @@ fn create_info_plist(args: &Args, app_path: &Path) -> Result<()> {
         plist_entries.push(("JVFApplicationSingleInstanceModeEnabled".to_string(), Value::Boolean(true)));
     }
     
-    let plist_data = plist::Dictionary::from_iter(plist_entries);
+    if let Some(category) = &args.category {
+        plist_entries.push(("LSApplicationCategoryType".to_string(), Value::String(category.clone())));
+    }
+    
+    if args.background_only {
+        plist_entries.push(("LSBackgroundOnly".to_string(), Value::Boolean(true)));
+    }
+    
+    // Register the document types the app can view
+    if !args.document_types.is_empty() {
+        let document_types = args.document_types.iter().map(|extension| {
+            let extension = extension.trim_start_matches('.');
+            Value::Dictionary(plist::Dictionary::from_iter([
+                ("CFBundleTypeName".to_string(), Value::String(format!("{} document", extension.to_uppercase()))),
+                ("CFBundleTypeExtensions".to_string(), Value::Array(vec![Value::String(extension.to_string())])),
+                ("CFBundleTypeRole".to_string(), Value::String("Viewer".to_string())),
+                ("LSHandlerRank".to_string(), Value::String("Alternate".to_string())),
+            ]))
+        });
+        plist_entries.push(("CFBundleDocumentTypes".to_string(), Value::Array(document_types.collect())));
+    }
+    
+    let mut plist_data = plist::Dictionary::from_iter(plist_entries);
+    
+    // Merge the extra keys, which take precedence over the generated ones
+    if let Some(merge_plist) = &args.merge_plist {
+        let merge = Value::from_file(merge_plist)
+            .context(format!("Failed to read merge plist: {}", merge_plist))?
+            .into_dictionary()
+            .context(format!("{} is not a dictionary", merge_plist))?;
+        for (key, value) in merge {
+            plist_data.insert(key, value);
+        }
+    }
     
     let plist_path = app_path.join("Contents").join("Info.plist");
     
     let file = std::fs::File::create(plist_path)
         .context("Failed to create Info.plist file")?;
     
-    plist::to_writer_xml(file, &Value::Dictionary(plist_data))
-        .context("Failed to write Info.plist content")?;
+    match args.plist_format {
+        PlistFormat::Xml => plist::to_writer_xml(file, &Value::Dictionary(plist_data)),
+        PlistFormat::Binary => plist::to_writer_binary(file, &Value::Dictionary(plist_data)),
+    }
+    .context("Failed to write Info.plist content")?;
+    
+    // PkgInfo repeats the package type and signature for older Finder versions
+    fs::write(
+        app_path.join("Contents").join("PkgInfo"),
+        format!("{}{}", PACKAGE_TYPE, BUNDLE_SIGNATURE),
+    )
+    .context("Failed to write PkgInfo")?;
     
     Ok(())
 }
 
-/// Copies the icon file to the app bundle
-fn copy_icon(icon_path: &str, app_path: &Path) -> Result<()> {
+/// Reads the Info.plist of an existing app bundle
+fn read_info_plist(app_path: &Path) -> Result<plist::Dictionary> {
+    let plist_path = app_path.join("Contents").join("Info.plist");
+    let value = Value::from_file(&plist_path)
+        .context(format!("Failed to read {}", plist_path.display()))?;
+    value
+        .into_dictionary()
+        .context(format!("{} is not a dictionary", plist_path.display()))
+}
+
+/// Copies the icon file to the app bundle, converting it to .icns if needed and compiling asset
+/// catalogs to Assets.car
+fn copy_icon(icon_path: &str, icon_name: &str, app_path: &Path) -> Result<()> {
     let source_path = Path::new(icon_path);
     
-    // Check if the icon exists and is a .icns file
+    // Check if the icon exists
     if !source_path.exists() {
         anyhow::bail!("Icon file not found: {}", icon_path);
     }
     
-    if source_path.extension().unwrap_or_default() != "icns" {
-        println!("Warning: Icon should be in .icns format for best results");
+    let resources = app_path.join("Contents").join("Resources");
+    let target_path = resources.join(icon::bundle_file_name(icon_path, icon_name)?);
+    
+    if assets::is_asset_catalog(source_path) {
+        let set = assets::read_icon_set(source_path, icon_name)?;
+        let car_path = resources.join(assets::CAR_NAME);
+        fs::write(&car_path, assets::compile(&set)?)
+            .context(format!("Failed to write asset catalog: {}", car_path.display()))?;
+        fs::write(&target_path, icon::png_files_to_icns(source_path, &set.files())?)
+            .context(format!("Failed to write icon: {}", target_path.display()))?;
+    } else if icon::is_icns(source_path) {
+        // Copy the icon
+        fs::copy(source_path, target_path)
+            .context("Failed to copy icon to app bundle")?;
+    } else {
+        let icns = icon::to_icns(source_path)?;
+        fs::write(&target_path, icns)
+            .context(format!("Failed to write icon: {}", target_path.display()))?;
     }
     
-    let target_path = app_path
-        .join("Contents")
-        .join("Resources")
-        .join(source_path.file_name().unwrap());
-    
-    // Copy the icon
-    fs::copy(source_path, target_path)
-        .context("Failed to copy icon to app bundle")?;
-    
     Ok(())
 }
 
+/// Recursively copies a directory, recreating symlinks instead of following them
+fn copy_tree(source: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(target)
+        .context(format!("Failed to create directory: {}", target.display()))?;
+    for entry in fs::read_dir(source).context(format!("Failed to read directory: {}", source.display()))? {
+        let entry = entry?;
+        let source_path = entry.path();
+        let target_path = target.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let link = fs::read_link(&source_path)?;
+            if fs::symlink_metadata(&target_path).is_ok() {
+                fs::remove_file(&target_path)?;
+            }
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&link, &target_path)
+                .context(format!("Failed to create symlink: {}", target_path.display()))?;
+            #[cfg(not(unix))]
+            fs::copy(&source_path, &target_path)
+                .context(format!("Failed to copy {}", source_path.display()))?;
+        } else if file_type.is_dir() {
+            copy_tree(&source_path, &target_path)?;
+        } else {
+            // fs::copy keeps the permission bits
+            fs::copy(&source_path, &target_path)
+                .context(format!("Failed to copy {} to {}", source_path.display(), target_path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Single-quotes a value for a generated shell script, so quotes, `$`, backticks and newlines stay literal
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
 /// Copy additional files or directories to the app bundle
 fn copy_additional_files(args: &Args, app_path: &Path) -> Result<()> {
     for file_entry in &args.additional_files {
-        // Split the entry by colon to get source and target paths
-        let parts: Vec<&str> = file_entry.split(':').collect();
-        let source_path = parts[0];
-        
-        // Determine the target path
-        let target_path = if parts.len() > 1 && !parts[1].is_empty() {
-            // User provided specific target path
-            app_path.join("Contents").join(parts[1])
-        } else {
-            // Use default location
-            match args.default_location {
-                DefaultLocation::Resources => app_path.join("Contents").join("Resources"),
-                DefaultLocation::MacOS => app_path.join("Contents").join("MacOS"),
-                DefaultLocation::Contents => app_path.join("Contents"),
-            }.join(Path::new(source_path).file_name().unwrap())
-        };
-        
-        let source = Path::new(source_path);
-        
-        if !source.exists() {
-            anyhow::bail!("Source file or directory not found: {}", source_path);
-        }
-        
-        // Create parent directory if it doesn't exist
-        if let Some(parent) = target_path.parent() {
-            fs::create_dir_all(parent)
-                .context(format!("Failed to create directory: {}", parent.display()))?;
+        copy_additional_file(args, file_entry, app_path)?;
+    }
+    
+    Ok(())
+}
+
+/// Splits an additional file entry into its source path and the path it is copied to inside the
+/// app bundle
+fn additional_file_target<'a>(args: &Args, file_entry: &'a str, app_path: &Path) -> Result<(&'a str, PathBuf)> {
+    // Split the entry by colon to get source and target paths
+    let parts: Vec<&str> = file_entry.split(':').collect();
+    let source_path = parts[0];
+    let source_name = Path::new(source_path)
+        .file_name()
+        .context(format!("Invalid additional file path: {}", source_path))?;
+    
+    // Determine the target path
+    let target_path = if parts.len() > 1 {
+        // Targets are relative to Contents and must name a path inside it, watch mode removes
+        // the target before copying it again
+        let target = Path::new(parts[1]);
+        if target.is_absolute()
+            || target.components().any(|c| matches!(c, std::path::Component::ParentDir))
+            || !target.components().any(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            anyhow::bail!("Invalid additional file target: {} (expected a path inside Contents)", file_entry);
         }
+        app_path.join("Contents").join(target)
+    } else {
+        // Use default location
+        match args.default_location {
+            DefaultLocation::Resources => app_path.join("Contents").join("Resources"),
+            DefaultLocation::MacOS => app_path.join("Contents").join("MacOS"),
+            DefaultLocation::Contents => app_path.join("Contents"),
+        }.join(source_name)
+    };
+    
+    // Directories are copied into the parent of the target under their own name
+    if Path::new(source_path).is_dir() {
+        let parent = target_path.parent().context(format!("Invalid additional file target: {}", file_entry))?;
+        return Ok((source_path, parent.join(source_name)));
+    }
+    Ok((source_path, target_path))
+}
+
+/// Copies one additional file or directory into the app bundle
+fn copy_additional_file(args: &Args, file_entry: &str, app_path: &Path) -> Result<()> {
+    let (source_path, target_path) = additional_file_target(args, file_entry, app_path)?;
+    
+    let source = Path::new(source_path);
+    
+    if !source.exists() {
+        anyhow::bail!("Source file or directory not found: {}", source_path);
+    }
+    
+    // Create parent directory if it doesn't exist
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {}", parent.display()))?;
+    }
+    
+    // Copy file or directory
+    if source.is_file() {
+        // Copy file
+        fs::copy(source, &target_path)
+            .context(format!("Failed to copy file {} to {}", source_path, target_path.display()))?;
         
-        // Copy file or directory
-        if source.is_file() {
-            // Copy file
-            fs::copy(source, &target_path)
-                .context(format!("Failed to copy file {} to {}", source_path, target_path.display()))?;
+        // Check if source is executable, and if so, set permissions on target
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(source)
+                .context(format!("Failed to get metadata for {}", source_path))?;
+            let permissions = metadata.permissions();
             
-            // Check if source is executable, and if so, set permissions on target
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let metadata = fs::metadata(source)
-                    .context(format!("Failed to get metadata for {}", source_path))?;
-                let permissions = metadata.permissions();
-                
-                if permissions.mode() & 0o111 != 0 {
-                    Command::new("chmod")
-                        .args(&["+x", &target_path.to_string_lossy()])
-                        .output()
-                        .context(format!("Failed to set executable permissions on {}", target_path.display()))?;
-                }
+            if permissions.mode() & 0o111 != 0 {
+                Command::new("chmod")
+                    .args(["+x", &target_path.to_string_lossy()])
+                    .output()
+                    .context(format!("Failed to set executable permissions on {}", target_path.display()))?;
             }
-        } else {
-            // Copy directory recursively
-            let options = CopyOptions::new()
-                .overwrite(true)
-                .copy_inside(true);
-            
-            dir::copy(source, target_path.parent().unwrap(), &options)
-                .context(format!("Failed to copy directory {} to {}", source_path, target_path.parent().unwrap().display()))?;
         }
+    } else {
+        // Copy directory recursively
+        let options = CopyOptions::new()
+            .overwrite(true)
+            .copy_inside(true);
         
-        println!("Added {} to {}", source_path, target_path.display());
+        dir::copy(source, target_path.parent().unwrap(), &options)
+            .context(format!("Failed to copy directory {} to {}", source_path, target_path.parent().unwrap().display()))?;
     }
     
+    println!("Added {} to {}", source_path, target_path.display());
+    
     Ok(())
 }
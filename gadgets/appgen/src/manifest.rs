@@ -0,0 +1,140 @@
+//! TOML build manifests.
+//!
+//! A manifest is a saved command line: its keys are the long option names of the build
+//! (`name = "Demo"`, `additional-file = ["data.txt:Resources/data.txt"]`, `show-terminal = true`)
+//! and are expanded into arguments placed in front of the real ones, so flags given on the
+//! command line override the manifest. Relative paths are resolved against the manifest's
+//! directory. `[[login-item]]` and `[[helper-app]]` tables describe nested bundles, see
+//! [`crate::nested`].
+
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::nested::{NestedBundle, NestedKind};
+
+/// Options whose values are paths relative to the manifest
+const PATH_KEYS: &[&str] = &[
+    "executable",
+    "icon",
+    "output",
+    "entitlements",
+    "merge-plist",
+    "helper",
+    "framework",
+    "login-item",
+    "helper-app",
+    "python-venv",
+    "node-modules",
+    "runtime",
+];
+
+/// A manifest expanded into command line arguments
+pub struct Manifest {
+    pub args: Vec<OsString>,
+    pub nested: Vec<NestedBundle>,
+}
+
+/// Reads a manifest, checking its keys against the options of `command`
+pub fn load(path: &Path, command: &clap::Command) -> Result<Manifest> {
+    let content = fs::read_to_string(path).context(format!("Failed to read manifest: {}", path.display()))?;
+    let table: toml::Table = content
+        .parse()
+        .context(format!("Failed to parse manifest: {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    from_table(&table, base, command).context(format!("Invalid manifest: {}", path.display()))
+}
+
+/// Expands the keys of a manifest table into arguments
+pub fn from_table(table: &toml::Table, base: &Path, command: &clap::Command) -> Result<Manifest> {
+    let mut manifest = Manifest { args: Vec::new(), nested: Vec::new() };
+    for (key, value) in table {
+        if key == "manifest" {
+            anyhow::bail!("Manifests cannot include other manifests");
+        }
+
+        // Nested bundles are either inline tables or paths to their own manifests
+        if let Some(kind) = NestedKind::from_key(key) {
+            let items = value.as_array().map(|a| a.as_slice()).unwrap_or(std::slice::from_ref(value));
+            if items.iter().all(toml::Value::is_table) {
+                for item in items {
+                    let nested = NestedBundle::from_table(kind, item.as_table().unwrap(), base)
+                        .context(format!("Invalid [[{}]] entry", key))?;
+                    manifest.nested.push(nested);
+                }
+                continue;
+            }
+        }
+
+        let Some(arg) = command.get_arguments().find(|a| a.get_long() == Some(key.as_str())) else {
+            anyhow::bail!("Unknown key `{}`", key);
+        };
+        // Switches a template may turn on also accept `--switch=false`
+        let explicit_switch = arg.get_num_args().is_some_and(|range| range.takes_values());
+        let values = match value {
+            toml::Value::Array(items) => items.iter().collect(),
+            _ => vec![value],
+        };
+        for value in values {
+            let text = match value {
+                toml::Value::Boolean(enabled) if explicit_switch => {
+                    manifest.args.push(format!("--{}={}", key, enabled).into());
+                    continue;
+                }
+                toml::Value::Boolean(enabled) => {
+                    if *enabled {
+                        manifest.args.push(format!("--{}", key).into());
+                    }
+                    continue;
+                }
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                other => anyhow::bail!("Unsupported value for `{}`: {}", key, other),
+            };
+            manifest.args.push(format!("--{}", key).into());
+            manifest.args.push(resolve_value(key, &text, base).into());
+        }
+    }
+    Ok(manifest)
+}
+
+/// Resolves the path parts of an option value against the manifest directory
+fn resolve_value(key: &str, value: &str, base: &Path) -> String {
+    let resolve = |path: &str| -> String {
+        let expanded = match path.strip_prefix("~/") {
+            Some(rest) => format!("{}/{}", std::env::var("HOME").unwrap_or_default(), rest),
+            None => path.to_string(),
+        };
+        if Path::new(&expanded).is_absolute() {
+            expanded
+        } else {
+            base.join(expanded).to_string_lossy().to_string()
+        }
+    };
+    if PATH_KEYS.contains(&key) {
+        resolve(value)
+    } else if key == "additional-file" {
+        match value.split_once(':') {
+            Some((source, target)) => format!("{}:{}", resolve(source), target),
+            None => resolve(value),
+        }
+    } else {
+        value.to_string()
+    }
+}
+
+/// Parses raw arguments without enforcing the options a manifest or template may provide
+pub fn loose_matches(command: clap::Command, args: &[OsString]) -> Result<clap::ArgMatches, clap::Error> {
+    command
+        .mut_arg("executable", |a| a.required(false))
+        .mut_arg("name", |a| a.required(false))
+        .try_get_matches_from(args)
+}
+
+/// Path of `--manifest` in raw arguments
+pub fn find_manifest_arg(command: clap::Command, args: &[OsString]) -> Option<PathBuf> {
+    let matches = loose_matches(command, args).unwrap_or_else(|e| e.exit());
+    matches.get_one::<String>("manifest").map(PathBuf::from)
+}
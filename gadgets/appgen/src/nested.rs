@@ -0,0 +1,173 @@
+//! Nested bundles (login items, helper apps) and helper executables embedded in a bundle.
+//!
+//! Nested bundles are built with the same pipeline as the parent, into
+//! `Contents/Library/LoginItems` or `Contents/Helpers`. Their bundle ids must be children of
+//! the parent's id and default to `{parent id}.{name}`. Without an explicit `app-version`
+//! they share the parent's version and build number.
+
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::Args;
+use crate::manifest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestedKind {
+    LoginItem,
+    HelperApp,
+}
+
+impl NestedKind {
+    /// Manifest key and option name declaring nested bundles of this kind
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "login-item" => Some(NestedKind::LoginItem),
+            "helper-app" => Some(NestedKind::HelperApp),
+            _ => None,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            NestedKind::LoginItem => "login-item",
+            NestedKind::HelperApp => "helper-app",
+        }
+    }
+
+    /// Directory the bundle is placed in, relative to Contents
+    fn location(self) -> &'static str {
+        match self {
+            NestedKind::LoginItem => "Library/LoginItems",
+            NestedKind::HelperApp => "Helpers",
+        }
+    }
+}
+
+/// A nested bundle declared by a manifest
+#[derive(Debug, Clone)]
+pub struct NestedBundle {
+    kind: NestedKind,
+    args: Args,
+    /// Whether the bundle id was given, otherwise it is derived from the parent's
+    explicit_bundle_id: bool,
+    /// Whether the version was given, otherwise the parent's is used
+    explicit_app_version: bool,
+}
+
+impl NestedBundle {
+    /// Reads an inline `[[login-item]]` or `[[helper-app]]` table
+    pub fn from_table(kind: NestedKind, table: &toml::Table, base: &Path) -> Result<Self> {
+        let command = nested_command(kind);
+        let manifest = manifest::from_table(table, base, &command)?;
+        Self::parse(kind, manifest, command)
+    }
+
+    /// Reads a nested bundle from its own manifest file
+    pub fn from_manifest(kind: NestedKind, path: &Path) -> Result<Self> {
+        let command = nested_command(kind);
+        let manifest = manifest::load(path, &command)?;
+        Self::parse(kind, manifest, command).context(format!("Invalid {} manifest: {}", kind.key(), path.display()))
+    }
+
+    fn parse(kind: NestedKind, mut manifest: manifest::Manifest, command: clap::Command) -> Result<Self> {
+        crate::template::expand(command.clone(), &mut manifest.args, 0)?;
+        let matches = command.try_get_matches_from(&manifest.args)?;
+        let mut args = <Args as clap::FromArgMatches>::from_arg_matches(&matches)?;
+        if matches.value_source("output") == Some(ValueSource::CommandLine) {
+            anyhow::bail!("Nested bundles are placed by their parent, remove `output`");
+        }
+        args.nested.extend(manifest.nested);
+        let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        Ok(NestedBundle {
+            kind,
+            args,
+            explicit_bundle_id: explicit("bundle_id"),
+            explicit_app_version: explicit("app_version"),
+        })
+    }
+}
+
+fn nested_command(kind: NestedKind) -> clap::Command {
+    <Args as clap::Args>::augment_args(clap::Command::new(kind.key()).no_binary_name(true))
+}
+
+/// Collects the nested bundles of a build, resolving their bundle ids against the parent's
+pub fn collect(args: &Args) -> Result<Vec<NestedBundle>> {
+    let mut bundles = args.nested.clone();
+    for path in &args.login_items {
+        bundles.push(NestedBundle::from_manifest(NestedKind::LoginItem, Path::new(path))?);
+    }
+    for path in &args.helper_apps {
+        bundles.push(NestedBundle::from_manifest(NestedKind::HelperApp, Path::new(path))?);
+    }
+
+    let mut locations = HashSet::new();
+    for bundle in &mut bundles {
+        if !bundle.explicit_bundle_id {
+            bundle.args.bundle_id = format!("{}.{}", args.bundle_id, crate::cask::cask_token(&bundle.args.name));
+        }
+        if !bundle.explicit_app_version {
+            bundle.args.app_version = args.app_version.clone();
+            bundle.args.build_number = args.build_number.clone();
+        }
+        bundle.args.strict |= args.strict;
+        // Checked here as well so an invalid id fails before the parent is written
+        crate::lint::check_bundle_id_format(&bundle.args.bundle_id)
+            .context(format!("Invalid nested bundle {}", bundle.args.name))?;
+        let is_child = bundle
+            .args
+            .bundle_id
+            .strip_prefix(&args.bundle_id)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .is_some_and(|rest| !rest.is_empty());
+        if !is_child {
+            anyhow::bail!(
+                "Nested bundle id {} of {} must be a child of {} (for example {}.helper)",
+                bundle.args.bundle_id,
+                bundle.args.name,
+                args.bundle_id,
+                args.bundle_id
+            );
+        }
+        if !locations.insert((bundle.kind.location(), bundle.args.name.clone())) {
+            anyhow::bail!("Duplicate nested bundle {}.app in {}", bundle.args.name, bundle.kind.location());
+        }
+    }
+    Ok(bundles)
+}
+
+/// Copies the helper executables and builds the nested bundles into the bundle
+pub fn embed(args: &Args, bundles: &[NestedBundle], app_path: &Path) -> Result<()> {
+    let contents = app_path.join("Contents");
+
+    for helper in &args.helpers {
+        let source = Path::new(helper);
+        if !source.is_file() {
+            anyhow::bail!("Helper executable not found: {}", helper);
+        }
+        let helpers_dir = contents.join("Helpers");
+        fs::create_dir_all(&helpers_dir).context("Failed to create Helpers directory")?;
+        let target = helpers_dir.join(source.file_name().unwrap());
+        fs::copy(source, &target).context(format!("Failed to copy helper {}", helper))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target, fs::Permissions::from_mode(0o755))
+                .context(format!("Failed to set permissions on {}", target.display()))?;
+        }
+        println!("Added helper {} to {}", helper, target.display());
+    }
+
+    for bundle in bundles {
+        let location = contents.join(bundle.kind.location());
+        fs::create_dir_all(&location).context(format!("Failed to create {}", location.display()))?;
+        let mut nested_args = bundle.args.clone();
+        nested_args.output = location.to_string_lossy().to_string();
+        crate::build_app(&nested_args, &Default::default())
+            .context(format!("Failed to build nested bundle {}", bundle.args.name))?;
+    }
+    Ok(())
+}
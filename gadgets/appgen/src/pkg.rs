@@ -0,0 +1,302 @@
+//! Flat installer package (`.pkg`) generation that works without `pkgbuild`.
+//!
+//! The product archive is a xar file (see [`xar`]) holding a `Distribution` script and
+//! one component package with `PackageInfo`, a `Bom` (see [`bom`]), the `Payload` as a
+//! gzipped odc cpio archive (see [`cpio`]) and optionally the install `Scripts`.
+
+mod bom;
+mod cpio;
+mod xar;
+
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Options of the `pkg` subcommand
+#[derive(clap::Args, Debug)]
+pub struct PkgArgs {
+    /// Path to the .app bundle to package
+    #[arg(value_name = "APP")]
+    app: String,
+
+    /// Output path of the package (defaults to {app name}.pkg next to the bundle)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Package identifier (defaults to {bundle id}.pkg)
+    #[arg(long)]
+    identifier: Option<String>,
+
+    /// Package version (defaults to CFBundleShortVersionString of the bundle)
+    #[arg(long)]
+    package_version: Option<String>,
+
+    /// Directory the app is installed into
+    #[arg(long, default_value = "/Applications")]
+    install_location: String,
+
+    /// Optional LaunchAgent property list installed into /Library/LaunchAgents
+    #[arg(long, value_name = "PLIST")]
+    launch_agent: Option<String>,
+
+    /// Optional script run before the installation
+    #[arg(long, value_name = "SCRIPT")]
+    preinstall: Option<String>,
+
+    /// Optional script run after the installation
+    #[arg(long, value_name = "SCRIPT")]
+    postinstall: Option<String>,
+}
+
+/// What a payload entry is
+pub enum EntryKind {
+    Directory,
+    File(Vec<u8>),
+    Symlink(String),
+}
+
+/// A file system entry of the payload, `path` being relative to `/` (`./Applications/...`)
+pub struct PayloadEntry {
+    pub path: String,
+    pub kind: EntryKind,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u32,
+}
+
+impl PayloadEntry {
+    fn directory(path: &str, mode: u16, gid: u32) -> Self {
+        PayloadEntry {
+            path: path.to_string(),
+            kind: EntryKind::Directory,
+            mode,
+            uid: 0,
+            gid,
+            mtime: 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match &self.kind {
+            EntryKind::Directory => 0,
+            EntryKind::File(data) => data.len(),
+            EntryKind::Symlink(target) => target.len(),
+        }
+    }
+}
+
+/// Group id of `admin`, which owns `/Applications`
+const ADMIN_GID: u32 = 80;
+
+/// Creates the installer package described by `args`, returning its path
+pub fn create_pkg(args: &PkgArgs) -> Result<PathBuf> {
+    let app_path = Path::new(&args.app);
+    if !app_path.is_dir() {
+        anyhow::bail!("App bundle not found: {}", args.app);
+    }
+    let app_file_name = app_path
+        .file_name()
+        .context("Invalid app bundle path")?
+        .to_string_lossy()
+        .to_string();
+    let app_name = app_file_name.strip_suffix(".app").unwrap_or(&app_file_name).to_string();
+
+    let info = crate::read_info_plist(app_path)?;
+    let info_string = |key: &str| info.get(key).and_then(|v| v.as_string()).map(str::to_string);
+    let bundle_id = info_string("CFBundleIdentifier").context("Info.plist has no CFBundleIdentifier")?;
+    let short_version = info_string("CFBundleShortVersionString").unwrap_or_else(|| "1.0".to_string());
+    let bundle_version = info_string("CFBundleVersion").unwrap_or_else(|| short_version.clone());
+    let identifier = args.identifier.clone().unwrap_or_else(|| format!("{}.pkg", bundle_id));
+    let version = args.package_version.clone().unwrap_or_else(|| short_version.clone());
+
+    // Payload rooted at `/`, with every intermediate directory listed
+    let install_dir = args.install_location.trim_matches('/');
+    let mut entries = vec![PayloadEntry::directory(".", 0o755, 0)];
+    let mut current = String::from(".");
+    for component in install_dir.split('/').filter(|c| !c.is_empty()) {
+        current = format!("{}/{}", current, component);
+        let gid = if current == "./Applications" { ADMIN_GID } else { 0 };
+        entries.push(PayloadEntry::directory(&current, 0o775, gid));
+    }
+    let app_payload_path = format!("{}/{}", current, app_file_name);
+    collect_entries(app_path, &app_payload_path, &mut entries)?;
+
+    if let Some(agent) = &args.launch_agent {
+        let source = Path::new(agent);
+        if !source.is_file() {
+            anyhow::bail!("LaunchAgent property list not found: {}", agent);
+        }
+        entries.push(PayloadEntry::directory("./Library", 0o755, 0));
+        entries.push(PayloadEntry::directory("./Library/LaunchAgents", 0o755, 0));
+        entries.push(PayloadEntry {
+            path: format!("./Library/LaunchAgents/{}", source.file_name().unwrap().to_string_lossy()),
+            kind: EntryKind::File(fs::read(source).context("Failed to read LaunchAgent property list")?),
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+        });
+    }
+
+    let install_kbytes = entries.iter().map(|e| e.size().div_ceil(1024)).sum::<usize>();
+    let number_of_files = entries.len();
+
+    let mut scripts = Vec::new();
+    for (name, script) in [("preinstall", &args.preinstall), ("postinstall", &args.postinstall)] {
+        if let Some(script) = script {
+            let data = fs::read(script).context(format!("Failed to read {} script: {}", name, script))?;
+            scripts.push((name, data));
+        }
+    }
+
+    let mut script_refs = String::new();
+    for (name, _) in &scripts {
+        script_refs.push_str(&format!("        <{0} file=\"./{0}\"/>\n", name));
+    }
+    let package_info = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<pkg-info format-version="2" identifier="{identifier}" version="{version}" install-location="/" auth="root" postinstall-action="none">
+    <payload numberOfFiles="{number_of_files}" installKBytes="{install_kbytes}"/>
+    <bundle path="{bundle_path}" id="{bundle_id}" CFBundleShortVersionString="{short_version}" CFBundleVersion="{bundle_version}"/>
+    <bundle-version>
+        <bundle id="{bundle_id}"/>
+    </bundle-version>
+    <scripts>
+{script_refs}    </scripts>
+</pkg-info>
+"#,
+        identifier = escape_xml(&identifier),
+        version = escape_xml(&version),
+        bundle_path = escape_xml(&app_payload_path),
+        bundle_id = escape_xml(&bundle_id),
+        short_version = escape_xml(&short_version),
+        bundle_version = escape_xml(&bundle_version),
+    );
+
+    let component_name = format!("{}.pkg", app_name.replace(' ', "_"));
+    let distribution = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<installer-gui-script minSpecVersion="2">
+    <title>{title}</title>
+    <options customize="never" require-scripts="false" hostArchitectures="x86_64,arm64"/>
+    <domains enable_localSystem="true"/>
+    <choices-outline>
+        <line choice="default">
+            <line choice="{identifier}"/>
+        </line>
+    </choices-outline>
+    <choice id="default"/>
+    <choice id="{identifier}" visible="false">
+        <pkg-ref id="{identifier}"/>
+    </choice>
+    <pkg-ref id="{identifier}" version="{version}" onConclusion="none" installKBytes="{install_kbytes}">#{component}</pkg-ref>
+</installer-gui-script>
+"#,
+        title = escape_xml(&app_name),
+        identifier = escape_xml(&identifier),
+        version = escape_xml(&version),
+        component = escape_xml(&component_name),
+    );
+
+    let payload = gzip(&cpio::write_cpio(&entries))?;
+    let bom = bom::write_bom(&entries);
+
+    let mut component = vec![
+        xar::XarEntry::file("Bom", bom),
+        xar::XarEntry::file("PackageInfo", package_info.into_bytes()),
+        xar::XarEntry::file("Payload", payload),
+    ];
+    if !scripts.is_empty() {
+        let script_entries: Vec<PayloadEntry> = std::iter::once(PayloadEntry::directory(".", 0o755, 0))
+            .chain(scripts.into_iter().map(|(name, data)| PayloadEntry {
+                path: format!("./{}", name),
+                kind: EntryKind::File(data),
+                mode: 0o755,
+                uid: 0,
+                gid: 0,
+                mtime: 0,
+            }))
+            .collect();
+        component.push(xar::XarEntry::file("Scripts", gzip(&cpio::write_cpio(&script_entries))?));
+    }
+    let archive = xar::write_xar(vec![
+        xar::XarEntry::file("Distribution", distribution.into_bytes()),
+        xar::XarEntry::directory(&component_name, component),
+    ])?;
+
+    let output = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => app_path.with_file_name(format!("{}.pkg", app_name)),
+    };
+    fs::write(&output, archive).context(format!("Failed to write package: {}", output.display()))?;
+    Ok(output)
+}
+
+/// Recursively collects `source` into payload entries below `path`
+fn collect_entries(source: &Path, path: &str, entries: &mut Vec<PayloadEntry>) -> Result<()> {
+    let metadata = fs::symlink_metadata(source)
+        .context(format!("Failed to get metadata for {}", source.display()))?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default();
+    let mut entry = PayloadEntry {
+        path: path.to_string(),
+        kind: EntryKind::Directory,
+        mode: permissions_of(&metadata),
+        uid: 0,
+        gid: ADMIN_GID,
+        mtime,
+    };
+
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(source).context(format!("Failed to read symlink {}", source.display()))?;
+        entry.kind = EntryKind::Symlink(target.to_string_lossy().to_string());
+        entry.mode = 0o755;
+        entries.push(entry);
+    } else if metadata.is_file() {
+        entry.kind = EntryKind::File(fs::read(source).context(format!("Failed to read {}", source.display()))?);
+        entries.push(entry);
+    } else {
+        entries.push(entry);
+        let mut children = fs::read_dir(source)
+            .context(format!("Failed to read directory {}", source.display()))?
+            .collect::<Result<Vec<_>, _>>()?;
+        children.sort_by_key(|c| c.file_name());
+        for child in children {
+            let child_path = format!("{}/{}", path, child.file_name().to_string_lossy());
+            collect_entries(&child.path(), &child_path, entries)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn permissions_of(metadata: &fs::Metadata) -> u16 {
+    use std::os::unix::fs::PermissionsExt;
+    (metadata.permissions().mode() & 0o7777) as u16
+}
+
+#[cfg(not(unix))]
+fn permissions_of(metadata: &fs::Metadata) -> u16 {
+    if metadata.is_dir() { 0o755 } else { 0o644 }
+}
+
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+pub(crate) fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,230 @@
+//! Bill of materials (`BOMStore`) writer, the replacement for `mkbom`.
+//!
+//! The store is a 512 byte header followed by numbered blocks, the variable table and the
+//! block index. The `Paths` tree lists every payload entry with its mode, owner, size and
+//! `cksum` style CRC. `HLIndex`, `VIndex` and `Size64` are written as empty trees.
+
+use super::{EntryKind, PayloadEntry};
+
+const HEADER_SIZE: usize = 512;
+/// Maximum number of paths in a single leaf of the `Paths` tree
+const PATHS_PER_LEAF: usize = 256;
+
+const TYPE_FILE: u8 = 1;
+const TYPE_DIRECTORY: u8 = 2;
+const TYPE_LINK: u8 = 3;
+
+#[derive(Default)]
+struct BomStore {
+    blocks: Vec<Vec<u8>>,
+    vars: Vec<(&'static str, u32)>,
+}
+
+impl BomStore {
+    /// Adds a block, returning its index (index 0 is the reserved null block)
+    fn add(&mut self, data: Vec<u8>) -> u32 {
+        if self.blocks.is_empty() {
+            self.blocks.push(Vec::new());
+        }
+        self.blocks.push(data);
+        self.blocks.len() as u32 - 1
+    }
+
+    fn serialize(self) -> Vec<u8> {
+        let mut out = vec![0u8; HEADER_SIZE];
+        let mut pointers = Vec::with_capacity(self.blocks.len());
+        for (i, block) in self.blocks.iter().enumerate() {
+            if i == 0 {
+                pointers.push((0u32, 0u32));
+                continue;
+            }
+            pointers.push((out.len() as u32, block.len() as u32));
+            out.extend_from_slice(block);
+        }
+
+        let vars_offset = out.len();
+        put_u32(&mut out, self.vars.len() as u32);
+        for (name, index) in &self.vars {
+            put_u32(&mut out, *index);
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+        }
+        let vars_length = out.len() - vars_offset;
+
+        let index_offset = out.len();
+        put_u32(&mut out, pointers.len() as u32);
+        for (address, length) in &pointers {
+            put_u32(&mut out, *address);
+            put_u32(&mut out, *length);
+        }
+        put_u32(&mut out, 0); // free list
+        let index_length = out.len() - index_offset;
+
+        let mut header = Vec::with_capacity(32);
+        header.extend_from_slice(b"BOMStore");
+        put_u32(&mut header, 1);
+        put_u32(&mut header, pointers.len() as u32);
+        put_u32(&mut header, index_offset as u32);
+        put_u32(&mut header, index_length as u32);
+        put_u32(&mut header, vars_offset as u32);
+        put_u32(&mut header, vars_length as u32);
+        out[..header.len()].copy_from_slice(&header);
+        out
+    }
+}
+
+/// Serializes the bill of materials for the payload entries
+pub fn write_bom(entries: &[PayloadEntry]) -> Vec<u8> {
+    let mut store = BomStore::default();
+
+    let mut info = Vec::new();
+    put_u32(&mut info, 1); // version
+    put_u32(&mut info, entries.len() as u32);
+    put_u32(&mut info, 1); // info entries
+    info.extend_from_slice(&[0; 16]);
+    let info = store.add(info);
+
+    // Every path gets an id, its parent is looked up by path
+    let mut ids = std::collections::HashMap::new();
+    let mut indices = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let id = i as u32 + 1;
+        ids.insert(entry.path.as_str(), id);
+        let (parent, name) = match entry.path.rsplit_once('/') {
+            Some((parent, name)) => (ids.get(parent).copied().unwrap_or_default(), name),
+            None => (0, entry.path.as_str()),
+        };
+
+        let path_info2 = store.add(path_info(entry));
+        let mut path_info1 = Vec::new();
+        put_u32(&mut path_info1, id);
+        put_u32(&mut path_info1, path_info2);
+        let path_info1 = store.add(path_info1);
+
+        let mut file = Vec::new();
+        put_u32(&mut file, parent);
+        file.extend_from_slice(name.as_bytes());
+        file.push(0);
+        let file = store.add(file);
+        indices.push((path_info1, file));
+    }
+
+    let leaf_count = indices.len().div_ceil(PATHS_PER_LEAF).max(1);
+    // Leaves are linked to their neighbours, so their block numbers are reserved first
+    let first_leaf = store.blocks.len().max(1) as u32;
+    let mut leaves = Vec::with_capacity(leaf_count);
+    for leaf in 0..leaf_count {
+        let chunk = indices.chunks(PATHS_PER_LEAF).nth(leaf).unwrap_or_default();
+        let forward = if leaf + 1 < leaf_count { first_leaf + leaf as u32 + 1 } else { 0 };
+        let backward = if leaf > 0 { first_leaf + leaf as u32 - 1 } else { 0 };
+        let index = store.add(paths_node(true, forward, backward, chunk));
+        leaves.push((index, chunk.last().map(|(_, file)| *file).unwrap_or_default()));
+    }
+    let paths_root = if leaf_count == 1 {
+        leaves[0].0
+    } else {
+        store.add(paths_node(false, 0, 0, &leaves))
+    };
+    let paths = store.add(tree(paths_root, 4096, entries.len() as u32));
+
+    let empty_hl = store.add(paths_node(true, 0, 0, &[]));
+    let hl_index = store.add(tree(empty_hl, 4096, 0));
+
+    let empty_v = store.add(paths_node(true, 0, 0, &[]));
+    let v_tree = store.add(tree(empty_v, 128, 0));
+    let mut v_index = Vec::new();
+    put_u32(&mut v_index, 1);
+    put_u32(&mut v_index, v_tree);
+    put_u32(&mut v_index, 0);
+    v_index.push(0);
+    let v_index = store.add(v_index);
+
+    let empty_size = store.add(paths_node(true, 0, 0, &[]));
+    let size64 = store.add(tree(empty_size, 128, 0));
+
+    store.vars = vec![
+        ("BomInfo", info),
+        ("Paths", paths),
+        ("HLIndex", hl_index),
+        ("VIndex", v_index),
+        ("Size64", size64),
+    ];
+    store.serialize()
+}
+
+/// BOMPathInfo2 of an entry
+fn path_info(entry: &PayloadEntry) -> Vec<u8> {
+    let (kind, file_type, data, link) = match &entry.kind {
+        EntryKind::Directory => (TYPE_DIRECTORY, 0o040000u16, &[][..], None),
+        EntryKind::File(data) => (TYPE_FILE, 0o100000, &data[..], None),
+        EntryKind::Symlink(target) => (TYPE_LINK, 0o120000, target.as_bytes(), Some(target)),
+    };
+    let mut out = Vec::new();
+    out.push(kind);
+    out.push(1);
+    out.extend_from_slice(&0u16.to_be_bytes()); // architecture
+    out.extend_from_slice(&(file_type | entry.mode).to_be_bytes());
+    put_u32(&mut out, entry.uid);
+    put_u32(&mut out, entry.gid);
+    put_u32(&mut out, entry.mtime);
+    put_u32(&mut out, data.len() as u32);
+    out.push(1);
+    put_u32(&mut out, if kind == TYPE_DIRECTORY { 0 } else { cksum(data) });
+    match link {
+        Some(target) => {
+            put_u32(&mut out, target.len() as u32 + 1);
+            out.extend_from_slice(target.as_bytes());
+            out.push(0);
+        }
+        None => put_u32(&mut out, 0),
+    }
+    out
+}
+
+/// BOMPaths node, `indices` being `(value, key)` block pairs
+fn paths_node(is_leaf: bool, forward: u32, backward: u32, indices: &[(u32, u32)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(is_leaf as u16).to_be_bytes());
+    out.extend_from_slice(&(indices.len() as u16).to_be_bytes());
+    put_u32(&mut out, forward);
+    put_u32(&mut out, backward);
+    for (value, key) in indices {
+        put_u32(&mut out, *value);
+        put_u32(&mut out, *key);
+    }
+    out
+}
+
+/// BOMTree header pointing at the root paths node
+fn tree(child: u32, block_size: u32, path_count: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(b"tree");
+    put_u32(&mut out, 1);
+    put_u32(&mut out, child);
+    put_u32(&mut out, block_size);
+    put_u32(&mut out, path_count);
+    out.push(0);
+    out
+}
+
+/// The POSIX `cksum` CRC, which is what BOM files store
+fn cksum(data: &[u8]) -> u32 {
+    fn update(crc: u32, byte: u8) -> u32 {
+        let mut c = crc ^ ((byte as u32) << 24);
+        for _ in 0..8 {
+            c = if c & 0x8000_0000 != 0 { (c << 1) ^ 0x04C1_1DB7 } else { c << 1 };
+        }
+        c
+    }
+    let mut crc = data.iter().fold(0, |crc, b| update(crc, *b));
+    let mut len = data.len();
+    while len > 0 {
+        crc = update(crc, len as u8);
+        len >>= 8;
+    }
+    !crc
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
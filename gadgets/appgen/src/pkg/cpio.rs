@@ -0,0 +1,64 @@
+//! Portable ASCII (odc, `070707`) cpio writer, the format `Payload` and `Scripts` use.
+
+use super::{EntryKind, PayloadEntry};
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Serializes the entries into an uncompressed cpio archive
+pub fn write_cpio(entries: &[PayloadEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let (file_type, data) = match &entry.kind {
+            EntryKind::Directory => (S_IFDIR, &[][..]),
+            EntryKind::File(data) => (S_IFREG, &data[..]),
+            EntryKind::Symlink(target) => (S_IFLNK, target.as_bytes()),
+        };
+        let nlink = if matches!(entry.kind, EntryKind::Directory) { 2 } else { 1 };
+        write_header(
+            &mut out,
+            i as u32 + 1,
+            file_type | entry.mode as u32,
+            entry.uid,
+            entry.gid,
+            nlink,
+            entry.mtime,
+            &entry.path,
+            data.len(),
+        );
+        out.extend_from_slice(data);
+    }
+    write_header(&mut out, 0, 0, 0, 0, 1, 0, "TRAILER!!!", 0);
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_header(
+    out: &mut Vec<u8>,
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    name: &str,
+    size: usize,
+) {
+    let header = format!(
+        "070707{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:011o}{:06o}{:011o}",
+        0,
+        ino & 0o777777,
+        mode,
+        uid,
+        gid,
+        nlink,
+        0,
+        mtime,
+        name.len() + 1,
+        size
+    );
+    out.extend_from_slice(header.as_bytes());
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+}
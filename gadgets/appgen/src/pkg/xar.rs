@@ -0,0 +1,111 @@
+//! xar archive writer.
+//!
+//! Layout: a 28 byte header, the zlib compressed XML table of contents, then the heap.
+//! The heap starts with the SHA-1 of the compressed TOC, followed by the file data which
+//! is stored as is (`application/octet-stream`), as the installer expects.
+
+use anyhow::Result;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use sha1::{Digest, Sha1};
+use std::io::Write;
+
+use super::escape_xml;
+
+const HEADER_SIZE: u16 = 28;
+const CHECKSUM_SHA1: u32 = 1;
+const SHA1_SIZE: usize = 20;
+
+/// A file or directory of the archive
+pub struct XarEntry {
+    name: String,
+    data: Option<Vec<u8>>,
+    children: Vec<XarEntry>,
+}
+
+impl XarEntry {
+    pub fn file(name: &str, data: Vec<u8>) -> Self {
+        XarEntry { name: name.to_string(), data: Some(data), children: Vec::new() }
+    }
+
+    pub fn directory(name: &str, children: Vec<XarEntry>) -> Self {
+        XarEntry { name: name.to_string(), data: None, children }
+    }
+}
+
+/// Serializes the entries into a xar archive
+pub fn write_xar(entries: Vec<XarEntry>) -> Result<Vec<u8>> {
+    let mut heap = vec![0u8; SHA1_SIZE];
+    let mut toc = String::new();
+    toc.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xar>\n <toc>\n");
+    toc.push_str(&format!(
+        "  <checksum style=\"sha1\">\n   <offset>0</offset>\n   <size>{}</size>\n  </checksum>\n",
+        SHA1_SIZE
+    ));
+    toc.push_str(&format!(
+        "  <creation-time>{}</creation-time>\n",
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S")
+    ));
+    let mut next_id = 1;
+    for entry in &entries {
+        write_entry(entry, 2, &mut next_id, &mut toc, &mut heap);
+    }
+    toc.push_str(" </toc>\n</xar>\n");
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(toc.as_bytes())?;
+    let compressed_toc = encoder.finish()?;
+    heap[..SHA1_SIZE].copy_from_slice(&Sha1::digest(&compressed_toc));
+
+    let mut out = Vec::with_capacity(HEADER_SIZE as usize + compressed_toc.len() + heap.len());
+    out.extend_from_slice(b"xar!");
+    out.extend_from_slice(&HEADER_SIZE.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&(compressed_toc.len() as u64).to_be_bytes());
+    out.extend_from_slice(&(toc.len() as u64).to_be_bytes());
+    out.extend_from_slice(&CHECKSUM_SHA1.to_be_bytes());
+    out.extend_from_slice(&compressed_toc);
+    out.extend_from_slice(&heap);
+    Ok(out)
+}
+
+fn write_entry(entry: &XarEntry, depth: usize, next_id: &mut u32, toc: &mut String, heap: &mut Vec<u8>) {
+    let indent = " ".repeat(depth);
+    toc.push_str(&format!("{}<file id=\"{}\">\n", indent, next_id));
+    *next_id += 1;
+    toc.push_str(&format!("{} <name>{}</name>\n", indent, escape_xml(&entry.name)));
+    match &entry.data {
+        Some(data) => {
+            let checksum = hex(&Sha1::digest(data));
+            toc.push_str(&format!("{} <type>file</type>\n", indent));
+            toc.push_str(&format!("{} <mode>0644</mode>\n", indent));
+            toc.push_str(&format!("{} <data>\n", indent));
+            toc.push_str(&format!("{}  <length>{}</length>\n", indent, data.len()));
+            toc.push_str(&format!("{}  <offset>{}</offset>\n", indent, heap.len()));
+            toc.push_str(&format!("{}  <size>{}</size>\n", indent, data.len()));
+            toc.push_str(&format!("{}  <encoding style=\"application/octet-stream\"/>\n", indent));
+            toc.push_str(&format!(
+                "{}  <extracted-checksum style=\"sha1\">{}</extracted-checksum>\n",
+                indent, checksum
+            ));
+            toc.push_str(&format!(
+                "{}  <archived-checksum style=\"sha1\">{}</archived-checksum>\n",
+                indent, checksum
+            ));
+            toc.push_str(&format!("{} </data>\n", indent));
+            heap.extend_from_slice(data);
+        }
+        None => {
+            toc.push_str(&format!("{} <type>directory</type>\n", indent));
+            toc.push_str(&format!("{} <mode>0755</mode>\n", indent));
+            for child in &entry.children {
+                write_entry(child, depth + 1, next_id, toc, heap);
+            }
+        }
+    }
+    toc.push_str(&format!("{}</file>\n", indent));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
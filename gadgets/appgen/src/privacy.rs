@@ -0,0 +1,200 @@
+//! Privacy usage descriptions.
+//!
+//! macOS terminates apps touching a protected resource (camera, microphone, other apps through
+//! Apple Events, ...) when Info.plist has no usage description for it, and script payloads die
+//! without any dialog. `--privacy` adds the `NS*UsageDescription` keys, and after the bundle is
+//! written its scripts are searched for commands and APIs needing one that is still missing.
+//!
+//! Only the files directly in `Contents/MacOS` and `Contents/Resources` are searched: vendored
+//! runtimes and libraries live in subdirectories and mention these APIs without using them.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+
+/// Scripts larger than this are not searched
+const MAX_SCRIPT_SIZE: u64 = 1024 * 1024;
+
+/// A protected resource needing a usage description
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Permission {
+    Camera,
+    Microphone,
+    AppleEvents,
+    Location,
+    Contacts,
+    Calendars,
+    Reminders,
+    Photos,
+    Bluetooth,
+    SpeechRecognition,
+    DesktopFolder,
+    DocumentsFolder,
+    DownloadsFolder,
+    RemovableVolumes,
+    NetworkVolumes,
+    LocalNetwork,
+    SystemAdministration,
+}
+
+impl Permission {
+    /// Info.plist key of the usage description
+    pub fn key(self) -> &'static str {
+        match self {
+            Permission::Camera => "NSCameraUsageDescription",
+            Permission::Microphone => "NSMicrophoneUsageDescription",
+            Permission::AppleEvents => "NSAppleEventsUsageDescription",
+            Permission::Location => "NSLocationUsageDescription",
+            Permission::Contacts => "NSContactsUsageDescription",
+            Permission::Calendars => "NSCalendarsUsageDescription",
+            Permission::Reminders => "NSRemindersUsageDescription",
+            Permission::Photos => "NSPhotoLibraryUsageDescription",
+            Permission::Bluetooth => "NSBluetoothAlwaysUsageDescription",
+            Permission::SpeechRecognition => "NSSpeechRecognitionUsageDescription",
+            Permission::DesktopFolder => "NSDesktopFolderUsageDescription",
+            Permission::DocumentsFolder => "NSDocumentsFolderUsageDescription",
+            Permission::DownloadsFolder => "NSDownloadsFolderUsageDescription",
+            Permission::RemovableVolumes => "NSRemovableVolumesUsageDescription",
+            Permission::NetworkVolumes => "NSNetworkVolumesUsageDescription",
+            Permission::LocalNetwork => "NSLocalNetworkUsageDescription",
+            Permission::SystemAdministration => "NSSystemAdministrationUsageDescription",
+        }
+    }
+
+    /// Default usage description following the app name
+    fn purpose(self) -> &'static str {
+        match self {
+            Permission::Camera => "needs access to the camera",
+            Permission::Microphone => "needs access to the microphone",
+            Permission::AppleEvents => "needs to control other applications",
+            Permission::Location => "needs access to your location",
+            Permission::Contacts => "needs access to your contacts",
+            Permission::Calendars => "needs access to your calendars",
+            Permission::Reminders => "needs access to your reminders",
+            Permission::Photos => "needs access to your photo library",
+            Permission::Bluetooth => "needs to use Bluetooth",
+            Permission::SpeechRecognition => "needs to use speech recognition",
+            Permission::DesktopFolder => "needs access to files in your Desktop folder",
+            Permission::DocumentsFolder => "needs access to files in your Documents folder",
+            Permission::DownloadsFolder => "needs access to files in your Downloads folder",
+            Permission::RemovableVolumes => "needs access to files on removable volumes",
+            Permission::NetworkVolumes => "needs access to files on network volumes",
+            Permission::LocalNetwork => "needs to find and connect to devices on your local network",
+            Permission::SystemAdministration => "needs administrator privileges",
+        }
+    }
+
+    /// Commands and APIs of scripts needing the usage description
+    fn triggers(self) -> &'static [&'static str] {
+        match self {
+            Permission::Camera => &["imagesnap", "AVCaptureDevice", "cv2.VideoCapture", "-f avfoundation"],
+            Permission::Microphone => &["AVAudioRecorder", "pyaudio", "sounddevice", "-f avfoundation"],
+            Permission::AppleEvents => &["osascript", "tell application", "NSAppleScript"],
+            Permission::Location => &["CLLocationManager", "CoreLocationCLI"],
+            Permission::Contacts => &["CNContactStore"],
+            Permission::Calendars => &["EKEventStore", "icalBuddy"],
+            Permission::Reminders => &["EKReminder"],
+            Permission::Photos => &["PHPhotoLibrary", "osxphotos"],
+            Permission::Bluetooth => &["CBCentralManager", "blueutil"],
+            Permission::SpeechRecognition => &["SFSpeechRecognizer"],
+            Permission::DesktopFolder => &["~/Desktop", "$HOME/Desktop", "${HOME}/Desktop"],
+            Permission::DocumentsFolder => &["~/Documents", "$HOME/Documents", "${HOME}/Documents"],
+            Permission::DownloadsFolder => &["~/Downloads", "$HOME/Downloads", "${HOME}/Downloads"],
+            Permission::RemovableVolumes | Permission::NetworkVolumes => &[],
+            Permission::LocalNetwork => &["NWBrowser", "NetServiceBrowser", "dns-sd -B"],
+            Permission::SystemAdministration => &["with administrator privileges"],
+        }
+    }
+
+    fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+/// Parses the PERMISSION=TEXT of `--privacy-text`
+pub fn parse_text(value: &str) -> std::result::Result<(Permission, String), String> {
+    let (name, text) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected PERMISSION=TEXT, got \"{}\"", value))?;
+    let permission = Permission::from_str(name.trim(), true)?;
+    if text.trim().is_empty() {
+        return Err(format!("the usage description of {} is empty", name));
+    }
+    Ok((permission, text.to_string()))
+}
+
+/// Info.plist entries of the `--privacy` permissions and the ones given a `--privacy-text`
+pub fn usage_descriptions(args: &crate::Args) -> Vec<(String, plist::Value)> {
+    let mut permissions: Vec<Permission> = args.privacy.clone();
+    permissions.extend(args.privacy_text.iter().map(|(permission, _)| *permission));
+    let mut entries: Vec<(String, plist::Value)> = Vec::new();
+    for permission in permissions {
+        if entries.iter().any(|(key, _)| key == permission.key()) {
+            continue;
+        }
+        let text = match args.privacy_text.iter().rev().find(|(p, _)| *p == permission) {
+            Some((_, text)) => text.clone(),
+            None => format!("{} {}.", args.name, permission.purpose()),
+        };
+        entries.push((permission.key().to_string(), plist::Value::String(text)));
+    }
+    entries
+}
+
+/// Warns about scripts of the bundle that need a usage description missing from Info.plist
+/// (failing with `strict`)
+pub fn check_payloads(app_path: &Path, strict: bool) -> Result<()> {
+    let info = crate::read_info_plist(app_path)?;
+    let contents = app_path.join("Contents");
+    let mut findings = Vec::new();
+    for dir in ["MacOS", "Resources"] {
+        let Ok(entries) = fs::read_dir(contents.join(dir)) else {
+            continue;
+        };
+        let mut paths = entries
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.sort();
+        for path in paths {
+            let Some(text) = read_script(&path) else {
+                continue;
+            };
+            let relative = path.strip_prefix(app_path).unwrap_or(&path).display().to_string();
+            for permission in Permission::value_variants() {
+                if info.contains_key(permission.key()) {
+                    continue;
+                }
+                if let Some(trigger) = permission.triggers().iter().find(|t| text.contains(*t)) {
+                    findings.push(format!(
+                        "{} uses `{}`, which needs {} (add --privacy {})",
+                        relative,
+                        trigger,
+                        permission.key(),
+                        permission.name()
+                    ));
+                }
+            }
+        }
+    }
+    if strict && !findings.is_empty() {
+        anyhow::bail!("Missing usage descriptions:\n  {}", findings.join("\n  "));
+    }
+    for finding in &findings {
+        println!("Warning: {}", finding);
+    }
+    Ok(())
+}
+
+/// Text of a script, None for directories, binaries and large files
+fn read_script(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_SCRIPT_SIZE {
+        return None;
+    }
+    let data = fs::read(path).ok()?;
+    if data.iter().take(8192).any(|b| *b == 0) {
+        return None;
+    }
+    String::from_utf8(data).ok()
+}
@@ -0,0 +1,282 @@
+//! Script payloads: shebang detection, vendored runtimes and the launcher running them.
+//!
+//! Apps started from Finder get a minimal `PATH` and no shell profile, so a script relying on
+//! `#!/usr/bin/env python3` often finds no interpreter. Python, Node and other interpreted
+//! scripts are therefore moved to `MacOS/{name}_script` and started by a launcher that sets up
+//! the search paths and prefers the runtime vendored into `Contents/Resources`.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Locations of interpreters installed by Homebrew and installers, missing from Finder's `PATH`
+const EXTRA_PATH: &str = "/opt/homebrew/bin:/usr/local/bin";
+
+/// Interpreter line of a script
+#[derive(Debug)]
+pub struct Shebang {
+    /// Interpreter path, or its name when resolved through `/usr/bin/env`
+    pub interpreter: String,
+    pub args: Vec<String>,
+}
+
+impl Shebang {
+    fn name(&self) -> &str {
+        self.interpreter.rsplit('/').next().unwrap_or(&self.interpreter)
+    }
+
+    fn kind(&self) -> ScriptKind {
+        let name = self.name();
+        if name.starts_with("python") {
+            ScriptKind::Python
+        } else if name == "node" || name == "nodejs" {
+            ScriptKind::Node
+        } else if ["sh", "bash", "zsh", "dash", "ksh", "csh", "tcsh", "fish"].contains(&name) {
+            ScriptKind::Shell
+        } else {
+            ScriptKind::Other
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptKind {
+    Python,
+    Node,
+    Shell,
+    Other,
+}
+
+/// Reads the shebang of a file, `None` for binaries and files without one
+pub fn read_shebang(path: &Path) -> Result<Option<Shebang>> {
+    let data = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    let Some(line) = data.strip_prefix(b"#!") else {
+        return Ok(None);
+    };
+    let line = line.split(|b| *b == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.split_whitespace().map(str::to_string);
+    let Some(mut interpreter) = parts.next() else {
+        return Ok(None);
+    };
+    let mut args: Vec<String> = parts.collect();
+    if interpreter.ends_with("/env") {
+        // `env -S` splits the rest of the line itself
+        if args.first().is_some_and(|a| a == "-S") {
+            args.remove(0);
+        }
+        if args.is_empty() {
+            return Ok(None);
+        }
+        interpreter = args.remove(0);
+    }
+    Ok(Some(Shebang { interpreter, args }))
+}
+
+/// Where the launcher finds the interpreter
+enum Runtime {
+    /// The interpreter named by the shebang, looked up on the target machine
+    System,
+    /// A vendored virtualenv at `Resources/venv`, running the interpreter named by the shebang
+    Venv(String),
+    /// A vendored interpreter, relative to `Resources`
+    Bundled(String),
+}
+
+/// Vendors the runtime of a script executable and replaces it with a launcher
+pub fn package_script(args: &crate::Args, app_path: &Path, target_path: &Path) -> Result<()> {
+    let shebang = read_shebang(target_path)?;
+    let kind = shebang.as_ref().map(Shebang::kind);
+    if args.python_venv.is_some() && kind != Some(ScriptKind::Python) {
+        anyhow::bail!("--python-venv requires a Python script executable (with a python shebang)");
+    }
+    if args.node_modules.is_some() && kind != Some(ScriptKind::Node) {
+        anyhow::bail!("--node-modules requires a Node script executable (with a node shebang)");
+    }
+    if args.runtime.is_some() && !matches!(kind, Some(ScriptKind::Python | ScriptKind::Node | ScriptKind::Other)) {
+        anyhow::bail!("--runtime requires a script executable with an interpreter shebang");
+    }
+    let Some(shebang) = shebang else {
+        return Ok(());
+    };
+    // Shells always exist on macOS and need no launcher
+    if kind == Some(ScriptKind::Shell) {
+        return Ok(());
+    }
+
+    let resources = app_path.join("Contents").join("Resources");
+    let mut runtime = Runtime::System;
+    if let Some(venv) = &args.python_venv {
+        vendor_venv(Path::new(venv), &resources, shebang.name())?;
+        runtime = Runtime::Venv(shebang.name().to_string());
+    }
+    if let Some(node_modules) = &args.node_modules {
+        let source = Path::new(node_modules);
+        if !source.is_dir() {
+            anyhow::bail!("node_modules directory not found: {}", node_modules);
+        }
+        crate::copy_tree(source, &resources.join("node_modules"))?;
+        println!("Vendored {} into {}", node_modules, resources.join("node_modules").display());
+    }
+    if let Some(runtime_path) = &args.runtime {
+        runtime = Runtime::Bundled(vendor_runtime(Path::new(runtime_path), &resources, shebang.name())?);
+    }
+    if matches!(runtime, Runtime::System) {
+        check_system_interpreter(&shebang);
+    }
+
+    // Keep the script next to the launcher that replaces it
+    let file_name = target_path.file_name().unwrap().to_string_lossy().to_string();
+    let script_path = target_path.with_file_name(format!("{}_script", file_name));
+    fs::rename(target_path, &script_path).context("Failed to rename script executable")?;
+    fs::write(target_path, launcher_script(&shebang, kind.unwrap(), &runtime, &file_name))
+        .context("Failed to write script launcher")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(target_path, fs::Permissions::from_mode(0o755))
+            .context("Failed to set permissions on script launcher")?;
+    }
+    Ok(())
+}
+
+fn vendor_venv(venv: &Path, resources: &Path, interpreter_name: &str) -> Result<()> {
+    if !venv.join("pyvenv.cfg").is_file() {
+        anyhow::bail!("{} is not a virtualenv (no pyvenv.cfg)", venv.display());
+    }
+    // The launcher runs the interpreter the shebang names, python3.12 stays python3.12
+    let python = venv.join("bin").join(interpreter_name);
+    if fs::symlink_metadata(&python).is_err() {
+        anyhow::bail!(
+            "{} has no bin/{}, the interpreter named by the script's shebang",
+            venv.display(),
+            interpreter_name
+        );
+    }
+    // Virtualenvs link to the interpreter they were created from, which must exist on the target
+    if let Ok(link) = fs::read_link(&python)
+        && link.is_absolute()
+    {
+        println!(
+            "Warning: the virtualenv interpreter links to {}, which must exist on the target Mac. Use a relocatable Python (such as python-build-standalone) with --runtime for a self-contained bundle",
+            link.display()
+        );
+    }
+    crate::copy_tree(venv, &resources.join("venv"))?;
+    println!("Vendored virtualenv {} into {}", venv.display(), resources.join("venv").display());
+    Ok(())
+}
+
+/// Copies an interpreter (a binary, or a directory with `bin/{name}`) into `Resources/runtime`,
+/// returning the interpreter path relative to `Resources`
+fn vendor_runtime(source: &Path, resources: &Path, interpreter_name: &str) -> Result<String> {
+    let runtime_dir = resources.join("runtime");
+    let file_name = source
+        .file_name()
+        .context(format!("Invalid runtime path: {}", source.display()))?
+        .to_string_lossy()
+        .to_string();
+    let relative = if source.is_dir() {
+        let candidates = [interpreter_name, "python3", "node"];
+        let interpreter = candidates
+            .iter()
+            .map(|name| PathBuf::from("bin").join(name))
+            .find(|path| source.join(path).exists())
+            .context(format!("{} has no bin/{}", source.display(), interpreter_name))?;
+        crate::copy_tree(source, &runtime_dir.join(&file_name))?;
+        format!("runtime/{}/{}", file_name, interpreter.to_string_lossy())
+    } else if source.is_file() {
+        fs::create_dir_all(&runtime_dir).context("Failed to create runtime directory")?;
+        let target = runtime_dir.join(&file_name);
+        fs::copy(source, &target).context(format!("Failed to copy runtime {}", source.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target, fs::Permissions::from_mode(0o755))?;
+        }
+        format!("runtime/{}", file_name)
+    } else {
+        anyhow::bail!("Runtime not found: {}", source.display());
+    };
+    println!("Vendored runtime {} into {}", source.display(), runtime_dir.display());
+    Ok(relative)
+}
+
+/// Warns when the interpreter is unlikely to exist where the app runs
+fn check_system_interpreter(shebang: &Shebang) {
+    let found = if shebang.interpreter.starts_with('/') {
+        Path::new(&shebang.interpreter).exists()
+    } else {
+        let search_path = format!("{}:{}", std::env::var("PATH").unwrap_or_default(), EXTRA_PATH);
+        search_path
+            .split(':')
+            .any(|dir| Path::new(dir).join(&shebang.interpreter).is_file())
+    };
+    if !found {
+        println!(
+            "Warning: interpreter {} was not found on this machine, make sure it exists on the target Mac",
+            shebang.interpreter
+        );
+    }
+    match shebang.kind() {
+        ScriptKind::Python => println!(
+            "Warning: macOS does not ship Python 3 by default (/usr/bin/python3 asks to install the developer tools), consider --python-venv or --runtime"
+        ),
+        ScriptKind::Node => println!("Warning: macOS does not ship Node.js, consider --runtime with a node binary"),
+        _ => {}
+    }
+}
+
+/// Writes the launcher; names taken from the script and the runtime are single-quoted, the
+/// variables around them double-quoted
+fn launcher_script(shebang: &Shebang, kind: ScriptKind, runtime: &Runtime, name: &str) -> String {
+    let mut setup = String::new();
+    let interpreter = match runtime {
+        Runtime::System => crate::shell_quote(&shebang.interpreter),
+        Runtime::Venv(python) => {
+            setup.push_str("export VIRTUAL_ENV=\"$RESOURCES/venv\"\nexport PATH=\"$VIRTUAL_ENV/bin:$PATH\"\n");
+            format!("\"$VIRTUAL_ENV/bin/\"{}", crate::shell_quote(python))
+        }
+        Runtime::Bundled(relative) => {
+            let bin_dir = Path::new(relative).parent().unwrap_or(Path::new(""));
+            setup.push_str(&format!(
+                "export PATH=\"$RESOURCES/\"{}\":$PATH\"\n",
+                crate::shell_quote(&bin_dir.to_string_lossy())
+            ));
+            format!("\"$RESOURCES/\"{}", crate::shell_quote(relative))
+        }
+    };
+    match kind {
+        ScriptKind::Python => {
+            // Bytecode written next to the sources would break the code signature
+            setup.push_str("export PYTHONDONTWRITEBYTECODE=1\n");
+        }
+        ScriptKind::Node => {
+            setup.push_str(
+                "if [ -d \"$RESOURCES/node_modules\" ]; then\n    export NODE_PATH=\"$RESOURCES/node_modules\"\n    export PATH=\"$RESOURCES/node_modules/.bin:$PATH\"\nfi\n",
+            );
+        }
+        ScriptKind::Shell | ScriptKind::Other => {}
+    }
+    let interpreter_args: String = shebang.args.iter().map(|a| format!(" {}", crate::shell_quote(a))).collect();
+
+    format!(
+        r#"#!/bin/bash
+
+# Launcher running {name} with the bundled runtime
+SCRIPT_DIR="$( cd "$( dirname "${{BASH_SOURCE[0]}}" )" && pwd )"
+RESOURCES="$SCRIPT_DIR/../Resources"
+
+# Apps started from Finder get a minimal PATH
+export PATH="{extra_path}:$PATH"
+{setup}
+exec {interpreter}{interpreter_args} "$SCRIPT_DIR/"{script} "$@"
+"#,
+        name = name.replace(['\n', '\r'], " "),
+        extra_path = EXTRA_PATH,
+        setup = setup,
+        interpreter = interpreter,
+        interpreter_args = interpreter_args,
+        script = crate::shell_quote(&format!("{}_script", name)),
+    )
+}
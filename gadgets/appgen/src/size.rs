@@ -0,0 +1,367 @@
+//! Bundle size reports and deduplication.
+//!
+//! `appgen size` breaks the size of a bundle down by directory and file type and lists files with
+//! identical contents (same SHA-256 and permissions). `--dedup` replaces every copy but the first
+//! (in path order) with a hard link or a relative symlink to it, either in an existing bundle or
+//! while building one. Hard-linked files are counted once and symlinks are not followed.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// How duplicates are replaced
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DedupMode {
+    /// Hard links, transparent to the app but kept apart again by copies that do not preserve them
+    HardLink,
+    /// Relative symlinks, preserved by archives and disk images
+    Symlink,
+}
+
+/// Options of the `size` subcommand
+#[derive(clap::Args, Debug)]
+pub struct SizeArgs {
+    /// Path to the .app bundle
+    #[arg(value_name = "APP")]
+    app: String,
+
+    /// Depth of the directories in the breakdown, counted from the bundle (2 lists Contents/*)
+    #[arg(long, default_value_t = 2)]
+    depth: usize,
+
+    /// Rows shown per section
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Replace duplicates in the bundle (invalidates an existing signature)
+    #[arg(long, value_enum, value_name = "MODE")]
+    dedup: Option<DedupMode>,
+}
+
+/// A regular file of a bundle
+struct File {
+    /// Path relative to the bundle
+    path: PathBuf,
+    size: u64,
+    mode: u32,
+    /// Device and inode, shared by hard links
+    inode: Option<(u64, u64)>,
+}
+
+/// Files with the same contents and permissions, sorted by path
+struct Duplicates {
+    size: u64,
+    paths: Vec<PathBuf>,
+    /// Bytes taken by the copies beyond the first
+    wasted: u64,
+}
+
+/// Prints the size report of a bundle, deduplicating it first if asked to
+pub fn report(args: &SizeArgs) -> Result<()> {
+    let app_path = Path::new(&args.app);
+    if let Some(mode) = args.dedup {
+        if app_path.join("Contents/_CodeSignature").exists() {
+            println!("Warning: replacing files breaks the signature, sign the bundle again afterwards");
+        }
+        dedup(app_path, mode)?;
+        println!();
+    }
+    let files = scan(app_path)?;
+    let counted = counted_once(&files);
+    let total: u64 = counted.iter().map(|file| file.size).sum();
+    println!(
+        "{}: {} in {} file(s)",
+        app_path.display(),
+        format_size(total),
+        counted.len()
+    );
+
+    let mut directories: BTreeMap<PathBuf, (u64, usize)> = BTreeMap::new();
+    let mut types: BTreeMap<String, (u64, usize)> = BTreeMap::new();
+    for file in &counted {
+        let parent = file.path.parent().unwrap_or(Path::new(""));
+        let directory: PathBuf = parent.components().take(args.depth).collect();
+        let entry = directories.entry(directory).or_default();
+        entry.0 += file.size;
+        entry.1 += 1;
+        let extension = match file.path.extension() {
+            Some(extension) => format!(".{}", extension.to_string_lossy().to_lowercase()),
+            None => "(none)".to_string(),
+        };
+        let entry = types.entry(extension).or_default();
+        entry.0 += file.size;
+        entry.1 += 1;
+    }
+    let rows = |entries: Vec<(String, (u64, usize))>| -> Vec<[String; 4]> {
+        let mut entries = entries;
+        entries.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.0.cmp(&b.0)));
+        entries
+            .into_iter()
+            .take(args.top)
+            .map(|(name, (size, count))| {
+                [
+                    name,
+                    format_size(size),
+                    format!("{:.1}%", size as f64 * 100.0 / total.max(1) as f64),
+                    format!("{} file(s)", count),
+                ]
+            })
+            .collect()
+    };
+    println!("\nBy directory:");
+    print_table(&rows(
+        directories
+            .into_iter()
+            .map(|(directory, totals)| (directory.display().to_string(), totals))
+            .collect(),
+    ));
+    println!("\nBy file type:");
+    print_table(&rows(types.into_iter().collect()));
+
+    let groups = duplicates(app_path, &files)?;
+    if groups.is_empty() {
+        println!("\nNo duplicate files");
+        return Ok(());
+    }
+    let wasted: u64 = groups.iter().map(|group| group.wasted).sum();
+    println!(
+        "\nDuplicates: {} group(s), {} could be saved with --dedup:",
+        groups.len(),
+        format_size(wasted)
+    );
+    for group in groups.iter().take(args.top) {
+        println!("  {} x{}", format_size(group.size), group.paths.len());
+        for path in &group.paths {
+            println!("    {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Replaces duplicate files of a bundle with links to the first copy, returning the bytes saved
+pub fn dedup(app_path: &Path, mode: DedupMode) -> Result<u64> {
+    let files = scan(app_path)?;
+    let inodes: HashMap<&Path, Option<(u64, u64)>> =
+        files.iter().map(|file| (file.path.as_path(), file.inode)).collect();
+    let groups = duplicates(app_path, &files)?;
+    let (mut replaced, mut saved) = (0, 0);
+    for group in &groups {
+        let original = &group.paths[0];
+        for copy in &group.paths[1..] {
+            // Already hard links of the original
+            if inodes[copy.as_path()].is_some() && inodes[copy.as_path()] == inodes[original.as_path()] {
+                continue;
+            }
+            let copy_path = app_path.join(copy);
+            let copy_dir = copy.parent().context(format!("Invalid duplicate path {}", copy.display()))?;
+            fs::remove_file(&copy_path).context(format!("Failed to remove {}", copy_path.display()))?;
+            match mode {
+                DedupMode::HardLink => fs::hard_link(app_path.join(original), &copy_path),
+                DedupMode::Symlink => symlink(&relative_path(copy_dir, original), &copy_path),
+            }
+            .context(format!("Failed to link {} to {}", copy.display(), original.display()))?;
+            replaced += 1;
+        }
+        saved += group.wasted;
+    }
+    if replaced > 0 {
+        let kind = match mode {
+            DedupMode::HardLink => "hard links",
+            DedupMode::Symlink => "symlinks",
+        };
+        println!(
+            "Replaced {} duplicate file(s) with {}, saving {}",
+            replaced,
+            kind,
+            format_size(saved)
+        );
+    }
+    Ok(saved)
+}
+
+fn scan(app_path: &Path) -> Result<Vec<File>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<File>) -> Result<()> {
+        let mut children = fs::read_dir(dir)
+            .context(format!("Failed to read directory: {}", dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        children.sort();
+        for path in children {
+            let metadata = fs::symlink_metadata(&path)?;
+            if metadata.is_dir() {
+                walk(root, &path, files)?;
+            } else if metadata.is_file() {
+                let relative = path
+                    .strip_prefix(root)
+                    .context(format!("{} is outside of {}", path.display(), root.display()))?;
+                files.push(File {
+                    path: relative.to_path_buf(),
+                    size: metadata.len(),
+                    mode: mode(&metadata),
+                    inode: inode(&metadata),
+                });
+            }
+        }
+        Ok(())
+    }
+    if !app_path.is_dir() {
+        anyhow::bail!("App bundle not found: {}", app_path.display());
+    }
+    let mut files = Vec::new();
+    walk(app_path, app_path, &mut files)?;
+    Ok(files)
+}
+
+/// Files with hard links to files listed before them left out
+fn counted_once<'a>(files: impl IntoIterator<Item = &'a File>) -> Vec<&'a File> {
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .filter(|file| file.inode.is_none_or(|inode| seen.insert(inode)))
+        .collect()
+}
+
+/// Groups of non-empty files with the same contents and permissions, largest waste first
+///
+/// Files are only grouped with files of the same nested bundle, whose signature would not accept
+/// links leaving it.
+fn duplicates(app_path: &Path, files: &[File]) -> Result<Vec<Duplicates>> {
+    let mut candidates: HashMap<(u64, u32, &Path), Vec<&File>> = HashMap::new();
+    for file in files.iter().filter(|file| file.size > 0) {
+        candidates
+            .entry((file.size, file.mode, innermost_bundle(&file.path)))
+            .or_default()
+            .push(file);
+    }
+    let mut groups = Vec::new();
+    for ((size, _, _), candidates) in candidates {
+        if counted_once(candidates.iter().copied()).len() < 2 {
+            continue;
+        }
+        let mut by_hash: BTreeMap<Vec<u8>, Vec<&File>> = BTreeMap::new();
+        for file in candidates {
+            let path = app_path.join(&file.path);
+            let data = fs::read(&path).context(format!("Failed to read {}", path.display()))?;
+            by_hash.entry(Sha256::digest(&data).to_vec()).or_default().push(file);
+        }
+        for (_, same) in by_hash {
+            let copies = counted_once(same.iter().copied()).len();
+            if copies < 2 {
+                continue;
+            }
+            let mut paths: Vec<PathBuf> = same.iter().map(|file| file.path.clone()).collect();
+            paths.sort();
+            groups.push(Duplicates {
+                size,
+                paths,
+                wasted: size * (copies as u64 - 1),
+            });
+        }
+    }
+    groups.sort_by(|a, b| b.wasted.cmp(&a.wasted).then(a.paths.cmp(&b.paths)));
+    Ok(groups)
+}
+
+/// The deepest nested bundle containing a path, or the empty path for the bundle itself
+fn innermost_bundle(path: &Path) -> &Path {
+    const BUNDLE_EXTENSIONS: &[&str] = &["app", "appex", "bundle", "framework", "xpc"];
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| {
+            ancestor
+                .extension()
+                .is_some_and(|ext| BUNDLE_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()))
+        })
+        .unwrap_or(Path::new(""))
+}
+
+/// Path of `target` relative to the directory `from`, both relative to the same root
+fn relative_path(from: &Path, target: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let target: Vec<Component> = target.components().collect();
+    let common = from.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    path.extend(&target[common..]);
+    path
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    let dir = link
+        .parent()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "link has no parent directory"))?;
+    let target = dir.join(target);
+    fs::copy(target, link).map(|_| ())
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    metadata.permissions().readonly() as u32
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Size with a decimal unit, as Finder shows it
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+fn print_table(rows: &[[String; 4]]) {
+    let mut widths = [0; 3];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        println!(
+            "  {:w0$}  {:>w1$}  {:>w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+    }
+}
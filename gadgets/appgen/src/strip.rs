@@ -0,0 +1,397 @@
+//! Stripping symbols and debug information from executables.
+//!
+//! `--strip` does what `strip -x` does to the Mach-O files in `Contents/MacOS`, without Apple's
+//! tools: local and debugging (STABS) symbols leave the symbol table, `__DWARF` segments are
+//! dropped and `__LINKEDIT` is rewritten without the space they took. Exported and undefined
+//! symbols stay, dyld binds by them. An embedded signature goes too, sign the bundle again.
+//!
+//! The unstripped images are kept in `{name}.symbols.zip` next to the bundle, one `{UUID}/{file}`
+//! entry per architecture keyed by the LC_UUID that crash reports and debuggers match images by.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::macho::{self, MachO, Slice};
+
+/// Symbol types of debugging entries
+const N_STAB: u8 = 0xe0;
+/// External symbol
+const N_EXT: u8 = 0x01;
+/// Indirect symbol table entries that do not refer to a symbol
+const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+/// Scattered relocations carry an address instead of a symbol
+const R_SCATTERED: u32 = 0x8000_0000;
+const R_EXTERN: u32 = 1 << 27;
+
+const LC_DYLD_INFO: u32 = 0x22;
+const LC_DYLD_INFO_ONLY: u32 = 0x8000_0022;
+/// Commands locating a single blob of __LINKEDIT by `dataoff` and `datasize`
+const LINKEDIT_DATA_COMMANDS: &[u32] = &[
+    0x1e,        // LC_SEGMENT_SPLIT_INFO
+    0x26,        // LC_FUNCTION_STARTS
+    0x29,        // LC_DATA_IN_CODE
+    0x2b,        // LC_DYLIB_CODE_SIGN_DRS
+    0x2e,        // LC_LINKER_OPTIMIZATION_HINT
+    0x36,        // LC_ATOM_INFO
+    0x8000_0033, // LC_DYLD_EXPORTS_TRIE
+    0x8000_0034, // LC_DYLD_CHAINED_FIXUPS
+];
+
+/// Segment holding the DWARF debug sections
+const DWARF_SEGMENT: &str = "__DWARF";
+
+/// What a blob of __LINKEDIT holds, the ones referring to symbols are rewritten
+#[derive(Clone, Copy, PartialEq)]
+enum Blob {
+    Symbols,
+    Strings,
+    IndirectSymbols,
+    ExternalRelocations,
+    Signature,
+    Other,
+}
+
+/// A blob of __LINKEDIT and the load command fields locating it
+struct Region {
+    blob: Blob,
+    /// File offsets of the offset and size (or count) fields
+    offset_field: usize,
+    size_field: usize,
+    offset: usize,
+    size: usize,
+}
+
+/// Strips the Mach-O files of Contents/MacOS and archives their unstripped images next to the bundle
+pub fn strip_bundle(args: &crate::Args, app_path: &Path) -> Result<()> {
+    let macos_dir = app_path.join("Contents").join("MacOS");
+    let mut paths = fs::read_dir(&macos_dir)
+        .context(format!("Failed to read directory: {}", macos_dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    let mut archived: Vec<(String, Vec<u8>)> = Vec::new();
+    let (mut stripped_files, mut saved) = (0, 0);
+    for path in paths {
+        if !fs::symlink_metadata(&path)?.is_file() {
+            continue;
+        }
+        let data = fs::read(&path).context(format!("Failed to read {}", path.display()))?;
+        if !macho::is_macho(&data) {
+            continue;
+        }
+        let file_name = path
+            .file_name()
+            .context(format!("Invalid executable path {}", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let (fat, slices) = macho::split_slices(&data).context(format!("Failed to parse {}", path.display()))?;
+        let (mut changed, mut was_signed) = (false, false);
+        let mut stripped_slices = Vec::with_capacity(slices.len());
+        for slice in slices {
+            let arch = macho::arch_name(slice.cputype);
+            let image = MachO::parse(slice.data.clone())?;
+            let Some(stripped) =
+                strip_image(&image).context(format!("Failed to strip {} ({})", path.display(), arch))?
+            else {
+                stripped_slices.push(slice);
+                continue;
+            };
+            was_signed |= image.code_signature().is_some();
+            match image.uuid() {
+                Some(uuid) => archived.push((format!("{}/{}", uuid, file_name), image.data)),
+                None => println!(
+                    "Warning: MacOS/{} ({}) has no LC_UUID, its symbols are not archived",
+                    file_name, arch
+                ),
+            }
+            changed = true;
+            stripped_slices.push(Slice {
+                data: stripped,
+                ..slice
+            });
+        }
+        if !changed {
+            continue;
+        }
+        let stripped = macho::join_slices(fat, stripped_slices);
+        // Writing over the file keeps its permissions
+        fs::write(&path, &stripped).context(format!("Failed to write {}", path.display()))?;
+        println!(
+            "Stripped MacOS/{}: {} -> {}",
+            file_name,
+            crate::size::format_size(data.len() as u64),
+            crate::size::format_size(stripped.len() as u64)
+        );
+        if was_signed && !args.sign {
+            println!(
+                "Warning: stripping removed the signature of MacOS/{}, add --sign",
+                file_name
+            );
+        }
+        stripped_files += 1;
+        saved += data.len().saturating_sub(stripped.len()) as u64;
+    }
+
+    if stripped_files == 0 {
+        println!("No symbols to strip in Contents/MacOS");
+        return Ok(());
+    }
+    let mut summary = format!(
+        "Saved {} by stripping {} file(s)",
+        crate::size::format_size(saved),
+        stripped_files
+    );
+    if !archived.is_empty() {
+        let archive_path = Path::new(&args.output).join(format!("{}.symbols.zip", args.name));
+        write_archive(&archive_path, &archived)?;
+        summary.push_str(&format!(", symbols archived in {}", archive_path.display()));
+    }
+    println!("{}", summary);
+    Ok(())
+}
+
+fn write_archive(path: &Path, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    let file = fs::File::create(path).context(format!("Failed to create {}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .unix_permissions(0o755);
+    for (name, data) in entries {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(data)?;
+    }
+    zip.finish().context(format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// The image without local symbols, debug segments and signature, None if it has nothing to strip
+fn strip_image(image: &MachO) -> Result<Option<Vec<u8>>> {
+    let mut image = MachO {
+        data: image.data.clone(),
+        is_64: image.is_64,
+    };
+    let commands = image.load_commands();
+    let segments = image.segments();
+    let linkedit = image.segment("__LINKEDIT").context("No __LINKEDIT segment")?;
+    let debug_segments: Vec<&macho::Segment> = segments.iter().filter(|s| s.name == DWARF_SEGMENT).collect();
+
+    // Symbols: keep the external ones, in order, and map their old indices to new ones
+    let nlist_size = if image.is_64 { 16 } else { 12 };
+    let symtab = commands
+        .iter()
+        .find(|(cmd, _, _)| *cmd == macho::LC_SYMTAB)
+        .map(|c| c.1);
+    let (mut symbols, mut strings) = (Vec::new(), vec![b' ', 0]);
+    let mut new_index: Vec<Option<u32>> = Vec::new();
+    if let Some(symtab) = symtab {
+        let (symoff, nsyms) = (image.u32_at(symtab + 8) as usize, image.u32_at(symtab + 12) as usize);
+        let (stroff, strsize) = (image.u32_at(symtab + 16) as usize, image.u32_at(symtab + 20) as usize);
+        let table = image
+            .data
+            .get(stroff..stroff + strsize)
+            .context("String table out of bounds")?;
+        for i in 0..nsyms {
+            let entry = image
+                .data
+                .get(symoff + i * nlist_size..symoff + (i + 1) * nlist_size)
+                .context("Symbol table out of bounds")?;
+            let n_type = entry[4];
+            if n_type & N_STAB != 0 || n_type & N_EXT == 0 {
+                new_index.push(None);
+                continue;
+            }
+            new_index.push(Some((symbols.len() / nlist_size) as u32));
+            let mut entry = entry.to_vec();
+            let strx = u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize;
+            if strx != 0 {
+                let name = table.get(strx..).context("Symbol name out of bounds")?;
+                let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+                entry[..4].copy_from_slice(&(strings.len() as u32).to_le_bytes());
+                strings.extend_from_slice(&name[..end]);
+                strings.push(0);
+            }
+            symbols.extend_from_slice(&entry);
+        }
+    }
+    let removed_symbols = new_index.iter().filter(|index| index.is_none()).count();
+    if removed_symbols == 0 && debug_segments.is_empty() {
+        return Ok(None);
+    }
+    let align = if image.is_64 { 8 } else { 4 };
+    strings.resize(strings.len().div_ceil(align) * align, 0);
+    let map_symbol = |index: u32| -> Result<u32> {
+        new_index
+            .get(index as usize)
+            .copied()
+            .flatten()
+            .context(format!("Symbol {} is referenced but local", index))
+    };
+
+    // The debug segments have to sit between the kept segments and __LINKEDIT, or after it
+    let mut kept_end = image.header_size() + image.sizeofcmds() as usize;
+    for segment in &segments {
+        if segment.name != DWARF_SEGMENT && segment.name != linkedit.name && segment.filesize > 0 {
+            if segment.fileoff >= linkedit.fileoff {
+                anyhow::bail!("Segment {} follows __LINKEDIT", segment.name);
+            }
+            kept_end = kept_end.max((segment.fileoff + segment.filesize) as usize);
+        }
+    }
+    let mut linkedit_offset = linkedit.fileoff as usize;
+    for segment in &debug_segments {
+        if segment.filesize > 0 && segment.fileoff < linkedit.fileoff {
+            if (segment.fileoff as usize) < kept_end {
+                anyhow::bail!("The {} segment is not at the end of the file", DWARF_SEGMENT);
+            }
+            linkedit_offset = linkedit_offset.min(segment.fileoff as usize);
+        }
+    }
+
+    // Lay the blobs of __LINKEDIT out again, with the rewritten symbol tables
+    let mut regions = linkedit_regions(&image, &commands);
+    regions.sort_by_key(|region| region.offset);
+    let linkedit_end = (linkedit.fileoff + linkedit.filesize) as usize;
+    let mut previous_end = linkedit.fileoff as usize;
+    for region in &regions {
+        if region.offset < previous_end || region.offset + region.size > linkedit_end {
+            anyhow::bail!("Unexpected __LINKEDIT layout");
+        }
+        previous_end = region.offset + region.size;
+    }
+    let mut new_linkedit = Vec::new();
+    for region in &regions {
+        if region.blob == Blob::Signature {
+            continue;
+        }
+        let old = &image.data[region.offset..region.offset + region.size];
+        let (data, count) = match region.blob {
+            Blob::Symbols => (symbols.clone(), symbols.len() / nlist_size),
+            Blob::Strings => (strings.clone(), strings.len()),
+            Blob::IndirectSymbols => {
+                let mut data = old.to_vec();
+                for entry in data.chunks_exact_mut(4) {
+                    let index = u32::from_le_bytes(entry.try_into().unwrap());
+                    if index & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) == 0 {
+                        entry.copy_from_slice(&map_symbol(index)?.to_le_bytes());
+                    }
+                }
+                (data, image.u32_at(region.size_field) as usize)
+            }
+            Blob::ExternalRelocations => {
+                let mut data = old.to_vec();
+                for entry in data.chunks_exact_mut(8) {
+                    let address = u32::from_le_bytes(entry[..4].try_into().unwrap());
+                    let info = u32::from_le_bytes(entry[4..].try_into().unwrap());
+                    if address & R_SCATTERED == 0 && info & R_EXTERN != 0 {
+                        let symbol = map_symbol(info & 0x00ff_ffff)?;
+                        entry[4..].copy_from_slice(&((info & 0xff00_0000) | symbol).to_le_bytes());
+                    }
+                }
+                (data, image.u32_at(region.size_field) as usize)
+            }
+            Blob::Signature | Blob::Other => (old.to_vec(), image.u32_at(region.size_field) as usize),
+        };
+        new_linkedit.resize(new_linkedit.len().div_ceil(align) * align, 0);
+        image.set_u32(region.offset_field, (linkedit_offset + new_linkedit.len()) as u32);
+        image.set_u32(region.size_field, count as u32);
+        new_linkedit.extend_from_slice(&data);
+    }
+
+    if let Some((_, dysymtab, _)) = commands.iter().find(|(cmd, _, _)| *cmd == macho::LC_DYSYMTAB) {
+        let kept = |range: std::ops::Range<usize>| {
+            new_index[range.start.min(new_index.len())..range.end.min(new_index.len())]
+                .iter()
+                .filter(|index| index.is_some())
+                .count()
+        };
+        // (first index, count) pairs of the local, defined external and undefined symbols
+        for field in [8, 16, 24] {
+            let first = image.u32_at(dysymtab + field) as usize;
+            let count = image.u32_at(dysymtab + field + 4) as usize;
+            image.set_u32(dysymtab + field, kept(0..first) as u32);
+            image.set_u32(dysymtab + field + 4, kept(first..first + count) as u32);
+        }
+    }
+    if image.is_64 {
+        image.set_u64(linkedit.cmd_offset + 40, linkedit_offset as u64);
+    } else {
+        image.set_u32(linkedit.cmd_offset + 32, linkedit_offset as u32);
+    }
+    let vmsize = if image.is_64 {
+        image.u64_at(linkedit.cmd_offset + 32)
+    } else {
+        image.u32_at(linkedit.cmd_offset + 28) as u64
+    };
+    image.set_segment_size(
+        &linkedit,
+        new_linkedit.len() as u64,
+        vmsize.max(new_linkedit.len() as u64),
+    );
+
+    // Drop the signature and debug segment commands, last first so the offsets stay valid
+    let mut removed: Vec<(usize, usize)> = commands
+        .iter()
+        .filter(|(cmd, offset, _)| {
+            *cmd == macho::LC_CODE_SIGNATURE || debug_segments.iter().any(|s| s.cmd_offset == *offset)
+        })
+        .map(|(_, offset, size)| (*offset, *size))
+        .collect();
+    removed.sort();
+    for (offset, size) in removed.into_iter().rev() {
+        image.remove_load_command(offset, size);
+    }
+
+    let mut data = image.data;
+    data.truncate(linkedit_offset);
+    data.extend_from_slice(&new_linkedit);
+    Ok(Some(data))
+}
+
+/// The blobs of __LINKEDIT the load commands point at
+fn linkedit_regions(image: &MachO, commands: &[(u32, usize, usize)]) -> Vec<Region> {
+    let nlist_size = if image.is_64 { 16 } else { 12 };
+    let module_size = if image.is_64 { 56 } else { 52 };
+    let mut regions = Vec::new();
+    let mut add = |blob: Blob, offset_field: usize, size_field: usize, scale: usize| {
+        let size = image.u32_at(size_field) as usize * scale;
+        if size > 0 {
+            regions.push(Region {
+                blob,
+                offset_field,
+                size_field,
+                offset: image.u32_at(offset_field) as usize,
+                size,
+            });
+        }
+    };
+    for &(cmd, offset, _) in commands {
+        match cmd {
+            macho::LC_SYMTAB => {
+                add(Blob::Symbols, offset + 8, offset + 12, nlist_size);
+                add(Blob::Strings, offset + 16, offset + 20, 1);
+            }
+            macho::LC_DYSYMTAB => {
+                add(Blob::Other, offset + 32, offset + 36, 8); // table of contents
+                add(Blob::Other, offset + 40, offset + 44, module_size);
+                add(Blob::Other, offset + 48, offset + 52, 4); // referenced symbols
+                add(Blob::IndirectSymbols, offset + 56, offset + 60, 4);
+                add(Blob::ExternalRelocations, offset + 64, offset + 68, 8);
+                add(Blob::Other, offset + 72, offset + 76, 8); // local relocations
+            }
+            LC_DYLD_INFO | LC_DYLD_INFO_ONLY => {
+                // Rebase, bind, weak bind, lazy bind and export info
+                for i in 0..5 {
+                    add(Blob::Other, offset + 8 + 8 * i, offset + 12 + 8 * i, 1);
+                }
+            }
+            macho::LC_CODE_SIGNATURE => add(Blob::Signature, offset + 8, offset + 12, 1),
+            cmd if LINKEDIT_DATA_COMMANDS.contains(&cmd) => add(Blob::Other, offset + 8, offset + 12, 1),
+            _ => {}
+        }
+    }
+    regions
+}
@@ -0,0 +1,60 @@
+//! Bundle templates for common app shapes.
+//!
+//! A template stands for a set of options that are placed in front of all others, before
+//! the manifest and the command line, so any option given later overrides it.
+
+use clap::ValueEnum;
+use std::ffi::OsString;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Template {
+    /// Menu bar app without a Dock icon, one instance per user
+    MenubarAgent,
+    /// Faceless background process without Dock icon or menu bar
+    BackgroundDaemon,
+    /// Command line program opened in a Terminal window
+    CliWithTerminal,
+    /// Regular Dock app opening documents, combine with --document-type
+    DocumentViewer,
+}
+
+impl Template {
+    /// Options the template stands for
+    fn preset(self) -> &'static [&'static str] {
+        match self {
+            Template::MenubarAgent => &[
+                "--show-terminal=false",
+                "--single-instance=true",
+                "--category",
+                "public.app-category.utilities",
+            ],
+            Template::BackgroundDaemon => &[
+                "--show-terminal=false",
+                "--background-only=true",
+                "--single-instance=true",
+            ],
+            Template::CliWithTerminal => &[
+                "--show-terminal=true",
+                "--run-in-terminal=true",
+                "--single-instance=false",
+                "--category",
+                "public.app-category.developer-tools",
+            ],
+            Template::DocumentViewer => &[
+                "--show-terminal=true",
+                "--single-instance=false",
+                "--category",
+                "public.app-category.productivity",
+            ],
+        }
+    }
+}
+
+/// Inserts the options of the template selected in `args` at `position`
+pub fn expand(command: clap::Command, args: &mut Vec<OsString>, position: usize) -> Result<(), clap::Error> {
+    let matches = crate::manifest::loose_matches(command, args)?;
+    if let Some(template) = matches.get_one::<Template>("template") {
+        args.splice(position..position, template.preset().iter().map(OsString::from));
+    }
+    Ok(())
+}
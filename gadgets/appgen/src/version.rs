@@ -0,0 +1,117 @@
+//! Versions read from the git repository.
+//!
+//! `--app-version from-git` takes CFBundleShortVersionString from the highest `vX.Y.Z` tag
+//! reachable from HEAD. `--build-number auto` takes CFBundleVersion from the build number of a
+//! known CI system, or the number of commits reachable from HEAD outside CI (CI checkouts are
+//! often shallow, which makes commit counts unreliable there).
+//!
+//! The repository is the one containing the manifest, or the executable without a manifest.
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::path::Path;
+
+/// Value of `--app-version` reading the version from the latest tag
+pub const FROM_GIT: &str = "from-git";
+/// Value of `--build-number` deriving the build number
+pub const AUTO: &str = "auto";
+
+/// Build number variables of CI systems, in the order they are checked
+const CI_BUILD_NUMBERS: &[&str] = &[
+    "GITHUB_RUN_NUMBER",
+    "CI_PIPELINE_IID",
+    "BUILDKITE_BUILD_NUMBER",
+    "CIRCLE_BUILD_NUM",
+    "TRAVIS_BUILD_NUMBER",
+    "BUILD_NUMBER",
+];
+
+/// Replaces `from-git` and `auto` in the version options with the values they stand for
+pub fn resolve(args: &crate::Args) -> Result<crate::Args> {
+    let mut args = args.clone();
+    if args.app_version != FROM_GIT && args.build_number.as_deref() != Some(AUTO) {
+        return Ok(args);
+    }
+    let dir = repository_dir(&args);
+    let repo = Repository::discover(dir).context(format!(
+        "--app-version from-git and --build-number auto need a git repository, none found from {}",
+        dir.display()
+    ))?;
+    if args.app_version == FROM_GIT {
+        args.app_version = latest_tag_version(&repo)?;
+        println!("Using version {} from git tags", args.app_version);
+    }
+    if args.build_number.as_deref() == Some(AUTO) {
+        let build_number = match ci_build_number() {
+            Some((variable, number)) => {
+                println!("Using build number {} from {}", number, variable);
+                number
+            }
+            None => {
+                let count = commit_count(&repo)?;
+                println!("Using build number {} from the commit count", count);
+                count.to_string()
+            }
+        };
+        args.build_number = Some(build_number);
+    }
+    Ok(args)
+}
+
+/// Directory the repository is discovered from: the manifest's, or else the executable's, so the
+/// result does not depend on where appgen is run
+fn repository_dir(args: &crate::Args) -> &Path {
+    let path = args.manifest.as_deref().unwrap_or(&args.executable);
+    match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// `X.Y.Z` of the highest `vX.Y.Z` tag reachable from HEAD
+fn latest_tag_version(repo: &Repository) -> Result<String> {
+    let head = repo.head().context("Repository has no HEAD")?.peel_to_commit()?.id();
+    let mut latest: Option<(u64, u64, u64)> = None;
+    for name in repo.tag_names(Some("v*"))?.iter().flatten() {
+        let Some(version) = parse_tag(name) else {
+            continue;
+        };
+        let Ok(commit) = repo
+            .revparse_single(&format!("refs/tags/{}", name))
+            .and_then(|object| object.peel_to_commit())
+        else {
+            continue;
+        };
+        let reachable = commit.id() == head || repo.graph_descendant_of(head, commit.id())?;
+        if reachable && latest.is_none_or(|latest| version > latest) {
+            latest = Some(version);
+        }
+    }
+    let (major, minor, patch) = latest.context("No vX.Y.Z tag is reachable from HEAD")?;
+    Ok(format!("{}.{}.{}", major, minor, patch))
+}
+
+fn parse_tag(name: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = name.strip_prefix('v')?.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+fn ci_build_number() -> Option<(&'static str, String)> {
+    CI_BUILD_NUMBERS.iter().find_map(|variable| {
+        let value = std::env::var(variable).ok()?;
+        let value = value.trim();
+        (!value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())).then(|| (*variable, value.to_string()))
+    })
+}
+
+fn commit_count(repo: &Repository) -> Result<usize> {
+    let mut walk = repo.revwalk()?;
+    walk.push_head().context("Repository has no HEAD")?;
+    let mut count = 0;
+    for commit in walk {
+        commit?;
+        count += 1;
+    }
+    Ok(count)
+}
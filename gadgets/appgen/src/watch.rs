@@ -0,0 +1,281 @@
+//! Watch mode: keeps the bundle up to date while its inputs change.
+//!
+//! The executable, icon and additional files are copied again on their own when they change.
+//! Any other input (the manifest, nested bundle manifests, helpers, frameworks, merge plist,
+//! entitlements) re-reads the command line and rebuilds the whole bundle. A signed bundle is
+//! signed again after every update.
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::Args;
+use crate::codesign::{SignOptions, SigningArgs};
+
+/// Time to wait for more events once a change is seen, compilers write files in several steps
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// What a watched path feeds into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Input {
+    Executable,
+    Icon,
+    /// Index into `Args::additional_files`
+    AdditionalFile(usize),
+    /// Anything needing a full rebuild
+    Other,
+}
+
+/// State of the watch loop, replaced on every full rebuild
+struct Build {
+    args: Args,
+    signing: SigningArgs,
+    sign_options: Option<SignOptions>,
+    inputs: Vec<(PathBuf, Input)>,
+}
+
+impl Build {
+    fn new(args: Args, signing: SigningArgs) -> Result<Self> {
+        let sign_options = if args.sign { Some(signing.resolve()?) } else { None };
+        let inputs = inputs(&args, &signing);
+        Ok(Build {
+            args,
+            signing,
+            sign_options,
+            inputs,
+        })
+    }
+
+    fn app_path(&self) -> PathBuf {
+        Path::new(&self.args.output).join(format!("{}.app", self.args.name))
+    }
+
+    /// Inputs an event path belongs to
+    fn affected(&self, path: &Path) -> Vec<Input> {
+        self.inputs
+            .iter()
+            .filter(|(input_path, _)| path.starts_with(input_path))
+            .map(|(_, input)| *input)
+            .collect()
+    }
+}
+
+/// Builds the bundle, then rebuilds it whenever an input changes, until interrupted
+pub fn run(args: Args, signing: SigningArgs) -> Result<()> {
+    let mut build = Build::new(args, signing)?;
+    crate::build_app(&build.args, &build.signing)?;
+    let mut app = None;
+    if build.args.relaunch {
+        app = Some(launch(&build)?);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("Failed to start file watcher")?;
+    let mut watched = watch_inputs(&mut watcher, &build.inputs, HashSet::new())?;
+    println!(
+        "Watching {} input(s) for changes, press Ctrl-C to stop",
+        build.inputs.len()
+    );
+
+    loop {
+        let mut changes = BTreeSet::new();
+        let mut next = Some(receiver.recv().context("File watcher stopped")?);
+        while let Some(event) = next {
+            match event {
+                // Reading the inputs while copying them raises access events, which are ignored
+                Ok(event)
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) =>
+                {
+                    for path in &event.paths {
+                        changes.extend(build.affected(path));
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => println!("Warning: file watcher error: {}", e),
+            }
+            next = receiver.recv_timeout(DEBOUNCE).ok();
+        }
+        if changes.is_empty() {
+            continue;
+        }
+
+        if let Some(mut child) = app.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let result = if changes.contains(&Input::Other) {
+            println!("Inputs changed, rebuilding {}", build.app_path().display());
+            rebuild(&build).map(|new_build| {
+                build = new_build;
+            })
+        } else {
+            update(&build, &changes)
+        };
+        match result {
+            Ok(()) => println!("Updated {}", build.app_path().display()),
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                continue;
+            }
+        }
+        watched = watch_inputs(&mut watcher, &build.inputs, watched)?;
+        if build.args.relaunch {
+            match launch(&build) {
+                Ok(child) => app = Some(child),
+                Err(e) => eprintln!("Error: {:#}", e),
+            }
+        }
+    }
+}
+
+/// Paths the bundle is built from
+fn inputs(args: &Args, signing: &SigningArgs) -> Vec<(PathBuf, Input)> {
+    let mut inputs = vec![(absolute(&args.executable), Input::Executable)];
+    if let Some(icon) = &args.icon {
+        inputs.push((absolute(icon), Input::Icon));
+    }
+    for (index, entry) in args.additional_files.iter().enumerate() {
+        let source = entry.split(':').next().unwrap_or_default();
+        inputs.push((absolute(source), Input::AdditionalFile(index)));
+    }
+    let others = args
+        .manifest
+        .iter()
+        .chain(&args.login_items)
+        .chain(&args.helper_apps)
+        .chain(&args.helpers)
+        .chain(&args.frameworks)
+        .chain(&args.merge_plist)
+        .chain(&signing.entitlements);
+    inputs.extend(others.map(|path| (absolute(path), Input::Other)));
+    inputs
+}
+
+/// Absolute path with symlinks in the parent resolved, matching the paths of watcher events
+fn absolute(path: &str) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path));
+    match (path.parent().and_then(|p| fs::canonicalize(p).ok()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path,
+    }
+}
+
+/// Watches the inputs, directories recursively and files through their parent directory
+/// (builds usually replace files rather than writing them in place)
+fn watch_inputs(
+    watcher: &mut impl Watcher,
+    inputs: &[(PathBuf, Input)],
+    watched: HashSet<PathBuf>,
+) -> Result<HashSet<PathBuf>> {
+    let mut wanted = HashSet::new();
+    for (path, _) in inputs {
+        let (target, mode) = if path.is_dir() {
+            (path.clone(), RecursiveMode::Recursive)
+        } else {
+            match path.parent() {
+                Some(parent) => (parent.to_path_buf(), RecursiveMode::NonRecursive),
+                None => continue,
+            }
+        };
+        if wanted.insert(target.clone()) && !watched.contains(&target) {
+            watcher
+                .watch(&target, mode)
+                .context(format!("Failed to watch {}", target.display()))?;
+        }
+    }
+    for stale in watched.difference(&wanted) {
+        let _ = watcher.unwatch(stale);
+    }
+    Ok(wanted)
+}
+
+/// Re-reads the command line and manifest and builds the whole bundle
+fn rebuild(build: &Build) -> Result<Build> {
+    let cli = crate::parse_cli_from(std::env::args_os().collect())?;
+    let args = cli.build.context("Build options are no longer valid")?;
+    let new_build = Build::new(args, cli.signing)?;
+    crate::build_app(&new_build.args, &new_build.signing)?;
+    // A renamed bundle leaves the previous one behind
+    if new_build.app_path() != build.app_path() {
+        println!("Note: {} is no longer updated", build.app_path().display());
+    }
+    Ok(new_build)
+}
+
+/// Copies the changed inputs into the existing bundle
+fn update(build: &Build, changes: &BTreeSet<Input>) -> Result<()> {
+    let args = &build.args;
+    let app_path = build.app_path();
+    // Deduplicated files share their data, copying over one of them would change the others,
+    // and the symbol archive of a stripped bundle covers all of its executables
+    if !app_path.exists() || args.dedup.is_some() || args.strip {
+        return crate::build_app(args, &build.signing);
+    }
+    for change in changes {
+        match change {
+            Input::Executable => {
+                println!("Executable changed: {}", args.executable);
+                let macos_dir = app_path.join("Contents").join("MacOS");
+                let name = executable_name(args);
+                // Wrappers and launchers move the executable next to them
+                for file in [name.clone(), format!("{}_original", name), format!("{}_script", name)] {
+                    let path = macos_dir.join(file);
+                    if path.exists() {
+                        fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+                    }
+                }
+                crate::copy_executable(args, &app_path)?;
+                crate::framework::link_executable(args, &app_path)?;
+            }
+            Input::Icon => {
+                let icon = args.icon.as_deref().unwrap();
+                println!("Icon changed: {}", icon);
+                crate::copy_icon(icon, &args.icon_name, &app_path)?;
+            }
+            Input::AdditionalFile(index) => {
+                let entry = &args.additional_files[*index];
+                // Directories are replaced so removed files do not linger, the target is the
+                // copy of the directory and never the directory it was copied into
+                let (_, target) = crate::additional_file_target(args, entry, &app_path)?;
+                if target.is_dir() {
+                    fs::remove_dir_all(&target).context(format!("Failed to remove {}", target.display()))?;
+                }
+                crate::copy_additional_file(args, entry, &app_path)?;
+            }
+            Input::Other => unreachable!("handled by a full rebuild"),
+        }
+    }
+    if let Some(options) = &build.sign_options {
+        crate::codesign::sign_bundle(&app_path, options)?;
+    }
+    Ok(())
+}
+
+fn executable_name(args: &Args) -> String {
+    Path::new(&args.executable)
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Starts the bundle's executable directly, so it can be stopped before the next update
+fn launch(build: &Build) -> Result<Child> {
+    let executable = build
+        .app_path()
+        .join("Contents")
+        .join("MacOS")
+        .join(executable_name(&build.args));
+    println!("Launching {}", build.app_path().display());
+    std::process::Command::new(&executable)
+        .spawn()
+        .context(format!("Failed to launch {}", executable.display()))
+}
@@ -0,0 +1,292 @@
+//! Workspace manifests building several apps at once.
+//!
+//! A manifest with `[[app]]` tables describes one app per table. Its other keys are defaults
+//! shared by every app, which the app's own keys and then the command line override. Two keys
+//! exist only here: `bundle-id-prefix` gives apps without a bundle id `{prefix}.{name}`, and
+//! `version-file` reads the version from a text file or a Cargo.toml. Apps are built in
+//! parallel and a failing app does not stop the others.
+
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::codesign::SigningArgs;
+use crate::{Args, Cli, manifest};
+
+const PREFIX_KEY: &str = "bundle-id-prefix";
+const VERSION_FILE_KEY: &str = "version-file";
+
+/// Path of `--manifest` in raw arguments when it declares `[[app]]` tables
+pub fn find_workspace(args: &[OsString]) -> Result<Option<PathBuf>> {
+    let Some(path) = manifest::find_manifest_arg(Cli::command(), args) else {
+        return Ok(None);
+    };
+    Ok(read_table(&path)?.contains_key("app").then_some(path))
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let content = fs::read_to_string(path).context(format!("Failed to read manifest: {}", path.display()))?;
+    content
+        .parse()
+        .context(format!("Failed to parse manifest: {}", path.display()))
+}
+
+/// Options shared by all apps of a workspace
+struct Defaults {
+    args: Vec<OsString>,
+    bundle_id_prefix: Option<String>,
+    version: Option<String>,
+    /// Options given on the command line, applied last
+    overrides: Vec<OsString>,
+}
+
+/// Result of building one app
+struct Outcome {
+    label: String,
+    bundle_id: String,
+    version: String,
+    elapsed: Duration,
+    result: Result<PathBuf, String>,
+}
+
+/// Builds every `[[app]]` of a workspace manifest and prints a summary
+pub fn build_all(path: &Path, raw_args: &[OsString]) -> Result<()> {
+    let mut table = read_table(path)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let apps = match table.remove("app") {
+        Some(toml::Value::Array(apps)) if apps.iter().all(toml::Value::is_table) => apps,
+        _ => anyhow::bail!(
+            "Invalid manifest: {}: `app` must be a list of [[app]] tables",
+            path.display()
+        ),
+    };
+    let defaults = read_defaults(table, base, raw_args).context(format!("Invalid manifest: {}", path.display()))?;
+
+    // Apps are prepared one after the other so bundle clashes are found before building
+    let mut app_paths = HashSet::new();
+    let plans: Vec<(String, Result<(Args, SigningArgs)>)> = apps
+        .iter()
+        .enumerate()
+        .map(|(index, app)| {
+            let app = app.as_table().unwrap();
+            let label = match app.get("name").and_then(toml::Value::as_str) {
+                Some(name) => name.to_string(),
+                None => format!("app #{}", index + 1),
+            };
+            let plan = prepare_app(app, base, &defaults).and_then(|(args, signing)| {
+                let app_path = Path::new(&args.output).join(format!("{}.app", args.name));
+                if !app_paths.insert(app_path.clone()) {
+                    anyhow::bail!("Another app is also built at {}", app_path.display());
+                }
+                Ok((args, signing))
+            });
+            (label, plan)
+        })
+        .collect();
+
+    let outcomes: Vec<Outcome> = plans
+        .into_par_iter()
+        .map(|(label, plan)| {
+            let start = Instant::now();
+            match plan {
+                Ok((args, signing)) => Outcome {
+                    label,
+                    bundle_id: args.bundle_id.clone(),
+                    version: match &args.build_number {
+                        Some(build_number) => format!("{} ({})", args.app_version, build_number),
+                        None => args.app_version.clone(),
+                    },
+                    result: crate::build_app(&args, &signing)
+                        .map(|()| Path::new(&args.output).join(format!("{}.app", args.name)))
+                        .map_err(|e| format!("{:#}", e)),
+                    elapsed: start.elapsed(),
+                },
+                Err(e) => Outcome {
+                    label,
+                    bundle_id: String::new(),
+                    version: String::new(),
+                    result: Err(format!("{:#}", e)),
+                    elapsed: start.elapsed(),
+                },
+            }
+        })
+        .collect();
+
+    print_summary(&outcomes);
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} app(s) failed to build", failed, outcomes.len());
+    }
+    Ok(())
+}
+
+fn read_defaults(mut table: toml::Table, base: &Path, raw_args: &[OsString]) -> Result<Defaults> {
+    for key in ["login-item", "helper-app"] {
+        if table.contains_key(key) {
+            anyhow::bail!("Declare `{}` inside an [[app]] table", key);
+        }
+    }
+    let bundle_id_prefix = match table.remove(PREFIX_KEY) {
+        Some(toml::Value::String(prefix)) => Some(prefix.trim_end_matches('.').to_string()),
+        Some(_) => anyhow::bail!("`{}` must be a string", PREFIX_KEY),
+        None => None,
+    };
+    let version = take_version_file(&mut table, base)?;
+    let args = manifest::from_table(&table, base, &Cli::command())?.args;
+    Ok(Defaults {
+        args,
+        bundle_id_prefix,
+        version,
+        overrides: strip_manifest_arg(&raw_args[1..]),
+    })
+}
+
+/// Removes `version-file` from a table, returning the version it points to
+fn take_version_file(table: &mut toml::Table, base: &Path) -> Result<Option<String>> {
+    let Some(value) = table.remove(VERSION_FILE_KEY) else {
+        return Ok(None);
+    };
+    if table.contains_key("app-version") {
+        anyhow::bail!("Use either `app-version` or `{}`, not both", VERSION_FILE_KEY);
+    }
+    let file = value
+        .as_str()
+        .context(format!("`{}` must be a path", VERSION_FILE_KEY))?;
+    read_version(&base.join(file)).map(Some)
+}
+
+/// Reads a version from a Cargo.toml (`package.version` or `workspace.package.version`) or
+/// from a text file holding only the version
+fn read_version(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path).context(format!("Failed to read version file: {}", path.display()))?;
+    if path.file_name().is_some_and(|name| name == "Cargo.toml") {
+        let cargo: toml::Table = content.parse().context(format!("Failed to parse {}", path.display()))?;
+        let package = cargo
+            .get("package")
+            .or_else(|| cargo.get("workspace").and_then(|w| w.get("package")));
+        return match package.and_then(|p| p.get("version")) {
+            Some(toml::Value::String(version)) => Ok(version.clone()),
+            Some(_) => anyhow::bail!(
+                "{} inherits its version, point `{}` at the workspace Cargo.toml",
+                path.display(),
+                VERSION_FILE_KEY
+            ),
+            None => anyhow::bail!("{} has no package version", path.display()),
+        };
+    }
+    let version = content.trim();
+    if version.is_empty() || version.contains('\n') {
+        anyhow::bail!("{} must contain only the version", path.display());
+    }
+    Ok(version.to_string())
+}
+
+/// Drops `--manifest` from the command line options applied to every app
+fn strip_manifest_arg(args: &[OsString]) -> Vec<OsString> {
+    let mut stripped = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--manifest" {
+            iter.next();
+        } else if !arg.to_string_lossy().starts_with("--manifest=") {
+            stripped.push(arg.clone());
+        }
+    }
+    stripped
+}
+
+/// Assembles the options of one app: defaults, the app's table, then the command line
+fn prepare_app(app: &toml::Table, base: &Path, defaults: &Defaults) -> Result<(Args, SigningArgs)> {
+    let mut app = app.clone();
+    let version = take_version_file(&mut app, base)?.or(defaults.version.clone());
+    let app_manifest = manifest::from_table(&app, base, &Cli::command())?;
+
+    let mut args = vec![OsString::from("appgen")];
+    args.extend(defaults.args.iter().cloned());
+    if let Some(version) = version {
+        args.push("--app-version".into());
+        args.push(version.into());
+    }
+    args.extend(app_manifest.args);
+    args.extend(defaults.overrides.iter().cloned());
+    crate::template::expand(Cli::command(), &mut args, 1)?;
+
+    let matches = Cli::command()
+        .try_get_matches_from(&args)
+        .map_err(|e| anyhow::anyhow!(clap_message(&e)))?;
+    let cli = Cli::from_arg_matches(&matches)?;
+    let mut build = cli
+        .build
+        .context("Subcommands cannot be combined with a workspace manifest")?;
+    if build.watch {
+        anyhow::bail!("--watch is not supported with [[app]] manifests");
+    }
+    if let Some(prefix) = &defaults.bundle_id_prefix
+        && matches.value_source("bundle_id") != Some(ValueSource::CommandLine)
+    {
+        build.bundle_id = format!("{}.{}", prefix, crate::cask::cask_token(&build.name));
+    }
+    build.nested.extend(app_manifest.nested);
+    // Resolved here so the summary shows the versions from git
+    Ok((crate::version::resolve(&build)?, cli.signing))
+}
+
+/// Message of a clap error without the `error:` prefix and usage
+fn clap_message(error: &clap::Error) -> String {
+    let text = error.to_string();
+    let message: Vec<&str> = text
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .map(str::trim)
+        .collect();
+    message.join(" ").trim_start_matches("error: ").to_string()
+}
+
+fn print_summary(outcomes: &[Outcome]) {
+    let rows: Vec<[String; 5]> = outcomes
+        .iter()
+        .map(|outcome| {
+            let (status, detail) = match &outcome.result {
+                Ok(app_path) => ("ok".to_string(), app_path.display().to_string()),
+                Err(e) => ("FAILED".to_string(), e.lines().next().unwrap_or_default().to_string()),
+            };
+            [
+                outcome.label.clone(),
+                outcome.bundle_id.clone(),
+                outcome.version.clone(),
+                format!("{} ({:.1}s)", status, outcome.elapsed.as_secs_f64()),
+                detail,
+            ]
+        })
+        .collect();
+    let header = ["App", "Bundle id", "Version", "Result", "Details"].map(String::from);
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    println!();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = format!(
+            "{:w0$}  {:w1$}  {:w2$}  {:w3$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        );
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,161 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use quick_xml::events::Event;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn appgen(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Builds Demo.app with the given versions and packs it as `{dir}/releases/{archive_name}`
+fn release(dir: &Path, archive_name: &str, version: &str, build: &str) {
+    let executable = dir.join("demo");
+    fs::write(&executable, format!("#!/bin/sh\necho {}\n", version)).unwrap();
+    let build_dir = dir.join("build");
+    appgen(&[
+        "--executable",
+        executable.to_str().unwrap(),
+        "--name",
+        "Demo",
+        "--bundle-id",
+        "com.example.demo",
+        "--app-version",
+        version,
+        "--build-number",
+        build,
+        "--output",
+        build_dir.to_str().unwrap(),
+    ]);
+    let archive = fs::File::create(dir.join("releases").join(archive_name)).unwrap();
+    let mut tar = tar::Builder::new(GzEncoder::new(archive, Compression::default()));
+    tar.append_dir_all("Demo.app", build_dir.join("Demo.app")).unwrap();
+    tar.into_inner().unwrap().finish().unwrap();
+    fs::remove_dir_all(build_dir).unwrap();
+}
+
+/// An `<item>` of the generated feed
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    build: String,
+    url: String,
+    length: usize,
+    signature: Option<String>,
+}
+
+fn read_items(xml: &str) -> Vec<Item> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut items = Vec::new();
+    let mut build = String::new();
+    let mut in_version = false;
+    loop {
+        match reader.read_event().unwrap() {
+            Event::Start(e) if e.name().as_ref() == b"sparkle:version" => in_version = true,
+            Event::End(e) if e.name().as_ref() == b"sparkle:version" => in_version = false,
+            Event::Text(text) if in_version => build = text.unescape().unwrap().to_string(),
+            Event::Empty(e) if e.name().as_ref() == b"enclosure" => {
+                let attribute = |name: &str| {
+                    e.try_get_attribute(name)
+                        .unwrap()
+                        .map(|a| a.unescape_value().unwrap().to_string())
+                };
+                items.push(Item {
+                    build: build.clone(),
+                    url: attribute("url").unwrap(),
+                    length: attribute("length").unwrap().parse().unwrap(),
+                    signature: attribute("sparkle:edSignature"),
+                });
+            }
+            Event::Eof => return items,
+            _ => {}
+        }
+    }
+}
+
+fn generate(dir: &Path, key_file: &Path) -> (String, Vec<Item>) {
+    let output = dir.join("appcast.xml");
+    let stdout = appgen(&[
+        "appcast",
+        dir.join("releases").to_str().unwrap(),
+        "--download-url-prefix",
+        "https://example.com/releases",
+        "--ed-key-file",
+        key_file.to_str().unwrap(),
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    (stdout, read_items(&fs::read_to_string(output).unwrap()))
+}
+
+fn write_key(dir: &Path) -> (std::path::PathBuf, VerifyingKey) {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let path = dir.join("ed25519.key");
+    fs::write(&path, BASE64.encode(key.to_bytes())).unwrap();
+    (path, key.verifying_key())
+}
+
+#[test]
+fn enclosure_signatures_verify_against_the_public_key() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("releases")).unwrap();
+    release(dir.path(), "Demo-1.0.tar.gz", "1.0", "1");
+    release(dir.path(), "Demo 1.1.tar.gz", "1.1", "2");
+    let (key_file, public_key) = write_key(dir.path());
+
+    let (stdout, items) = generate(dir.path(), &key_file);
+    assert!(stdout.contains(&format!("SUPublicEDKey: {}", BASE64.encode(public_key.as_bytes()))));
+    assert_eq!(items.len(), 2);
+    for item in items {
+        let file_name = item
+            .url
+            .strip_prefix("https://example.com/releases/")
+            .unwrap()
+            .replace("%20", " ");
+        let data = fs::read(dir.path().join("releases").join(file_name)).unwrap();
+        assert_eq!(item.length, data.len());
+        let signature = BASE64.decode(item.signature.unwrap()).unwrap();
+        let signature = Signature::from_slice(&signature).unwrap();
+        public_key.verify_strict(&data, &signature).unwrap();
+        assert!(public_key.verify_strict(b"tampered", &signature).is_err());
+    }
+}
+
+#[test]
+fn merging_keeps_older_items_and_replaces_the_same_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let releases = dir.path().join("releases");
+    fs::create_dir(&releases).unwrap();
+    let (key_file, _) = write_key(dir.path());
+
+    release(dir.path(), "Demo-1.0.tar.gz", "1.0", "1");
+    release(dir.path(), "Demo-1.1.tar.gz", "1.1", "2");
+    let (_, first) = generate(dir.path(), &key_file);
+    assert_eq!(first.iter().map(|i| i.build.as_str()).collect::<Vec<_>>(), ["2", "1"]);
+
+    // Only the newest archives are kept around, build 2 is republished under a new name
+    fs::remove_file(releases.join("Demo-1.0.tar.gz")).unwrap();
+    fs::remove_file(releases.join("Demo-1.1.tar.gz")).unwrap();
+    release(dir.path(), "Demo-1.1-fixed.tar.gz", "1.1", "2");
+    release(dir.path(), "Demo-1.10.tar.gz", "1.10", "10");
+    let (_, second) = generate(dir.path(), &key_file);
+
+    let builds: Vec<&str> = second.iter().map(|i| i.build.as_str()).collect();
+    assert_eq!(builds, ["10", "2", "1"], "items are ordered by version, newest first");
+    assert_eq!(second[2], first[1], "the item of build 1 is kept as it was");
+    assert_eq!(second[1].url, "https://example.com/releases/Demo-1.1-fixed.tar.gz");
+    assert_ne!(second[1], first[0]);
+}
@@ -0,0 +1,239 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Set to rewrite the snapshots from the current output instead of comparing against them
+const UPDATE_VAR: &str = "APPGEN_UPDATE_FIXTURES";
+
+fn fixtures() -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "bundles"]
+        .iter()
+        .collect()
+}
+
+/// Path of a fixture input (executables, icon, additional files)
+fn input(name: &str) -> String {
+    fixtures().join("inputs").join(name).to_str().unwrap().to_string()
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Builds `{output}/{name}.app` from the base options plus `extra`
+fn build(output: &Path, executable: &str, name: &str, extra: &[&str]) -> Output {
+    let executable = input(executable);
+    let mut args = vec![
+        "--executable",
+        &executable,
+        "--name",
+        name,
+        "--bundle-id",
+        "com.example.hello",
+        "--output",
+        output.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    run(&args)
+}
+
+/// One line per entry of the bundle: directories end with `/`, symlinks show their target and
+/// files whether they are executable and their size
+fn layout(app: &Path) -> String {
+    fn walk(root: &Path, dir: &Path, lines: &mut Vec<String>) {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+        entries.sort();
+        for path in entries {
+            let relative = path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+            let metadata = fs::symlink_metadata(&path).unwrap();
+            if metadata.file_type().is_symlink() {
+                lines.push(format!("{} -> {}", relative, fs::read_link(&path).unwrap().display()));
+            } else if metadata.is_dir() {
+                lines.push(format!("{}/", relative));
+                walk(root, &path, lines);
+            } else {
+                lines.push(format!("{} {} {}", relative, kind(&metadata), metadata.len()));
+            }
+        }
+    }
+    let mut lines = Vec::new();
+    walk(app, app, &mut lines);
+    lines.join("\n") + "\n"
+}
+
+#[cfg(unix)]
+fn kind(metadata: &fs::Metadata) -> &'static str {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o111 != 0 {
+        "exec"
+    } else {
+        "file"
+    }
+}
+
+#[cfg(not(unix))]
+fn kind(_metadata: &fs::Metadata) -> &'static str {
+    "file"
+}
+
+/// Compares the layout and Info.plist of a bundle with `tests/fixtures/bundles/{case}`
+fn check_snapshots(case: &str, app: &Path) {
+    let dir = fixtures().join(case);
+    let info = fs::read_to_string(app.join("Contents/Info.plist")).unwrap();
+    for (file_name, actual) in [("layout.txt", layout(app)), ("Info.plist", info)] {
+        let path = dir.join(file_name);
+        if std::env::var_os(UPDATE_VAR).is_some() {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            expected == actual,
+            "{} does not match the generated bundle, rerun the tests with {}=1 if the change is intended\n\
+             --- expected\n{}\n--- actual\n{}",
+            path.display(),
+            UPDATE_VAR,
+            expected,
+            actual
+        );
+    }
+}
+
+/// Builds a bundle that has to succeed and checks it against the snapshots of `case`, returning
+/// the directory holding it
+fn check_case(case: &str, executable: &str, name: &str, extra: &[&str]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), executable, name, extra);
+    assert!(
+        output.status.success(),
+        "building {} failed: {}",
+        case,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    check_snapshots(case, &dir.path().join(format!("{}.app", name)));
+    dir
+}
+
+/// Builds a bundle that has to fail, returning the error
+fn build_error(executable: &str, extra: &[&str]) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), executable, "Hello", extra);
+    assert!(!output.status.success(), "appgen {:?} succeeded", extra);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn minimal_script_bundle() {
+    check_case("minimal", "hello.sh", "Hello", &[]);
+}
+
+#[test]
+fn icon_and_additional_files() {
+    let data = format!("{}:Resources/texts/data.txt", input("data.txt"));
+    let tool = format!("{}:MacOS/tool.sh", input("tool.sh"));
+    check_case(
+        "resources",
+        "hello.sh",
+        "Hello",
+        &[
+            "--icon",
+            &input("icon.png"),
+            "--additional-file",
+            &data,
+            "--additional-file",
+            &input("config"),
+            "--additional-file",
+            &tool,
+        ],
+    );
+}
+
+#[test]
+fn metadata_and_single_instance_wrapper() {
+    check_case(
+        "metadata",
+        "hello.sh",
+        "Hello World",
+        &[
+            "--app-version",
+            "2.1.0",
+            "--build-number",
+            "42",
+            "--category",
+            "public.app-category.developer-tools",
+            "--document-type",
+            "txt",
+            "--document-type",
+            "md",
+            "--privacy",
+            "camera,microphone",
+            "--single-instance",
+            "--show-terminal",
+        ],
+    );
+}
+
+#[test]
+fn signed_macho_bundle() {
+    let dir = check_case("signed", "hello-arm64", "Hello", &["--sign"]);
+    let app = dir.path().join("Hello.app");
+    let output = run(&["verify", app.to_str().unwrap()]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.success(),
+        "verify failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("valid on disk"), "{}", stdout);
+}
+
+#[test]
+fn missing_executable_fails() {
+    let error = build_error("missing", &[]);
+    assert!(error.contains("Executable file not found"), "{}", error);
+}
+
+#[test]
+fn missing_icon_fails() {
+    let error = build_error("hello.sh", &["--icon", &input("missing.icns")]);
+    assert!(error.contains("Icon file not found"), "{}", error);
+}
+
+#[test]
+fn missing_additional_file_fails() {
+    let error = build_error("hello.sh", &["--additional-file", &input("missing.txt")]);
+    assert!(error.contains("Source file or directory not found"), "{}", error);
+}
+
+#[test]
+fn additional_file_outside_the_bundle_fails() {
+    for target in ["../escaped.txt", "/tmp/escaped.txt", ".", "./.", ""] {
+        let entry = format!("{}:{}", input("data.txt"), target);
+        let error = build_error("hello.sh", &["--additional-file", &entry]);
+        assert!(error.contains("Invalid additional file target"), "{}", error);
+    }
+}
+
+#[test]
+fn executable_without_a_name_keeps_the_existing_bundle() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(build(dir.path(), "hello.sh", "Hello", &[]).status.success());
+    let output = run(&[
+        "--executable",
+        "..",
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.example.hello",
+        "--output",
+        dir.path().to_str().unwrap(),
+    ]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("Invalid executable path: .."), "{}", stderr);
+    assert!(dir.path().join("Hello.app/Contents/MacOS/hello.sh").is_file());
+}
@@ -0,0 +1,130 @@
+//! The install script is bash, so it is only run on unix hosts
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+fn appgen(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Builds `{dir}/Demo.app` and gives it a name and version that a shell would expand
+fn build_hostile_app(dir: &Path, name: &str, version: &str) {
+    let executable = dir.join("demo");
+    fs::write(&executable, "#!/bin/sh\necho demo\n").unwrap();
+    appgen(&[
+        "--executable",
+        executable.to_str().unwrap(),
+        "--name",
+        "Demo",
+        "--bundle-id",
+        "com.example.demo",
+        "--output",
+        dir.to_str().unwrap(),
+    ]);
+    let info_path = dir.join("Demo.app/Contents/Info.plist");
+    let mut info = plist::Value::from_file(&info_path).unwrap();
+    let dict = info.as_dictionary_mut().unwrap();
+    dict.insert("CFBundleName".to_string(), name.into());
+    dict.insert("CFBundleShortVersionString".to_string(), version.into());
+    info.to_file_xml(&info_path).unwrap();
+}
+
+fn write_executable(path: &Path, contents: &str) {
+    fs::write(path, contents).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn install_script_keeps_quotes_dollars_and_newlines_literal() {
+    let dir = tempfile::tempdir().unwrap();
+    let name = "Bob's $(touch name-expanded) `touch name-backtick`\ntouch name-injected";
+    let version = "1.0\ntouch version-injected";
+    build_hostile_app(dir.path(), name, version);
+
+    let archive_name = "Demo's \"$(touch archive-expanded)\" `x` \\.tar.gz";
+    let archive = dir.path().join(archive_name);
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(dir.path())
+        .arg("Demo.app")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let script = dir.path().join("install.sh");
+    appgen(&[
+        "cask",
+        dir.path().join("Demo.app").to_str().unwrap(),
+        "--archive",
+        archive.to_str().unwrap(),
+        "--url",
+        "https://example.com/$HOME/{version}/{archive}",
+        "--output",
+        dir.path().join("demo.rb").to_str().unwrap(),
+        "--install-script",
+        script.to_str().unwrap(),
+    ]);
+
+    // Stand-ins for the download and the copy, recording what the script passed them
+    let bin = dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    write_executable(
+        &bin.join("curl"),
+        "#!/bin/sh\nprintf '%s' \"$2\" > \"$RECORD_DIR/url\"\nprintf '%s' \"$4\" > \"$RECORD_DIR/archive\"\ncp \"$SOURCE_ARCHIVE\" \"$4\"\n",
+    );
+    write_executable(&bin.join("ditto"), "#!/bin/sh\ncp -R \"$1\" \"$2\"\n");
+
+    let work = dir.path().join("work");
+    let records = dir.path().join("records");
+    let applications = dir.path().join("Applications");
+    for path in [&work, &records, &applications] {
+        fs::create_dir(path).unwrap();
+    }
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+    let output = Command::new("bash")
+        .arg(&script)
+        .current_dir(&work)
+        .env("PATH", path)
+        .env("RECORD_DIR", &records)
+        .env("SOURCE_ARCHIVE", &archive)
+        .env("INSTALL_DIR", &applications)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "install script failed: {}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(applications.join("Demo.app/Contents/Info.plist").is_file());
+    assert_eq!(
+        fs::read_dir(&work).unwrap().count(),
+        0,
+        "the script ran an interpolated command"
+    );
+    let url = fs::read_to_string(records.join("url")).unwrap();
+    assert_eq!(url, format!("https://example.com/$HOME/{}/{}", version, archive_name));
+    let downloaded = fs::read_to_string(records.join("archive")).unwrap();
+    assert!(downloaded.ends_with(&format!("/{}", archive_name)), "{}", downloaded);
+    assert!(
+        stdout.contains(&format!("Installing {} {} to", name, version)),
+        "{}",
+        stdout
+    );
+}
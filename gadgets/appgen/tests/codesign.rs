@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+fn appgen(args: &[&str]) -> String {
+    let output = run(args);
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds and signs `{dir}/Hello.app` around the arm64 fixture executable
+fn build_signed_app(dir: &Path, extra: &[&str]) -> PathBuf {
+    let executable = input("hello-arm64");
+    let mut args = vec![
+        "--executable",
+        &executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.example.hello",
+        "--output",
+        dir.to_str().unwrap(),
+        "--sign",
+    ];
+    args.extend_from_slice(extra);
+    appgen(&args);
+    dir.join("Hello.app")
+}
+
+/// Runs `appgen verify` on a bundle that has to fail, returning the problems reported
+fn verify_error(app: &Path) -> String {
+    let output = run(&["verify", app.to_str().unwrap()]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success(), "verify accepted a modified bundle");
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    stderr
+}
+
+fn u32_le(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+fn u32_be(data: &[u8], offset: usize) -> usize {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+/// Offset of the signature blob in `slot` of a thin 64-bit Mach-O, if it has one
+fn signature_blob(data: &[u8], slot: usize) -> Option<usize> {
+    let mut offset = 32;
+    for _ in 0..u32_le(data, 16) {
+        if u32_le(data, offset) == 0x1d {
+            let superblob = u32_le(data, offset + 8);
+            return (0..u32_be(data, superblob + 8))
+                .map(|i| superblob + 12 + 8 * i)
+                .find(|entry| u32_be(data, *entry) == slot)
+                .map(|entry| superblob + u32_be(data, entry + 4));
+        }
+        offset += u32_le(data, offset + 4);
+    }
+    None
+}
+
+/// Offset of the CodeDirectory blob in a thin 64-bit Mach-O
+fn code_directory_offset(data: &[u8]) -> usize {
+    signature_blob(data, 0).expect("no code directory")
+}
+
+#[test]
+fn freshly_signed_bundle_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = format!("{}:Resources/data.txt", input("data.txt"));
+    let app = build_signed_app(dir.path(), &["--additional-file", &data]);
+    let stdout = appgen(&["verify", app.to_str().unwrap()]);
+    assert!(stdout.contains("valid on disk"), "{}", stdout);
+}
+
+#[test]
+fn modified_resource_fails_verification() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = format!("{}:Resources/data.txt", input("data.txt"));
+    let app = build_signed_app(dir.path(), &["--additional-file", &data]);
+    fs::write(app.join("Contents/Resources/data.txt"), "changed after signing\n").unwrap();
+    let error = verify_error(&app);
+    assert!(error.contains("Resources/data.txt"), "{}", error);
+}
+
+#[test]
+fn modified_executable_fails_verification() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = build_signed_app(dir.path(), &[]);
+    let executable = app.join("Contents/MacOS/hello-arm64");
+    let mut data = fs::read(&executable).unwrap();
+    // First instruction of __text
+    data[0x400] ^= 0xff;
+    fs::write(&executable, data).unwrap();
+    let error = verify_error(&app);
+    assert!(error.contains("code page 0 was modified"), "{}", error);
+}
+
+#[test]
+fn corrupt_code_directory_is_reported() {
+    // (problem, offset in the code directory, bytes written there)
+    let corruptions: [(&str, usize, &[u8]); 3] = [
+        ("hashes are out of bounds", 16, &u32::MAX.to_be_bytes()),
+        ("hashes are out of bounds", 28, &0x0100_0000u32.to_be_bytes()),
+        ("unsupported code directory page size", 39, &[64]),
+    ];
+    for (expected, field, bytes) in corruptions {
+        let dir = tempfile::tempdir().unwrap();
+        let app = build_signed_app(dir.path(), &[]);
+        let executable = app.join("Contents/MacOS/hello-arm64");
+        let mut data = fs::read(&executable).unwrap();
+        let cd = code_directory_offset(&data);
+        data[cd + field..cd + field + bytes.len()].copy_from_slice(bytes);
+        fs::write(&executable, data).unwrap();
+        let error = verify_error(&app);
+        assert!(error.contains(expected), "{}", error);
+    }
+}
+
+#[test]
+fn nested_helper_gets_neither_runtime_nor_entitlements() {
+    let dir = tempfile::tempdir().unwrap();
+    let helper = input("hello-arm64");
+    let app = build_signed_app(
+        dir.path(),
+        &["--helper", &helper, "--hardened-runtime", "--allow-network-client"],
+    );
+    appgen(&["verify", app.to_str().unwrap()]);
+
+    let flags = |path: &str| {
+        let data = fs::read(app.join(path)).unwrap();
+        let cd = code_directory_offset(&data);
+        (u32_be(&data, cd + 12), signature_blob(&data, 5).is_some())
+    };
+    // CS_ADHOC | CS_RUNTIME and an entitlements blob (slot 5) for the main executable only
+    assert_eq!(flags("Contents/MacOS/hello-arm64"), (0x10002, true));
+    assert_eq!(flags("Contents/Helpers/hello-arm64"), (0x2, false));
+}
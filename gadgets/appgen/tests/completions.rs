@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Set to rewrite the fixtures from the current output instead of comparing against them
+const UPDATE_VAR: &str = "APPGEN_UPDATE_FIXTURES";
+
+fn appgen_output(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Compares generated output with `tests/fixtures/completions/{name}`
+fn check_fixture(name: &str, actual: &str) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "completions", name]
+        .iter()
+        .collect();
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        expected == actual,
+        "{} is out of date with the CLI definition, rerun the tests with {}=1 and commit the result",
+        path.display(),
+        UPDATE_VAR
+    );
+}
+
+#[test]
+fn completions_match_fixtures() {
+    for (shell, file_name) in [("bash", "appgen.bash"), ("zsh", "_appgen"), ("fish", "appgen.fish")] {
+        check_fixture(file_name, &appgen_output(&["completions", shell]));
+    }
+}
+
+#[test]
+fn man_page_matches_fixture() {
+    check_fixture("appgen.1", &appgen_output(&["man"]));
+}
+
+#[test]
+fn completions_cover_subcommands_and_value_enums() {
+    let bash = appgen_output(&["completions", "bash"]);
+    for expected in ["dmg", "import", "--default-location", "resources mac-os contents", "menubar-agent"] {
+        assert!(bash.contains(expected), "bash completions miss {}", expected);
+    }
+}
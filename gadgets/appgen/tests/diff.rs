@@ -0,0 +1,171 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+fn appgen(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/Hello.app`, returning its path
+fn build_app(dir: &Path, executable: &str, extra: &[&str]) -> String {
+    let mut args = vec![
+        "--executable",
+        executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        dir.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    appgen(&args);
+    dir.join("Hello.app").to_str().unwrap().to_string()
+}
+
+fn mode(path: &Path) -> String {
+    format!("{:o}", fs::metadata(path).unwrap().permissions().mode() & 0o7777)
+}
+
+fn file_json(path: &str, data: &[u8]) -> Value {
+    let sha256: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    json!({ "kind": "file", "sha256": sha256, "size": data.len(), "path": path })
+}
+
+#[test]
+fn json_lists_every_kind_of_difference() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = build_app(
+        &dir.path().join("old"),
+        &input("hello-arm64"),
+        &[
+            "--app-version",
+            "1.0.0",
+            "--additional-file",
+            &format!("{}:Resources/data.txt", input("data.txt")),
+            "--additional-file",
+            &format!("{}:Resources/tool.sh", input("tool.sh")),
+        ],
+    );
+
+    // The new version is built for x86_64 and ships different files
+    let x86_64 = dir.path().join("x86_64");
+    fs::create_dir(&x86_64).unwrap();
+    let mut executable = fs::read(input("hello-arm64")).unwrap();
+    executable[4..8].copy_from_slice(&0x0100_0007u32.to_le_bytes());
+    fs::write(x86_64.join("hello-arm64"), &executable).unwrap();
+    let new = build_app(
+        &dir.path().join("new"),
+        x86_64.join("hello-arm64").to_str().unwrap(),
+        &[
+            "--app-version",
+            "1.1.0",
+            "--category",
+            "public.app-category.utilities",
+            "--additional-file",
+            &format!("{}:Resources/data.txt", input("tool.sh")),
+            "--additional-file",
+            &format!("{}:Resources/extra.png", input("icon.png")),
+        ],
+    );
+    let pkg_info = Path::new(&new).join("Contents/PkgInfo");
+    fs::set_permissions(&pkg_info, fs::Permissions::from_mode(0o600)).unwrap();
+
+    let stdout = appgen(&["diff", &old, &new, "--json"]);
+    let diff: Value = serde_json::from_str(&stdout).unwrap();
+    let read = |path: &str| fs::read(input(path)).unwrap();
+    // A changed file as listed by the diff, from the contents on both sides
+    let changed = |path: &str, old_data: &[u8], new_data: &[u8]| {
+        let entry = |data| {
+            let mut entry = file_json(path, data);
+            entry.as_object_mut().unwrap().remove("path");
+            entry
+        };
+        json!({ "path": path, "old": entry(old_data), "new": entry(new_data) })
+    };
+    let in_bundle = |app: &str, path: &str| fs::read(Path::new(app).join(path)).unwrap();
+    assert_eq!(
+        diff,
+        json!({
+            "old": old,
+            "new": new,
+            "info_plist": {
+                "added": { "LSApplicationCategoryType": "public.app-category.utilities" },
+                "removed": {},
+                "changed": {
+                    "CFBundleShortVersionString": { "old": "1.0.0", "new": "1.1.0" },
+                    "CFBundleVersion": { "old": "1.0.0", "new": "1.1.0" },
+                },
+            },
+            "files": {
+                "added": [file_json("Contents/Resources/extra.png", &read("icon.png"))],
+                "removed": [file_json("Contents/Resources/tool.sh", &read("tool.sh"))],
+                "changed": [
+                    changed(
+                        "Contents/Info.plist",
+                        &in_bundle(&old, "Contents/Info.plist"),
+                        &in_bundle(&new, "Contents/Info.plist"),
+                    ),
+                    changed("Contents/MacOS/hello-arm64", &read("hello-arm64"), &executable),
+                    changed("Contents/Resources/data.txt", &read("data.txt"), &read("tool.sh")),
+                ],
+            },
+            "permissions": [
+                { "path": "Contents/PkgInfo", "old": mode(&Path::new(&old).join("Contents/PkgInfo")), "new": "600" },
+                {
+                    "path": "Contents/Resources/data.txt",
+                    "old": mode(Path::new(&input("data.txt"))),
+                    "new": mode(Path::new(&input("tool.sh"))),
+                },
+            ],
+            "architectures": [
+                { "path": "Contents/MacOS/hello-arm64", "old": ["arm64"], "new": ["x86_64"] },
+            ],
+        }),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn identical_bundles_have_an_empty_diff() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = build_app(&dir.path().join("old"), &input("hello.sh"), &[]);
+    let new = build_app(&dir.path().join("new"), &input("hello.sh"), &[]);
+    let diff: Value = serde_json::from_str(&appgen(&["diff", &old, &new, "--json"])).unwrap();
+    assert_eq!(diff["info_plist"], json!({ "added": {}, "removed": {}, "changed": {} }));
+    assert_eq!(diff["files"], json!({ "added": [], "removed": [], "changed": [] }));
+    assert_eq!(diff["permissions"], json!([]));
+    assert_eq!(diff["architectures"], json!([]));
+}
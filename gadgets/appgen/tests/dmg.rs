@@ -0,0 +1,229 @@
+use flate2::read::ZlibDecoder;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+const SECTOR_SIZE: usize = 512;
+const ROOT_FOLDER_ID: u32 = 2;
+
+fn appgen(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Reads a UDIF image back into the raw volume, checking the koly trailer, the mish block table
+/// and both CRC32 checksums on the way
+fn read_udif(image: &[u8]) -> Vec<u8> {
+    let koly = &image[image.len() - 512..];
+    assert_eq!(&koly[..4], b"koly");
+    assert_eq!(u32_at(koly, 4), 4, "koly version");
+    assert_eq!(u32_at(koly, 8), 512, "koly header size");
+    let data_fork_length = u64_at(koly, 32) as usize;
+    let data_fork = &image[u64_at(koly, 24) as usize..][..data_fork_length];
+    assert_eq!(u32_at(koly, 80), 2, "data fork checksum type");
+    assert_eq!(u32_at(koly, 88), crc32fast::hash(data_fork), "data fork checksum");
+    let xml_offset = u64_at(koly, 216) as usize;
+    let xml_length = u64_at(koly, 224) as usize;
+    assert_eq!(xml_offset + xml_length + 512, image.len());
+    let sector_count = u64_at(koly, 492) as usize;
+
+    let resources = plist::Value::from_reader_xml(&image[xml_offset..xml_offset + xml_length]).unwrap();
+    let blkx = resources
+        .as_dictionary()
+        .and_then(|d| d.get("resource-fork"))
+        .and_then(|v| v.as_dictionary())
+        .and_then(|d| d.get("blkx"))
+        .and_then(|v| v.as_array())
+        .unwrap();
+    assert_eq!(blkx.len(), 1);
+    let mish = blkx[0].as_dictionary().unwrap()["Data"].as_data().unwrap();
+    assert_eq!(&mish[..4], b"mish");
+    assert_eq!(u64_at(mish, 8), 0, "first sector");
+    assert_eq!(u64_at(mish, 16) as usize, sector_count);
+
+    let mut volume = vec![0u8; sector_count * SECTOR_SIZE];
+    let chunk_count = u32_at(mish, 200) as usize;
+    let mut next_sector = 0;
+    for i in 0..chunk_count {
+        let chunk = &mish[204 + i * 40..204 + (i + 1) * 40];
+        let kind = u32_at(chunk, 0);
+        let sector = u64_at(chunk, 8) as usize;
+        let sectors = u64_at(chunk, 16) as usize;
+        let stored = &data_fork[u64_at(chunk, 24) as usize..][..u64_at(chunk, 32) as usize];
+        if kind == 0xFFFF_FFFF {
+            assert_eq!(i, chunk_count - 1, "the terminator ends the table");
+            assert_eq!(sector, sector_count);
+            break;
+        }
+        assert_eq!(sector, next_sector, "chunks cover the volume without gaps");
+        next_sector += sectors;
+        let target = &mut volume[sector * SECTOR_SIZE..(sector + sectors) * SECTOR_SIZE];
+        match kind {
+            0x0000_0002 => assert!(stored.is_empty()),
+            0x0000_0001 => target.copy_from_slice(stored),
+            0x8000_0005 => ZlibDecoder::new(stored).read_exact(target).unwrap(),
+            _ => panic!("unexpected chunk type {:#x}", kind),
+        }
+    }
+    assert_eq!(next_sector, sector_count);
+    assert_eq!(u32_at(mish, 72), crc32fast::hash(&volume), "volume checksum");
+    volume
+}
+
+/// A catalog record of the HFS+ volume
+struct Entry {
+    kind: u16,
+    record: Vec<u8>,
+}
+
+/// Parses the HFS+ volume header and collects the catalog leaf records by `(parent id, name)`
+fn read_catalog(volume: &[u8]) -> HashMap<(u32, String), Entry> {
+    let header = &volume[1024..1536];
+    assert_eq!(&header[..2], b"HX");
+    assert_eq!(u16_at(header, 2), 5, "HFSX version");
+    let block_size = u32_at(header, 40) as usize;
+    let total_blocks = u32_at(header, 44) as usize;
+    assert_eq!(block_size * total_blocks, volume.len());
+    assert_eq!(&volume[volume.len() - 1024..][..512], header, "alternate volume header");
+
+    let catalog_fork = &header[272..352];
+    let start = u32_at(catalog_fork, 16) as usize;
+    let blocks = u32_at(catalog_fork, 20) as usize;
+    let catalog = &volume[start * block_size..(start + blocks) * block_size];
+
+    let node_size = u16_at(catalog, 14 + 18) as usize;
+    let leaf_records = u32_at(catalog, 14 + 6) as usize;
+    let mut node_number = u32_at(catalog, 14 + 10) as usize;
+    let mut entries = HashMap::new();
+    while node_number != 0 {
+        let node = &catalog[node_number * node_size..(node_number + 1) * node_size];
+        assert_eq!(node[8], 0xFF, "leaf node kind");
+        for i in 0..u16_at(node, 10) as usize {
+            let offset = u16_at(node, node_size - 2 * (i + 1)) as usize;
+            let key_length = u16_at(node, offset) as usize;
+            let parent = u32_at(node, offset + 2);
+            let name_length = u16_at(node, offset + 6) as usize;
+            let units: Vec<u16> = (0..name_length).map(|j| u16_at(node, offset + 8 + 2 * j)).collect();
+            let record = node[offset + 2 + key_length..].to_vec();
+            let kind = u16_at(&record, 0);
+            entries.insert((parent, String::from_utf16(&units).unwrap()), Entry { kind, record });
+        }
+        node_number = u32_at(node, 0) as usize;
+    }
+    assert_eq!(entries.len(), leaf_records);
+    entries
+}
+
+impl Entry {
+    fn folder_id(&self) -> u32 {
+        assert_eq!(self.kind, 1, "folder record");
+        u32_at(&self.record, 8)
+    }
+
+    fn mode(&self) -> u16 {
+        u16_at(&self.record, 42)
+    }
+
+    /// Contents of the single extent data fork of a file record
+    fn data<'a>(&self, volume: &'a [u8], block_size: usize) -> &'a [u8] {
+        assert_eq!(self.kind, 2, "file record");
+        let size = u64_at(&self.record, 88) as usize;
+        let start = u32_at(&self.record, 104) as usize;
+        &volume[start * block_size..][..size]
+    }
+}
+
+fn build_demo_app(dir: &Path) {
+    let executable = dir.join("demo");
+    fs::write(&executable, "#!/bin/sh\necho demo\n").unwrap();
+    appgen(&[
+        "--executable",
+        executable.to_str().unwrap(),
+        "--name",
+        "Demo",
+        "--bundle-id",
+        "com.example.demo",
+        "--output",
+        dir.to_str().unwrap(),
+    ]);
+}
+
+fn check_dmg(compressed: bool) {
+    let dir = tempfile::tempdir().unwrap();
+    build_demo_app(dir.path());
+    let app_path = dir.path().join("Demo.app");
+    let dmg_path = dir.path().join("Demo.dmg");
+    let mut args = vec![
+        "dmg",
+        app_path.to_str().unwrap(),
+        "--output",
+        dmg_path.to_str().unwrap(),
+        "--volume-name",
+        "Demo Installer",
+    ];
+    if !compressed {
+        args.push("--no-compress");
+    }
+    appgen(&args);
+
+    let volume = read_udif(&fs::read(&dmg_path).unwrap());
+    let block_size = u32_at(&volume, 1024 + 40) as usize;
+    let catalog = read_catalog(&volume);
+    let entry = |parent: u32, name: &str| {
+        catalog
+            .get(&(parent, name.to_string()))
+            .unwrap_or_else(|| panic!("no catalog entry {} in folder {}", name, parent))
+    };
+
+    assert_eq!(entry(1, "Demo Installer").folder_id(), ROOT_FOLDER_ID);
+
+    let applications = entry(ROOT_FOLDER_ID, "Applications");
+    assert_eq!(applications.kind, 2, "the /Applications link is a file record");
+    assert_eq!(&applications.record[48..56], b"slnkrhap");
+    assert_eq!(applications.mode() & 0o170000, 0o120000);
+    assert_eq!(applications.data(&volume, block_size), b"/Applications");
+
+    let app = entry(ROOT_FOLDER_ID, "Demo.app").folder_id();
+    let contents = entry(app, "Contents").folder_id();
+    let info = entry(contents, "Info.plist").data(&volume, block_size);
+    assert_eq!(info, fs::read(dir.path().join("Demo.app/Contents/Info.plist")).unwrap());
+    let macos = entry(contents, "MacOS").folder_id();
+    let executable = entry(macos, "demo");
+    assert_eq!(executable.data(&volume, block_size), b"#!/bin/sh\necho demo\n");
+    assert_eq!(executable.mode() & 0o111, 0o111, "the executable keeps its permissions");
+
+    let ds_store = entry(ROOT_FOLDER_ID, ".DS_Store").data(&volume, block_size);
+    assert_eq!(&ds_store[4..8], b"Bud1");
+}
+
+#[test]
+fn compressed_dmg_reads_back() {
+    check_dmg(true);
+}
+
+#[test]
+fn uncompressed_dmg_reads_back() {
+    check_dmg(false);
+}
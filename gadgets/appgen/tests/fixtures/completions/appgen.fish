@@ -0,0 +1,188 @@
+# Print an optspec for argparse to handle cmd's options that are independent of any subcommand.
+function __fish_appgen_global_optspecs
+    string join \n e/executable= n/name= i/icon= icon-name= v/app-version= build-number= b/bundle-id= o/output= a/additional-file= d/default-location= t/show-terminal= s/single-instance= template= category= background-only= run-in-terminal= document-type= sign manifest= helper= framework= login-item= helper-app= python-venv= node-modules= runtime= merge-plist= plist-format= privacy= privacy-text= dedup= strip strict watch relaunch entitlements= hardened-runtime allow-network-client allow-camera allow-microphone allow-apple-events disable-library-validation h/help V/version
+end
+
+function __fish_appgen_needs_command
+    # Figure out if the current invocation already has a command.
+    set -l cmd (commandline -opc)
+    set -e cmd[1]
+    argparse -s (__fish_appgen_global_optspecs) -- $cmd 2>/dev/null
+    or return
+    if set -q argv[1]
+        # Also print the command, so this can be used to figure out what it is.
+        echo $argv[1]
+        return 1
+    end
+    return 0
+end
+
+function __fish_appgen_using_subcommand
+    set -l cmd (__fish_appgen_needs_command)
+    test -z "$cmd"
+    and return 1
+    contains -- $cmd[1] $argv
+end
+
+complete -c appgen -n "__fish_appgen_needs_command" -s e -l executable -d 'Path to the executable file to package' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s n -l name -d 'Name of the app (without .app extension)' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s i -l icon -d 'Optional icon file path: .icns, or .svg, .ico, .png, a directory of PNG files or an .iconset folder, converted to .icns, or an .xcassets asset catalog compiled to Assets.car' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l icon-name -d 'Icon set of the asset catalog given to --icon (CFBundleIconName)' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s v -l app-version -d 'Optional app version (CFBundleShortVersionString) "from-git" uses the latest vX.Y.Z tag reachable from HEAD' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l build-number -d 'Build number (CFBundleVersion), defaults to the app version "auto" uses the build number of the CI system, or the commit count outside CI' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s b -l bundle-id -d 'Optional bundle identifier' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s o -l output -d 'Output directory (app will be created as {output}/{name}.app)' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s a -l additional-file -d 'Additional files or directories to include in the app bundle Format: source_path:target_location Example: --additional-file data.txt:Resources/data.txt Example: --additional-file config:Contents/Resources/config' -r
+complete -c appgen -n "__fish_appgen_needs_command" -s d -l default-location -d 'Override the default location for additional files This option will set the base directory inside the app bundle where additional files will be copied if no specific target is provided' -r -f -a "resources\t''
+mac-os\t''
+contents\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -s t -l show-terminal -d 'Show terminal window when the application runs By default, the terminal window is hidden' -r -f -a "true\t''
+false\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -s s -l single-instance -d 'Enable single instance mode to ensure only one instance of the app runs per user This adds code to prevent multiple instances of the application from running simultaneously' -r -f -a "true\t''
+false\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -l template -d 'Preset options for a common app shape, options given later override the template' -r -f -a "menubar-agent\t'Menu bar app without a Dock icon, one instance per user'
+background-daemon\t'Faceless background process without Dock icon or menu bar'
+cli-with-terminal\t'Command line program opened in a Terminal window'
+document-viewer\t'Regular Dock app opening documents, combine with --document-type'"
+complete -c appgen -n "__fish_appgen_needs_command" -l category -d 'App Store category (LSApplicationCategoryType) Example: --category public.app-category.utilities' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l background-only -d 'Run as a faceless background process (LSBackgroundOnly)' -r -f -a "true\t''
+false\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -l run-in-terminal -d 'Open the executable in a Terminal window when the app is launched' -r -f -a "true\t''
+false\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -l document-type -d 'File extension the app can open as a viewer (CFBundleDocumentTypes), can be repeated' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l manifest -d 'Read build options from a TOML manifest, options given on the command line override it Keys are the long option names, e.g. name = "My App" or additional-file = ["data.txt"]' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l helper -d 'Helper executable copied into Contents/Helpers' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l framework -d 'Framework copied into Contents/Frameworks with its symlinks, can be repeated Adds an @executable_path/../Frameworks runpath to the executable' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l login-item -d 'Manifest of a login item bundle built into Contents/Library/LoginItems' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l helper-app -d 'Manifest of a helper app bundle built into Contents/Helpers' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l python-venv -d 'Virtualenv vendored into Contents/Resources/venv and used to run a Python script executable' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l node-modules -d 'node_modules directory vendored into Contents/Resources/node_modules for a Node script executable' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l runtime -d 'Interpreter binary, or a directory with bin/<interpreter>, vendored into Contents/Resources/runtime and used instead of the one named by the script\'s shebang' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l merge-plist -d 'Plist whose keys are added to the generated Info.plist, replacing generated keys of the same name' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l plist-format -d 'Format Info.plist is written in' -r -f -a "xml\t''
+binary\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -l privacy -d 'Protected resources the app uses, comma separated: adds their NS*UsageDescription keys' -r -f -a "camera\t''
+microphone\t''
+apple-events\t''
+location\t''
+contacts\t''
+calendars\t''
+reminders\t''
+photos\t''
+bluetooth\t''
+speech-recognition\t''
+desktop-folder\t''
+documents-folder\t''
+downloads-folder\t''
+removable-volumes\t''
+network-volumes\t''
+local-network\t''
+system-administration\t''"
+complete -c appgen -n "__fish_appgen_needs_command" -l privacy-text -d 'Usage description of a permission instead of the default text, can be repeated' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l dedup -d 'Replace files with identical contents by links to a single copy' -r -f -a "hard-link\t'Hard links, transparent to the app but kept apart again by copies that do not preserve them'
+symlink\t'Relative symlinks, preserved by archives and disk images'"
+complete -c appgen -n "__fish_appgen_needs_command" -l entitlements -d 'Entitlements plist embedded in the signature of the main executable' -r
+complete -c appgen -n "__fish_appgen_needs_command" -l sign -d 'Ad-hoc sign the bundle after building it Writes Contents/_CodeSignature/CodeResources and embeds a signature into the executable'
+complete -c appgen -n "__fish_appgen_needs_command" -l strip -d 'Remove local symbols and debug sections from the Mach-O files in Contents/MacOS The unstripped images are archived by LC_UUID in {output}/{name}.symbols.zip'
+complete -c appgen -n "__fish_appgen_needs_command" -l strict -d 'Treat metadata warnings (such as the example bundle id) as errors'
+complete -c appgen -n "__fish_appgen_needs_command" -l watch -d 'Keep running and update the bundle whenever the executable, icon, additional files or manifest change'
+complete -c appgen -n "__fish_appgen_needs_command" -l relaunch -d 'Restart the app after every rebuild in watch mode'
+complete -c appgen -n "__fish_appgen_needs_command" -l hardened-runtime -d 'Enable the hardened runtime (required for notarization)'
+complete -c appgen -n "__fish_appgen_needs_command" -l allow-network-client -d 'Entitlement: allow outgoing network connections (com.apple.security.network.client)'
+complete -c appgen -n "__fish_appgen_needs_command" -l allow-camera -d 'Entitlement: allow camera access (com.apple.security.device.camera)'
+complete -c appgen -n "__fish_appgen_needs_command" -l allow-microphone -d 'Entitlement: allow microphone access (com.apple.security.device.audio-input)'
+complete -c appgen -n "__fish_appgen_needs_command" -l allow-apple-events -d 'Entitlement: allow sending Apple Events to other apps (com.apple.security.automation.apple-events)'
+complete -c appgen -n "__fish_appgen_needs_command" -l disable-library-validation -d 'Entitlement: load libraries signed by other teams (com.apple.security.cs.disable-library-validation)'
+complete -c appgen -n "__fish_appgen_needs_command" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c appgen -n "__fish_appgen_needs_command" -s V -l version -d 'Print version'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "dmg" -d 'Pack an existing .app bundle into a compressed DMG disk image'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "pkg" -d 'Build a flat .pkg installer from an existing .app bundle'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "cask" -d 'Generate a Homebrew cask (and optionally an install script) for a bundle'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "appcast" -d 'Generate or update a Sparkle appcast from a directory of release archives'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "sign" -d 'Ad-hoc sign an existing .app bundle and seal its resources'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "verify" -d 'Verify the ad-hoc signature and sealed resources of a bundle'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "import" -d 'Write a manifest rebuilding an existing .app bundle, copying its files next to it'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "diff" -d 'Compare two .app bundles: Info.plist keys, files, permissions and architectures'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "install" -d 'Copy a bundle into /Applications, keeping a backup of the copy it replaces'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "uninstall" -d 'Remove an installed app by bundle id, optionally with its data and LaunchAgents'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "size" -d 'Break the size of a bundle down by directory and file type and find duplicate files'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "completions" -d 'Print the completion script of a shell'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "man" -d 'Print or write the man page'
+complete -c appgen -n "__fish_appgen_needs_command" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -s o -l output -d 'Output path of the disk image (defaults to {app name}.dmg next to the bundle)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l volume-name -d 'Volume name shown in Finder (defaults to the app name)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l background -d 'Optional background image of the Finder window (PNG recommended)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l window -d 'Finder window bounds when the volume is opened Format: X,Y,WIDTH,HEIGHT' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l icon-size -d 'Size of the icons in the Finder window' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l app-position -d 'Position of the app icon in the Finder window Format: X,Y' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l applications-position -d 'Position of the /Applications symlink in the Finder window Format: X,Y' -r
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -l no-compress -d 'Store the image uncompressed (UDRO) instead of zlib compressed (UDZO)'
+complete -c appgen -n "__fish_appgen_using_subcommand dmg" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -s o -l output -d 'Output path of the package (defaults to {app name}.pkg next to the bundle)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -l identifier -d 'Package identifier (defaults to {bundle id}.pkg)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -l package-version -d 'Package version (defaults to CFBundleShortVersionString of the bundle)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -l install-location -d 'Directory the app is installed into' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -l launch-agent -d 'Optional LaunchAgent property list installed into /Library/LaunchAgents' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -l preinstall -d 'Optional script run before the installation' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -l postinstall -d 'Optional script run after the installation' -r
+complete -c appgen -n "__fish_appgen_using_subcommand pkg" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -s a -l archive -d 'Release archive (.zip, .tar.gz or .dmg) containing the bundle, used for the sha256' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -s u -l url -d 'Download URL of the archive Placeholders: {version}, {name}, {archive}, {bundle_id} Example: https://example.com/releases/v{version}/{archive}' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -s o -l output -d 'Output path of the cask (defaults to {token}.rb in the current directory)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -l token -d 'Cask token (defaults to the lowercased app name with dashes)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -l desc -d 'Optional one line description' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -l homepage -d 'Optional homepage URL' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -l zap -d 'Paths removed by `brew uninstall --zap`, {bundle_id} is replaced Example: --zap "~/Library/Application Support/{bundle_id}"' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -l install-script -d 'Also write a curl-pipe install script verifying the archive checksum to this path' -r
+complete -c appgen -n "__fish_appgen_using_subcommand cask" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -s d -l download-url-prefix -d 'URL prefix the archives are downloaded from, the archive file name is appended' -r
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -s o -l output -d 'Output appcast path, updated in place if it exists (defaults to {DIR}/appcast.xml)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -l ed-key-file -d 'Private ed25519 key file (base64, as exported by Sparkle\'s `generate_keys -x`)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -l minimum-system-version -d 'Minimum macOS version (defaults to LSMinimumSystemVersion of each bundle)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -l release-notes-url -d 'Release notes link, placeholders: {version}, {build} Example: https://example.com/release-notes/{version}.html' -r
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -l title -d 'Channel title (defaults to the app name)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand appcast" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l entitlements -d 'Entitlements plist embedded in the signature of the main executable' -r
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l hardened-runtime -d 'Enable the hardened runtime (required for notarization)'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l allow-network-client -d 'Entitlement: allow outgoing network connections (com.apple.security.network.client)'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l allow-camera -d 'Entitlement: allow camera access (com.apple.security.device.camera)'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l allow-microphone -d 'Entitlement: allow microphone access (com.apple.security.device.audio-input)'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l allow-apple-events -d 'Entitlement: allow sending Apple Events to other apps (com.apple.security.automation.apple-events)'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -l disable-library-validation -d 'Entitlement: load libraries signed by other teams (com.apple.security.cs.disable-library-validation)'
+complete -c appgen -n "__fish_appgen_using_subcommand sign" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand verify" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand import" -s o -l output -d 'Directory receiving the manifest and the bundle\'s files (defaults to the app name)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand import" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand diff" -l json -d 'Print the differences as JSON'
+complete -c appgen -n "__fish_appgen_using_subcommand diff" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand install" -l to -d 'Applications folder to install into' -r
+complete -c appgen -n "__fish_appgen_using_subcommand install" -l backup-dir -d 'Folder receiving the replaced copy (defaults to ~/Library/Application Support/appgen/Backups)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand install" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand uninstall" -l from -d 'Applications folder to search, can be repeated (defaults to /Applications and ~/Applications)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand uninstall" -l home -d 'Home folder whose Library is cleaned (defaults to $HOME)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand uninstall" -l data -d 'Also remove ~/Library/{Application Support,Caches,Logs,Preferences}/<bundle-id>'
+complete -c appgen -n "__fish_appgen_using_subcommand uninstall" -l launch-agents -d 'Also unload and remove the LaunchAgents of ~/Library/LaunchAgents starting the app'
+complete -c appgen -n "__fish_appgen_using_subcommand uninstall" -l dry-run -d 'List what would be removed without removing anything'
+complete -c appgen -n "__fish_appgen_using_subcommand uninstall" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand size" -l depth -d 'Depth of the directories in the breakdown, counted from the bundle (2 lists Contents/*)' -r
+complete -c appgen -n "__fish_appgen_using_subcommand size" -l top -d 'Rows shown per section' -r
+complete -c appgen -n "__fish_appgen_using_subcommand size" -l dedup -d 'Replace duplicates in the bundle (invalidates an existing signature)' -r -f -a "hard-link\t'Hard links, transparent to the app but kept apart again by copies that do not preserve them'
+symlink\t'Relative symlinks, preserved by archives and disk images'"
+complete -c appgen -n "__fish_appgen_using_subcommand size" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c appgen -n "__fish_appgen_using_subcommand completions" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand man" -s o -l output -d 'Directory receiving appgen.1 and one appgen-<subcommand>.1 page per subcommand, instead of printing appgen.1 to standard output' -r
+complete -c appgen -n "__fish_appgen_using_subcommand man" -s h -l help -d 'Print help'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "dmg" -d 'Pack an existing .app bundle into a compressed DMG disk image'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "pkg" -d 'Build a flat .pkg installer from an existing .app bundle'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "cask" -d 'Generate a Homebrew cask (and optionally an install script) for a bundle'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "appcast" -d 'Generate or update a Sparkle appcast from a directory of release archives'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "sign" -d 'Ad-hoc sign an existing .app bundle and seal its resources'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "verify" -d 'Verify the ad-hoc signature and sealed resources of a bundle'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "import" -d 'Write a manifest rebuilding an existing .app bundle, copying its files next to it'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "diff" -d 'Compare two .app bundles: Info.plist keys, files, permissions and architectures'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "install" -d 'Copy a bundle into /Applications, keeping a backup of the copy it replaces'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "uninstall" -d 'Remove an installed app by bundle id, optionally with its data and LaunchAgents'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "size" -d 'Break the size of a bundle down by directory and file type and find duplicate files'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "completions" -d 'Print the completion script of a shell'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "man" -d 'Print or write the man page'
+complete -c appgen -n "__fish_appgen_using_subcommand help; and not __fish_seen_subcommand_from dmg pkg cask appcast sign verify import diff install uninstall size completions man help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
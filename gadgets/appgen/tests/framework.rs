@@ -0,0 +1,176 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Lays out `{dir}/Kit.framework` the way macOS does, with `cputype` written into its binary
+fn make_framework(dir: &Path, cputype: u32) -> PathBuf {
+    let framework = dir.join("Kit.framework");
+    let version = framework.join("Versions/A");
+    fs::create_dir_all(version.join("Resources")).unwrap();
+    let mut binary = fs::read(input("hello-arm64")).unwrap();
+    binary[4..8].copy_from_slice(&cputype.to_le_bytes());
+    fs::write(version.join("Kit"), binary).unwrap();
+    let info = plist::Dictionary::from_iter([
+        ("CFBundleIdentifier".to_string(), plist::Value::from("com.acme.kit")),
+        ("CFBundleExecutable".to_string(), plist::Value::from("Kit")),
+        ("CFBundlePackageType".to_string(), plist::Value::from("FMWK")),
+    ]);
+    plist::to_file_xml(version.join("Resources/Info.plist"), &info).unwrap();
+    fs::write(version.join("Resources/strings.txt"), "kit\n").unwrap();
+    symlink("A", framework.join("Versions/Current")).unwrap();
+    symlink("Versions/Current/Kit", framework.join("Kit")).unwrap();
+    symlink("Versions/Current/Resources", framework.join("Resources")).unwrap();
+    framework
+}
+
+fn build_app(dir: &Path, framework: &Path, extra: &[&str]) -> Output {
+    let executable = input("hello-arm64");
+    let mut args = vec![
+        "--executable",
+        &executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        dir.to_str().unwrap(),
+        "--framework",
+        framework.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    run(&args)
+}
+
+/// `files2` of a CodeResources file
+fn sealed_files(path: &Path) -> plist::Dictionary {
+    let resources = plist::Value::from_file(path).unwrap().into_dictionary().unwrap();
+    resources["files2"].as_dictionary().unwrap().clone()
+}
+
+fn u32_le(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+/// Paths of the LC_RPATH commands of a thin 64-bit Mach-O
+fn rpaths(data: &[u8]) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut offset = 32;
+    for _ in 0..u32_le(data, 16) {
+        if u32_le(data, offset) == 0x8000_001c {
+            let size = u32_le(data, offset + 4);
+            let path = &data[offset + u32_le(data, offset + 8)..offset + size];
+            let end = path.iter().position(|&b| b == 0).unwrap();
+            paths.push(String::from_utf8(path[..end].to_vec()).unwrap());
+        }
+        offset += u32_le(data, offset + 4);
+    }
+    paths
+}
+
+#[test]
+fn framework_symlinks_are_kept_and_rpath_is_added() {
+    let dir = tempfile::tempdir().unwrap();
+    let framework = make_framework(dir.path(), 0x0100_000c);
+    let output = build_app(dir.path(), &framework, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let contents = dir.path().join("Hello.app/Contents");
+    let embedded = contents.join("Frameworks/Kit.framework");
+    for (link, target) in [
+        ("Versions/Current", "A"),
+        ("Kit", "Versions/Current/Kit"),
+        ("Resources", "Versions/Current/Resources"),
+    ] {
+        let path = embedded.join(link);
+        assert!(
+            fs::symlink_metadata(&path).unwrap().file_type().is_symlink(),
+            "{}",
+            link
+        );
+        assert_eq!(fs::read_link(&path).unwrap(), Path::new(target));
+    }
+    assert!(fs::symlink_metadata(embedded.join("Versions/A/Kit")).unwrap().is_file());
+
+    let executable = fs::read(contents.join("MacOS/hello-arm64")).unwrap();
+    assert_eq!(rpaths(&executable), ["@executable_path/../Frameworks"]);
+}
+
+#[test]
+fn framework_without_the_executable_architecture_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    // x86_64 only, while the executable is arm64
+    let framework = make_framework(dir.path(), 0x0100_0007);
+    let output = build_app(dir.path(), &framework, &[]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Kit.framework has no arm64 slice for the app executable (it contains x86_64)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn signed_framework_is_sealed_as_nested_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let framework = make_framework(dir.path(), 0x0100_000c);
+    let output = build_app(dir.path(), &framework, &["--sign"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let app = dir.path().join("Hello.app");
+    let verify = run(&["verify", app.to_str().unwrap()]);
+    assert!(verify.status.success(), "{}", String::from_utf8_lossy(&verify.stderr));
+
+    // The framework carries its own seal in the current version
+    let embedded = app.join("Contents/Frameworks/Kit.framework");
+    let framework_files = sealed_files(&embedded.join("Versions/A/_CodeSignature/CodeResources"));
+    let mut sealed: Vec<&str> = framework_files.keys().map(String::as_str).collect();
+    sealed.sort();
+    assert_eq!(sealed, ["Resources/Info.plist", "Resources/strings.txt"]);
+
+    // and the app seals the framework as a whole, by its cdhash
+    let app_files = sealed_files(&app.join("Contents/_CodeSignature/CodeResources"));
+    let entry = app_files["Frameworks/Kit.framework"].as_dictionary().unwrap();
+    assert!(entry.contains_key("cdhash"), "{:?}", entry);
+    assert!(
+        !app_files
+            .keys()
+            .any(|path| path.starts_with("Frameworks/Kit.framework/")),
+        "{:?}",
+        app_files.keys().collect::<Vec<_>>()
+    );
+
+    fs::write(embedded.join("Versions/A/Resources/strings.txt"), "changed\n").unwrap();
+    let verify = run(&["verify", app.to_str().unwrap()]);
+    let stderr = String::from_utf8(verify.stderr).unwrap();
+    assert!(!verify.status.success());
+    assert!(
+        stderr.contains("Frameworks/Kit.framework: Resources/strings.txt: file modified"),
+        "{}",
+        stderr
+    );
+}
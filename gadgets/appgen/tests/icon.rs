@@ -0,0 +1,228 @@
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// ICNS element types and their pixel sizes, in the order appgen writes them
+const ELEMENTS: &[(&str, u32)] = &[
+    ("icp4", 16),
+    ("ic11", 32),
+    ("icp5", 32),
+    ("ic12", 64),
+    ("ic07", 128),
+    ("ic13", 256),
+    ("ic08", 256),
+    ("ic14", 512),
+    ("ic09", 512),
+    ("ic10", 1024),
+];
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+fn fixture(name: &str) -> String {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "icons", name]
+        .iter()
+        .collect();
+    path.to_str().unwrap().to_string()
+}
+
+fn run(dir: &Path, icon: &str, extra: &[&str]) -> Output {
+    let executable = dir.join("demo");
+    fs::write(&executable, "#!/bin/sh\necho demo\n").unwrap();
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args([
+            "--executable",
+            executable.to_str().unwrap(),
+            "--name",
+            "Demo",
+            "--bundle-id",
+            "com.acme.demo",
+            "--output",
+            dir.to_str().unwrap(),
+            "--icon",
+            icon,
+        ])
+        .args(extra)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Builds Demo.app with `--icon {fixture}`, returning the output and the images of the .icns by type
+fn convert(icon: &str, icns_name: &str) -> (String, Vec<(String, RgbaImage)>) {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run(dir.path(), &fixture(icon), &[]);
+    assert!(
+        output.status.success(),
+        "converting {} failed: {}",
+        icon,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let app = dir.path().join("Demo.app/Contents");
+    let info = plist::Value::from_file(app.join("Info.plist")).unwrap();
+    let icon_file = info.as_dictionary().unwrap()["CFBundleIconFile"].as_string().unwrap();
+    assert_eq!(icon_file, icns_name);
+    let icns = fs::read(app.join("Resources").join(icns_name)).unwrap();
+    (String::from_utf8(output.stdout).unwrap(), read_icns(&icns))
+}
+
+fn read_icns(data: &[u8]) -> Vec<(String, RgbaImage)> {
+    assert_eq!(&data[..4], b"icns");
+    assert_eq!(u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize, data.len());
+    let mut elements = Vec::new();
+    let mut offset = 8;
+    while offset < data.len() {
+        let ostype = String::from_utf8(data[offset..offset + 4].to_vec()).unwrap();
+        let length = u32::from_be_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let png = &data[offset + 8..offset + length];
+        let image = image::load_from_memory_with_format(png, ImageFormat::Png).unwrap();
+        elements.push((ostype, image.into_rgba8()));
+        offset += length;
+    }
+    let layout: Vec<(&str, u32)> = elements
+        .iter()
+        .map(|(ostype, image)| {
+            assert_eq!(image.width(), image.height(), "{} is not square", ostype);
+            (ostype.as_str(), image.width())
+        })
+        .collect();
+    assert_eq!(layout, ELEMENTS);
+    elements
+}
+
+fn u32_be(data: &[u8], offset: usize) -> usize {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+/// Block of a named variable of a BOM store
+fn bom_var<'a>(data: &'a [u8], name: &str) -> &'a [u8] {
+    assert_eq!(&data[..8], b"BOMStore");
+    let (index_offset, vars_offset) = (u32_be(data, 16), u32_be(data, 24));
+    let mut offset = vars_offset + 4;
+    for _ in 0..u32_be(data, vars_offset) {
+        let (block, length) = (u32_be(data, offset), data[offset + 4] as usize);
+        let var = &data[offset + 5..offset + 5 + length];
+        offset += 5 + length;
+        if var == name.as_bytes() {
+            let pointer = index_offset + 4 + 8 * block;
+            let (address, size) = (u32_be(data, pointer), u32_be(data, pointer + 4));
+            return &data[address..address + size];
+        }
+    }
+    panic!("no {} in the BOM store", name);
+}
+
+fn center(elements: &[(String, RgbaImage)], ostype: &str) -> Rgba<u8> {
+    let (_, image) = elements.iter().find(|(t, _)| t == ostype).unwrap();
+    *image.get_pixel(image.width() / 2, image.height() / 2)
+}
+
+#[test]
+fn svg_is_rendered_at_every_size() {
+    let (stdout, elements) = convert("icon.svg", "icon.icns");
+    assert!(!stdout.contains("Warning"), "{}", stdout);
+    for (ostype, image) in &elements {
+        assert_eq!(center(&elements, ostype), RED);
+        // The 2:1 drawing is centered, leaving the top and bottom transparent
+        assert_eq!(image.get_pixel(0, 0)[3], 0, "{}", ostype);
+    }
+}
+
+#[test]
+fn ico_images_are_scaled_from_the_closest_size() {
+    let (stdout, elements) = convert("icon.ico", "icon.icns");
+    assert_eq!(center(&elements, "icp4"), RED, "16x16 is taken from the 16x16 image");
+    assert_eq!(
+        center(&elements, "ic11"),
+        GREEN,
+        "32x32 is scaled down from the 48x48 image"
+    );
+    assert_eq!(center(&elements, "ic10"), GREEN);
+    assert!(
+        stdout.contains("Warning: upscaling the 48x48 icon image to 64x64, 128x128, 256x256, 512x512, 1024x1024"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn iconset_is_converted() {
+    let (stdout, elements) = convert("icon.iconset", "icon.icns");
+    assert!(!stdout.contains("Warning"), "{}", stdout);
+    assert_eq!(center(&elements, "icp4"), RED);
+    assert_eq!(center(&elements, "ic11"), BLUE);
+    assert_eq!(center(&elements, "ic10"), BLUE);
+}
+
+#[test]
+fn png_directory_is_converted() {
+    let (stdout, elements) = convert("pngs", "pngs.icns");
+    assert!(!stdout.contains("Warning"), "{}", stdout);
+    assert_eq!(center(&elements, "icp4"), RED);
+    assert_eq!(center(&elements, "ic12"), RED);
+    assert_eq!(center(&elements, "ic07"), GREEN);
+}
+
+#[test]
+fn small_png_is_upscaled_with_a_warning() {
+    let (stdout, elements) = convert("small.png", "small.icns");
+    assert_eq!(center(&elements, "ic10"), BLUE);
+    assert!(
+        stdout.contains("Warning: upscaling the 32x32 icon image to 64x64, 128x128, 256x256, 512x512, 1024x1024"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn icon_path_without_a_name_fails() {
+    for icon in [".", ".."] {
+        let dir = tempfile::tempdir().unwrap();
+        let output = run(dir.path(), icon, &[]);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!output.status.success());
+        assert!(stderr.contains("Invalid icon path"), "{}", stderr);
+    }
+}
+
+#[test]
+fn asset_catalog_is_compiled_with_an_icns_fallback() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run(dir.path(), &fixture("Assets.xcassets"), &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let contents = dir.path().join("Demo.app/Contents");
+    let info = plist::Value::from_file(contents.join("Info.plist")).unwrap();
+    let info = info.as_dictionary().unwrap();
+    assert_eq!(info["CFBundleIconName"].as_string(), Some("AppIcon"));
+    assert_eq!(info["CFBundleIconFile"].as_string(), Some("AppIcon.icns"));
+
+    // Two mac images and the multi-size rendition, the ios image is left out
+    let car = fs::read(contents.join("Resources/Assets.car")).unwrap();
+    let header = bom_var(&car, "CARHEADER");
+    assert_eq!(&header[..4], b"RATC");
+    assert_eq!(u32::from_le_bytes(header[16..20].try_into().unwrap()), 3);
+    bom_var(&car, "RENDITIONS");
+    bom_var(&car, "FACETKEYS");
+
+    let elements = read_icns(&fs::read(contents.join("Resources/AppIcon.icns")).unwrap());
+    assert_eq!(center(&elements, "icp4"), RED);
+    assert_eq!(center(&elements, "ic10"), BLUE);
+}
+
+#[test]
+fn asset_catalog_without_the_named_icon_set_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let catalog = fixture("Assets.xcassets");
+    let output = run(dir.path(), &catalog, &["--icon-name", "Missing"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains(&format!(
+            "Icon set Missing not found in {} (choose another one with --icon-name)",
+            catalog
+        )),
+        "{}",
+        stderr
+    );
+}
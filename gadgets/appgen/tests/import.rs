@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn appgen(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Files under `dir` relative to it, with their contents, in sorted order, leaving out Info.plist
+fn files(dir: &Path) -> Vec<(String, Vec<u8>)> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<(String, Vec<u8>)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(root, &path, files);
+            } else if !path.ends_with("Contents/Info.plist") {
+                let relative = path.strip_prefix(root).unwrap().to_string_lossy().to_string();
+                files.push((relative, fs::read(&path).unwrap()));
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files);
+    files.sort();
+    files
+}
+
+#[test]
+fn imported_bundle_rebuilds_identically() {
+    let dir = tempfile::tempdir().unwrap();
+    let original = dir.path().join("original");
+    let executable = input("hello.sh");
+    let data = format!("{}:Resources/data.txt", input("data.txt"));
+    let config = format!("{}:Resources/config", input("config"));
+    let tool = format!("{}:MacOS/tool.sh", input("tool.sh"));
+    let icon = input("icon.png");
+    appgen(&[
+        "--executable",
+        &executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        original.to_str().unwrap(),
+        "--icon",
+        &icon,
+        "--app-version",
+        "2.1.0",
+        "--build-number",
+        "7",
+        "--category",
+        "public.app-category.developer-tools",
+        "--privacy",
+        "camera",
+        "--plist-format",
+        "binary",
+        "--additional-file",
+        &data,
+        "--additional-file",
+        &config,
+        "--additional-file",
+        &tool,
+    ]);
+
+    let imported = dir.path().join("imported");
+    let original_app = original.join("Hello.app");
+    let stdout = appgen(&[
+        "import",
+        original_app.to_str().unwrap(),
+        "--output",
+        imported.to_str().unwrap(),
+    ]);
+    assert!(stdout.contains("Kept 1 Info.plist key(s)"), "{}", stdout);
+    let manifest = imported.join("appgen.toml");
+
+    let rebuilt = dir.path().join("rebuilt");
+    appgen(&[
+        "--manifest",
+        manifest.to_str().unwrap(),
+        "--output",
+        rebuilt.to_str().unwrap(),
+    ]);
+    let rebuilt_app = rebuilt.join("Hello.app");
+
+    let info = |app: &Path| plist::Value::from_file(app.join("Contents/Info.plist")).unwrap();
+    assert_eq!(info(&rebuilt_app), info(&original_app));
+    let original_plist = fs::read(original_app.join("Contents/Info.plist")).unwrap();
+    let rebuilt_plist = fs::read(rebuilt_app.join("Contents/Info.plist")).unwrap();
+    // Keys kept in Info.merge.plist come last, so only the values and the format match
+    assert!(original_plist.starts_with(b"bplist"));
+    assert!(rebuilt_plist.starts_with(b"bplist"));
+    let rebuilt_files = files(&rebuilt_app);
+    let paths: Vec<&str> = rebuilt_files.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "Contents/MacOS/hello.sh",
+            "Contents/MacOS/tool.sh",
+            "Contents/PkgInfo",
+            "Contents/Resources/config/settings.json",
+            "Contents/Resources/config/theme.conf",
+            "Contents/Resources/data.txt",
+            "Contents/Resources/icon.icns",
+        ]
+    );
+    assert_eq!(rebuilt_files, files(&original_app));
+}
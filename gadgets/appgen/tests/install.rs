@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+fn appgen(args: &[&str]) -> String {
+    let output = run(args);
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Builds `{dir}/build/Demo.app` printing `message`
+fn build_demo_app(dir: &Path, message: &str) -> PathBuf {
+    let executable = dir.join("demo");
+    fs::write(&executable, format!("#!/bin/sh\necho {}\n", message)).unwrap();
+    let output = dir.join("build");
+    appgen(&[
+        "--executable",
+        executable.to_str().unwrap(),
+        "--name",
+        "Demo",
+        "--bundle-id",
+        "com.example.demo",
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    output.join("Demo.app")
+}
+
+fn installed_script(applications: &Path) -> String {
+    fs::read_to_string(applications.join("Demo.app/Contents/MacOS/demo")).unwrap()
+}
+
+#[test]
+fn install_keeps_a_backup_of_the_replaced_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let applications = dir.path().join("Applications");
+    let backups = dir.path().join("Backups");
+    let install = |app: &Path| {
+        appgen(&[
+            "install",
+            app.to_str().unwrap(),
+            "--to",
+            applications.to_str().unwrap(),
+            "--backup-dir",
+            backups.to_str().unwrap(),
+        ])
+    };
+
+    install(&build_demo_app(dir.path(), "first"));
+    assert!(installed_script(&applications).contains("first"));
+    assert!(!backups.exists());
+
+    install(&build_demo_app(dir.path(), "second"));
+    assert!(installed_script(&applications).contains("second"));
+    let backed_up: Vec<PathBuf> = fs::read_dir(backups.join("com.example.demo"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(backed_up.len(), 1);
+    let backup_script = fs::read_to_string(backed_up[0].join("Contents/MacOS/demo")).unwrap();
+    assert!(backup_script.contains("first"));
+    // Only the installed copy is left in the applications folder
+    assert_eq!(fs::read_dir(&applications).unwrap().count(), 1);
+}
+
+#[test]
+fn uninstall_removes_the_app_data_and_launch_agents() {
+    let dir = tempfile::tempdir().unwrap();
+    let applications = dir.path().join("Applications");
+    let home = dir.path().join("home");
+    let library = home.join("Library");
+    appgen(&[
+        "install",
+        build_demo_app(dir.path(), "demo").to_str().unwrap(),
+        "--to",
+        applications.to_str().unwrap(),
+    ]);
+    let data = [
+        library.join("Application Support/com.example.demo/state.json"),
+        library.join("Caches/com.example.demo/cache.db"),
+        library.join("Logs/com.example.demo/demo.log"),
+        library.join("Preferences/com.example.demo.plist"),
+    ];
+    let unrelated = library.join("Caches/com.example.other/cache.db");
+    for path in data.iter().chain([&unrelated]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "data").unwrap();
+    }
+    let agents = library.join("LaunchAgents");
+    fs::create_dir_all(&agents).unwrap();
+    let agent = |label: &str, program: &str| {
+        plist::Value::Dictionary(plist::Dictionary::from_iter([
+            ("Label", plist::Value::String(label.to_string())),
+            ("Program", plist::Value::String(program.to_string())),
+        ]))
+    };
+    let helper = applications.join("Demo.app/Contents/MacOS/demo");
+    agent("com.example.demo.helper", "/usr/bin/true")
+        .to_file_xml(agents.join("com.example.demo.helper.plist"))
+        .unwrap();
+    agent("org.other.starter", helper.to_str().unwrap())
+        .to_file_xml(agents.join("org.other.starter.plist"))
+        .unwrap();
+    agent("com.example.other", "/usr/bin/true")
+        .to_file_xml(agents.join("com.example.other.plist"))
+        .unwrap();
+
+    let uninstall = |dry_run: bool| {
+        let mut args = vec![
+            "uninstall",
+            "com.example.demo",
+            "--from",
+            applications.to_str().unwrap(),
+            "--home",
+            home.to_str().unwrap(),
+            "--data",
+            "--launch-agents",
+        ];
+        if dry_run {
+            args.push("--dry-run");
+        }
+        appgen(&args)
+    };
+
+    let listing = uninstall(true);
+    assert!(listing.contains("Would remove app"), "{}", listing);
+    assert!(listing.contains("com.example.demo.helper.plist"), "{}", listing);
+    assert!(listing.contains("org.other.starter.plist"), "{}", listing);
+    assert!(!listing.contains("com.example.other"), "{}", listing);
+    assert!(applications.join("Demo.app").exists());
+    assert!(data.iter().all(|path| path.exists()));
+
+    uninstall(false);
+    assert!(!applications.join("Demo.app").exists());
+    assert!(!library.join("Application Support/com.example.demo").exists());
+    assert!(!library.join("Caches/com.example.demo").exists());
+    assert!(!library.join("Logs/com.example.demo").exists());
+    assert!(!data[3].exists());
+    assert!(!agents.join("com.example.demo.helper.plist").exists());
+    assert!(!agents.join("org.other.starter.plist").exists());
+    assert!(agents.join("com.example.other.plist").exists());
+    assert!(unrelated.exists());
+}
+
+#[test]
+fn uninstall_fails_for_unknown_bundle_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run(&[
+        "uninstall",
+        "com.example.missing",
+        "--from",
+        dir.path().to_str().unwrap(),
+        "--home",
+        dir.path().to_str().unwrap(),
+        "--data",
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No app with bundle id com.example.missing"));
+}
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/{name}.app` from hello.sh
+fn build(dir: &Path, name: &str, bundle_id: &str, extra: &[&str]) -> Output {
+    let executable = input("hello.sh");
+    let mut args = vec![
+        "--executable",
+        &executable,
+        "--name",
+        name,
+        "--bundle-id",
+        bundle_id,
+        "--output",
+        dir.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    run(&args)
+}
+
+#[test]
+fn clean_metadata_passes_strict() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(
+        dir.path(),
+        "Hello",
+        "com.acme.hello",
+        &["--app-version", "1.2.3", "--strict"],
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(!stdout.contains("Warning"), "{}", stdout);
+    assert!(dir.path().join("Hello.app/Contents/Info.plist").exists());
+}
+
+#[test]
+fn questionable_metadata_warns() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), "Hello.app", "com.example.hello", &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(
+        stdout.contains("Warning: App name \"Hello.app\" should not include the .app extension"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Warning: Bundle id \"com.example.hello\" is the example id, set your own with --bundle-id"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn strict_turns_warnings_into_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), "Hello", "com.example.hello", &["--strict"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("Invalid metadata for Hello:"), "{}", stderr);
+    assert!(
+        stderr.contains("Bundle id \"com.example.hello\" is the example id, set your own with --bundle-id"),
+        "{}",
+        stderr
+    );
+    // Nothing is written when the metadata is rejected
+    assert!(!dir.path().join("Hello.app").exists());
+}
+
+#[test]
+fn invalid_metadata_fails_without_strict() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), "Hello", "com.acme.hello", &["--app-version", "1.2.beta"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("CFBundleVersion must be 1 to 3 dot separated integers such as 1.2.3, got \"1.2.beta\""),
+        "{}",
+        stderr
+    );
+    assert!(!dir.path().join("Hello.app").exists());
+}
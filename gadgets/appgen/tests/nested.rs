@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/out/Menu.app` (com.acme.menu) with a login item, whose table gets `login_item` appended
+fn build(dir: &Path, login_item: &str) -> Output {
+    let manifest = dir.join("appgen.toml");
+    let text = format!(
+        "executable = {:?}\nname = \"Menu\"\nbundle-id = \"com.acme.menu\"\n\n[[login-item]]\nexecutable = {:?}\nname = \"Agent\"\n{}",
+        input("hello.sh"),
+        input("tool.sh"),
+        login_item
+    );
+    fs::write(&manifest, text).unwrap();
+    let output_dir = dir.join("out");
+    run(&[
+        "--manifest",
+        manifest.to_str().unwrap(),
+        "--output",
+        output_dir.to_str().unwrap(),
+    ])
+}
+
+fn bundle_id(app: &Path) -> String {
+    let info = plist::Value::from_file(app.join("Contents/Info.plist")).unwrap();
+    let info = info.as_dictionary().unwrap();
+    info["CFBundleIdentifier"].as_string().unwrap().to_string()
+}
+
+#[test]
+fn login_item_id_defaults_to_a_child_of_the_parent() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let app = dir.path().join("out/Menu.app");
+    assert_eq!(
+        bundle_id(&app.join("Contents/Library/LoginItems/Agent.app")),
+        "com.acme.menu.agent"
+    );
+}
+
+#[test]
+fn login_item_id_outside_the_parent_is_rejected() {
+    // A sibling sharing the parent id as a string prefix is not a child either
+    for id in ["com.other.agent", "com.acme.menubar", "com.acme.menu"] {
+        let dir = tempfile::tempdir().unwrap();
+        let output = build(dir.path(), &format!("bundle-id = {:?}\n", id));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!output.status.success(), "{}", id);
+        assert!(
+            stderr.contains(&format!(
+                "Nested bundle id {} of Agent must be a child of com.acme.menu (for example com.acme.menu.helper)",
+                id
+            )),
+            "{}",
+            stderr
+        );
+        assert!(!dir.path().join("out/Menu.app").exists(), "{}", id);
+    }
+}
+
+#[test]
+fn invalid_login_item_id_is_rejected_before_writing() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), "bundle-id = \"com.acme.menu.bad_id\"\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("Invalid nested bundle Agent"), "{}", stderr);
+    assert!(
+        stderr
+            .contains("Bundle id \"com.acme.menu.bad_id\" contains '_', only letters, digits, '-' and '.' are allowed"),
+        "{}",
+        stderr
+    );
+    assert!(!dir.path().join("out/Menu.app").exists());
+}
@@ -0,0 +1,265 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+fn appgen(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Parses a xar archive into a map of `path -> contents`
+fn read_xar(data: &[u8]) -> HashMap<String, Vec<u8>> {
+    assert_eq!(&data[..4], b"xar!");
+    let header_size = u16::from_be_bytes([data[4], data[5]]) as usize;
+    assert_eq!(header_size, 28);
+    assert_eq!(u16::from_be_bytes([data[6], data[7]]), 1, "xar version");
+    let toc_length = u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize;
+    let toc_uncompressed = u64::from_be_bytes(data[16..24].try_into().unwrap()) as usize;
+    assert_eq!(u32_at(data, 24), 1, "the heap checksum is a SHA-1");
+    let compressed_toc = &data[header_size..header_size + toc_length];
+    let heap = &data[header_size + toc_length..];
+    assert_eq!(&heap[..20], Sha1::digest(compressed_toc).as_slice());
+
+    let mut toc = String::new();
+    ZlibDecoder::new(compressed_toc).read_to_string(&mut toc).unwrap();
+    assert_eq!(toc.len(), toc_uncompressed);
+    assert!(
+        toc.contains("<checksum style=\"sha1\">\n   <offset>0</offset>\n   <size>20</size>"),
+        "the TOC does not point at the heap checksum:\n{}",
+        toc
+    );
+
+    // Minimal tag walker, enough for the TOC appgen writes
+    let mut files = HashMap::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut name = String::new();
+    let (mut offset, mut length) = (0, 0);
+    let mut checksum = String::new();
+    for piece in toc.split('<').skip(1) {
+        let (tag, text) = piece.split_once('>').unwrap();
+        let tag = tag.split_whitespace().next().unwrap_or_default();
+        match tag {
+            "file" => stack.push(String::new()),
+            "name" => {
+                name = text.to_string();
+                *stack.last_mut().unwrap() = name.clone();
+            }
+            "offset" if !stack.is_empty() => offset = text.parse::<usize>().unwrap(),
+            "length" => length = text.parse::<usize>().unwrap(),
+            "extracted-checksum" => checksum = text.to_string(),
+            "/data" => {
+                let data = &heap[offset..offset + length];
+                assert_eq!(checksum, hex(&Sha1::digest(data)), "checksum of {}", stack.join("/"));
+                files.insert(stack.join("/"), data.to_vec());
+            }
+            "/file" => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    assert!(!name.is_empty());
+    files
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Walks the `Paths` tree of a bill of materials into a map of `path -> (type, size)`
+fn bom_paths(data: &[u8]) -> HashMap<String, (u8, u32)> {
+    assert_eq!(&data[..8], b"BOMStore");
+    assert_eq!(u32_at(data, 8), 1, "BOM version");
+    let index = u32_at(data, 16) as usize;
+    let block_count = u32_at(data, 12) as usize;
+    assert_eq!(u32_at(data, index) as usize, block_count);
+    let block = |i: u32| {
+        let at = index + 4 + 8 * i as usize;
+        let (address, length) = (u32_at(data, at) as usize, u32_at(data, at + 4) as usize);
+        &data[address..address + length]
+    };
+
+    let vars = u32_at(data, 24) as usize;
+    let mut pos = vars + 4;
+    let mut paths_block = None;
+    for _ in 0..u32_at(data, vars) {
+        let name_length = data[pos + 4] as usize;
+        let name = &data[pos + 5..pos + 5 + name_length];
+        if name == b"Paths" {
+            paths_block = Some(u32_at(data, pos));
+        }
+        pos += 5 + name_length;
+    }
+    let tree = block(paths_block.expect("no Paths variable"));
+    assert_eq!(&tree[..4], b"tree");
+    let path_count = u32_at(tree, 16) as usize;
+
+    // Descend to the first leaf, then follow the forward links
+    let mut node = block(u32_at(tree, 8));
+    while u16::from_be_bytes([node[0], node[1]]) == 0 {
+        node = block(u32_at(node, 12));
+    }
+    let mut names: HashMap<u32, (u32, String)> = HashMap::new();
+    let mut infos = Vec::new();
+    loop {
+        let count = u16::from_be_bytes([node[2], node[3]]) as usize;
+        for i in 0..count {
+            let info = block(u32_at(node, 12 + 8 * i));
+            let file = block(u32_at(node, 16 + 8 * i));
+            let id = u32_at(info, 0);
+            let name = std::str::from_utf8(&file[4..file.len() - 1]).unwrap();
+            names.insert(id, (u32_at(file, 0), name.to_string()));
+            let info2 = block(u32_at(info, 4));
+            infos.push((id, info2[0], u32_at(info2, 18)));
+        }
+        match u32_at(node, 4) {
+            0 => break,
+            forward => node = block(forward),
+        }
+    }
+    assert_eq!(infos.len(), path_count);
+
+    let full_path = |mut id: u32| {
+        let mut parts = Vec::new();
+        while id != 0 {
+            let (parent, name) = &names[&id];
+            parts.push(name.clone());
+            id = *parent;
+        }
+        parts.reverse();
+        parts.join("/")
+    };
+    infos
+        .into_iter()
+        .map(|(id, kind, size)| (full_path(id), (kind, size)))
+        .collect()
+}
+
+/// Lists the paths of an odc cpio archive
+fn cpio_paths(data: &[u8]) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut pos = 0;
+    loop {
+        let header = std::str::from_utf8(&data[pos..pos + 76]).unwrap();
+        assert_eq!(&header[..6], "070707");
+        let name_size = usize::from_str_radix(&header[59..65], 8).unwrap();
+        let file_size = usize::from_str_radix(&header[65..76], 8).unwrap();
+        let name = std::str::from_utf8(&data[pos + 76..pos + 76 + name_size - 1]).unwrap();
+        if name == "TRAILER!!!" {
+            return paths;
+        }
+        paths.push(name.to_string());
+        pos += 76 + name_size + file_size;
+    }
+}
+
+fn gunzip(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut out).unwrap();
+    out
+}
+
+fn build_demo_app(dir: &Path) {
+    let executable = dir.join("demo");
+    fs::write(&executable, "#!/bin/sh\necho demo\n").unwrap();
+    appgen(&[
+        "--executable",
+        executable.to_str().unwrap(),
+        "--name",
+        "Demo",
+        "--bundle-id",
+        "com.example.demo",
+        "--app-version",
+        "2.0.1",
+        "--output",
+        dir.to_str().unwrap(),
+    ]);
+}
+
+#[test]
+fn pkg_contains_distribution_and_component() {
+    let dir = tempfile::tempdir().unwrap();
+    build_demo_app(dir.path());
+    let postinstall = dir.path().join("postinstall.sh");
+    fs::write(&postinstall, "#!/bin/sh\nexit 0\n").unwrap();
+    let agent = dir.path().join("com.example.demo.agent.plist");
+    fs::write(&agent, "<plist/>").unwrap();
+    let pkg_path = dir.path().join("Demo.pkg");
+
+    appgen(&[
+        "pkg",
+        dir.path().join("Demo.app").to_str().unwrap(),
+        "--output",
+        pkg_path.to_str().unwrap(),
+        "--postinstall",
+        postinstall.to_str().unwrap(),
+        "--launch-agent",
+        agent.to_str().unwrap(),
+    ]);
+
+    let files = read_xar(&fs::read(&pkg_path).unwrap());
+    let distribution = String::from_utf8(files["Distribution"].clone()).unwrap();
+    assert!(distribution.contains("<pkg-ref id=\"com.example.demo.pkg\" version=\"2.0.1\""));
+    assert!(distribution.contains("#Demo.pkg</pkg-ref>"));
+
+    let package_info = String::from_utf8(files["Demo.pkg/PackageInfo"].clone()).unwrap();
+    assert!(package_info.contains("identifier=\"com.example.demo.pkg\""));
+    assert!(package_info.contains("<postinstall file=\"./postinstall\"/>"));
+    assert!(!package_info.contains("preinstall"));
+
+    let payload = cpio_paths(&gunzip(&files["Demo.pkg/Payload"]));
+    for path in [
+        ".",
+        "./Applications",
+        "./Applications/Demo.app/Contents/Info.plist",
+        "./Applications/Demo.app/Contents/MacOS/demo",
+        "./Library/LaunchAgents/com.example.demo.agent.plist",
+    ] {
+        assert!(payload.contains(&path.to_string()), "missing {} in {:?}", path, payload);
+    }
+
+    // The bill of materials lists exactly the payload entries
+    let bom = bom_paths(&files["Demo.pkg/Bom"]);
+    let mut bom_list: Vec<&String> = bom.keys().collect();
+    bom_list.sort();
+    let mut payload_list: Vec<&String> = payload.iter().collect();
+    payload_list.sort();
+    assert_eq!(bom_list, payload_list);
+    assert_eq!(bom["./Applications"], (2, 0));
+    let executable = fs::read(dir.path().join("Demo.app/Contents/MacOS/demo")).unwrap();
+    assert_eq!(
+        bom["./Applications/Demo.app/Contents/MacOS/demo"],
+        (1, executable.len() as u32)
+    );
+
+    let scripts = cpio_paths(&gunzip(&files["Demo.pkg/Scripts"]));
+    assert_eq!(scripts, vec![".", "./postinstall"]);
+}
+
+#[test]
+fn pkg_without_scripts_has_no_scripts_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    build_demo_app(dir.path());
+
+    appgen(&["pkg", dir.path().join("Demo.app").to_str().unwrap()]);
+
+    let files = read_xar(&fs::read(dir.path().join("Demo.pkg")).unwrap());
+    assert!(files.contains_key("Demo.pkg/Payload"));
+    assert!(!files.contains_key("Demo.pkg/Scripts"));
+}
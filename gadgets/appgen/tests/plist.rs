@@ -0,0 +1,112 @@
+use plist::{Dictionary, Value};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn appgen(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Builds `{dir}/{format}/Demo.app` with Info.plist in `format`
+fn build_demo_app(dir: &Path, format: &str) -> std::path::PathBuf {
+    let executable = dir.join("demo");
+    fs::write(&executable, "#!/bin/sh\necho demo\n").unwrap();
+    let output = dir.join(format);
+    appgen(&[
+        "--executable",
+        executable.to_str().unwrap(),
+        "--name",
+        "Demo",
+        "--bundle-id",
+        "com.example.demo",
+        "--app-version",
+        "2.0.1",
+        "--category",
+        "public.app-category.utilities",
+        "--document-type",
+        "txt",
+        "--plist-format",
+        format,
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    output.join("Demo.app")
+}
+
+fn expected_info() -> Dictionary {
+    let string = |s: &str| Value::String(s.to_string());
+    Dictionary::from_iter([
+        ("CFBundleName", string("Demo")),
+        ("CFBundleDisplayName", string("Demo")),
+        ("CFBundleIdentifier", string("com.example.demo")),
+        ("CFBundleVersion", string("2.0.1")),
+        ("CFBundleShortVersionString", string("2.0.1")),
+        ("CFBundleExecutable", string("demo")),
+        ("CFBundleIconFile", string("")),
+        ("CFBundlePackageType", string("APPL")),
+        ("CFBundleSignature", string("????")),
+        ("LSMinimumSystemVersion", string("10.10.0")),
+        ("LSUIElement", Value::Boolean(true)),
+        ("NSHighResolutionCapable", Value::Boolean(true)),
+        ("LSApplicationCategoryType", string("public.app-category.utilities")),
+        (
+            "CFBundleDocumentTypes",
+            Value::Array(vec![Value::Dictionary(Dictionary::from_iter([
+                ("CFBundleTypeName", string("TXT document")),
+                ("CFBundleTypeExtensions", Value::Array(vec![string("txt")])),
+                ("CFBundleTypeRole", string("Viewer")),
+                ("LSHandlerRank", string("Alternate")),
+            ]))]),
+        ),
+    ])
+}
+
+fn read_info(app: &Path) -> Dictionary {
+    Value::from_file(app.join("Contents/Info.plist"))
+        .unwrap()
+        .into_dictionary()
+        .unwrap()
+}
+
+#[test]
+fn xml_and_binary_plists_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let xml_app = build_demo_app(dir.path(), "xml");
+    let binary_app = build_demo_app(dir.path(), "binary");
+
+    let xml_data = fs::read(xml_app.join("Contents/Info.plist")).unwrap();
+    assert!(xml_data.starts_with(b"<?xml"));
+    let binary_data = fs::read(binary_app.join("Contents/Info.plist")).unwrap();
+    assert!(binary_data.starts_with(b"bplist00"));
+
+    assert_eq!(read_info(&xml_app), expected_info());
+    assert_eq!(read_info(&binary_app), expected_info());
+}
+
+#[test]
+fn pkg_info_matches_info_plist() {
+    let dir = tempfile::tempdir().unwrap();
+    for format in ["xml", "binary"] {
+        let app = build_demo_app(dir.path(), format);
+        let info = read_info(&app);
+        let pkg_info = fs::read_to_string(app.join("Contents/PkgInfo")).unwrap();
+        assert_eq!(pkg_info, "APPL????");
+        assert_eq!(
+            pkg_info,
+            format!(
+                "{}{}",
+                info["CFBundlePackageType"].as_string().unwrap(),
+                info["CFBundleSignature"].as_string().unwrap()
+            )
+        );
+    }
+}
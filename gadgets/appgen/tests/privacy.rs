@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/Hello.app` around `executable`
+fn build(dir: &Path, executable: &str, extra: &[&str]) -> Output {
+    let mut args = vec![
+        "--executable",
+        executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        dir.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    run(&args)
+}
+
+/// Writes a script showing a notification through `osascript`
+fn apple_events_script(dir: &Path) -> String {
+    let script = dir.join("notify.sh");
+    fs::write(&script, "#!/bin/sh\nosascript -e 'display notification \"done\"'\n").unwrap();
+    script.to_str().unwrap().to_string()
+}
+
+const APPLE_EVENTS_FINDING: &str = "Contents/MacOS/notify.sh uses `osascript`, which needs \
+                                    NSAppleEventsUsageDescription (add --privacy apple-events)";
+
+#[test]
+fn usage_descriptions_use_the_default_or_the_last_given_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(
+        dir.path(),
+        &input("hello.sh"),
+        &[
+            "--privacy",
+            "camera,microphone",
+            "--privacy-text",
+            "camera=Hello scans QR codes.",
+            "--privacy-text",
+            "location=First text.",
+            "--privacy-text",
+            "location=Hello shows the weather where you are.",
+        ],
+    );
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let info = plist::Value::from_file(dir.path().join("Hello.app/Contents/Info.plist")).unwrap();
+    let info = info.as_dictionary().unwrap();
+    let text = |key: &str| info.get(key).and_then(plist::Value::as_string);
+    assert_eq!(text("NSCameraUsageDescription"), Some("Hello scans QR codes."));
+    assert_eq!(
+        text("NSMicrophoneUsageDescription"),
+        Some("Hello needs access to the microphone.")
+    );
+    // A later --privacy-text replaces an earlier one and adds the key on its own
+    assert_eq!(
+        text("NSLocationUsageDescription"),
+        Some("Hello shows the weather where you are.")
+    );
+    assert_eq!(text("NSAppleEventsUsageDescription"), None);
+}
+
+#[test]
+fn empty_usage_description_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = build(dir.path(), &input("hello.sh"), &["--privacy-text", "camera= "]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("the usage description of camera is empty"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn script_using_osascript_warns_without_apple_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = apple_events_script(dir.path());
+    let output = build(dir.path(), &script, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(
+        stdout.contains(&format!("Warning: {}", APPLE_EVENTS_FINDING)),
+        "{}",
+        stdout
+    );
+
+    let output = build(dir.path(), &script, &["--privacy", "apple-events"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(!stdout.contains("Warning"), "{}", stdout);
+}
+
+#[test]
+fn strict_fails_on_a_missing_usage_description() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = apple_events_script(dir.path());
+    let output = build(dir.path(), &script, &["--strict"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains(&format!("Missing usage descriptions:\n  {}", APPLE_EVENTS_FINDING)),
+        "{}",
+        stderr
+    );
+
+    let output = build(dir.path(), &script, &["--strict", "--privacy", "apple-events"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,110 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+fn write_executable(path: &Path, contents: &str) {
+    fs::write(path, contents).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Stub interpreter printing how it was started, one line per argument
+const STUB: &str = "#!/bin/sh\necho \"interpreter: $(basename \"$0\")\"\nfor arg; do echo \"arg: $arg\"; done\n";
+
+/// Builds `{dir}/Tool.app` around `tool` with the given shebang
+fn build(dir: &Path, shebang: &str, extra: &[&str]) -> Output {
+    let script = dir.join("tool");
+    write_executable(&script, &format!("{}\nprint('tool')\n", shebang));
+    let mut args = vec![
+        "--executable",
+        script.to_str().unwrap(),
+        "--name",
+        "Tool",
+        "--bundle-id",
+        "com.acme.tool",
+        "--output",
+        dir.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    run(&args)
+}
+
+/// Runs the launcher of `{dir}/Tool.app`, returning its output
+fn launch(dir: &Path) -> String {
+    let output = Command::new(dir.join("Tool.app/Contents/MacOS/tool"))
+        .arg("last")
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn make_venv(dir: &Path, interpreters: &[&str]) -> String {
+    let venv = dir.join("venv");
+    fs::create_dir_all(venv.join("bin")).unwrap();
+    fs::write(venv.join("pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+    for name in interpreters {
+        write_executable(&venv.join("bin").join(name), STUB);
+    }
+    venv.to_str().unwrap().to_string()
+}
+
+#[test]
+fn venv_runs_the_interpreter_named_by_the_shebang() {
+    let dir = tempfile::tempdir().unwrap();
+    let venv = make_venv(dir.path(), &["python3", "python3.12"]);
+    let output = build(dir.path(), "#!/usr/bin/env python3.12", &["--python-venv", &venv]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = launch(dir.path());
+    assert!(stdout.starts_with("interpreter: python3.12\n"), "{}", stdout);
+}
+
+#[test]
+fn venv_without_the_shebang_interpreter_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let venv = make_venv(dir.path(), &["python3"]);
+    let output = build(dir.path(), "#!/usr/bin/env python3.11", &["--python-venv", &venv]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("has no bin/python3.11"), "{}", stderr);
+}
+
+#[test]
+fn shebang_arguments_reach_the_interpreter_literally() {
+    let dir = tempfile::tempdir().unwrap();
+    let runtime = dir.path().join("fakepy");
+    write_executable(&runtime, STUB);
+    let hostile = ["-W\"x", "$(touch${IFS}pwned)", "`touch${IFS}pwned`", "it's", "$HOME"];
+    let shebang = format!("#!/usr/bin/env -S fakepy {}", hostile.join(" "));
+    let output = build(dir.path(), &shebang, &["--runtime", runtime.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = launch(dir.path());
+    let args: Vec<&str> = stdout.lines().filter_map(|line| line.strip_prefix("arg: ")).collect();
+    let script = dir.path().join("Tool.app/Contents/MacOS/tool_script");
+    let mut expected: Vec<&str> = hostile.to_vec();
+    expected.push(script.to_str().unwrap());
+    expected.push("last");
+    assert_eq!(args.len(), expected.len(), "{}", stdout);
+    assert_eq!(args[..hostile.len()], hostile);
+    assert!(
+        Path::new(args[hostile.len()]).ends_with("MacOS/tool_script"),
+        "{}",
+        stdout
+    );
+    assert_eq!(args[hostile.len() + 1], "last");
+    assert!(
+        !dir.path().join("pwned").exists(),
+        "a shebang argument ran as shell code"
+    );
+}
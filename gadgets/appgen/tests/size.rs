@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn appgen(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/Hello.app` with two copies of data.txt, one in a subdirectory, and a different file
+fn build_app(dir: &Path, extra: &[&str]) -> PathBuf {
+    let executable = input("hello-arm64");
+    let first = format!("{}:Resources/data.txt", input("data.txt"));
+    let second = format!("{}:Resources/more/data-copy.txt", input("data.txt"));
+    let other = format!("{}:Resources/tool.sh", input("tool.sh"));
+    let mut args = vec![
+        "--executable",
+        &executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        dir.to_str().unwrap(),
+        "--additional-file",
+        &first,
+        "--additional-file",
+        &second,
+        "--additional-file",
+        &other,
+    ];
+    args.extend_from_slice(extra);
+    appgen(&args);
+    dir.join("Hello.app")
+}
+
+#[test]
+fn size_report_lists_duplicates() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = build_app(dir.path(), &[]);
+    let stdout = appgen(&["size", app.to_str().unwrap()]);
+    let size = fs::metadata(input("data.txt")).unwrap().len();
+    assert!(
+        stdout.contains(&format!(
+            "Duplicates: 1 group(s), {} B could be saved with --dedup:",
+            size
+        )),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("    Contents/Resources/more/data-copy.txt\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("    Contents/Resources/data.txt\n"), "{}", stdout);
+    assert!(!stdout.contains("tool.sh\n"), "{}", stdout);
+
+    // The report leaves the bundle as it is
+    let copy = app.join("Contents/Resources/more/data-copy.txt");
+    assert!(fs::symlink_metadata(copy).unwrap().is_file());
+}
+
+#[test]
+fn dedup_symlink_points_at_the_first_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = build_app(dir.path(), &["--dedup", "symlink"]);
+    let copy = app.join("Contents/Resources/more/data-copy.txt");
+    assert!(fs::symlink_metadata(&copy).unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_link(&copy).unwrap(), Path::new("../data.txt"));
+    assert_eq!(fs::read(&copy).unwrap(), fs::read(input("data.txt")).unwrap());
+
+    let stdout = appgen(&["size", app.to_str().unwrap()]);
+    assert!(stdout.contains("No duplicate files"), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn dedup_hard_link_shares_the_inode() {
+    use std::os::unix::fs::MetadataExt;
+    let dir = tempfile::tempdir().unwrap();
+    let app = build_app(dir.path(), &["--dedup", "hard-link"]);
+    let original = fs::metadata(app.join("Contents/Resources/data.txt")).unwrap();
+    let copy = fs::symlink_metadata(app.join("Contents/Resources/more/data-copy.txt")).unwrap();
+    assert!(copy.is_file());
+    assert_eq!((copy.dev(), copy.ino()), (original.dev(), original.ino()));
+
+    let stdout = appgen(&["size", app.to_str().unwrap()]);
+    assert!(stdout.contains("No duplicate files"), "{}", stdout);
+}
+
+#[test]
+fn deduplicated_bundle_signs_and_verifies() {
+    for mode in ["symlink", "hard-link"] {
+        let dir = tempfile::tempdir().unwrap();
+        let app = build_app(dir.path(), &["--dedup", mode, "--sign"]);
+        let stdout = appgen(&["verify", app.to_str().unwrap()]);
+        assert!(stdout.contains("valid on disk"), "{}: {}", mode, stdout);
+    }
+}
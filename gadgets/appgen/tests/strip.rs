@@ -0,0 +1,197 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// UUID of the fixture, the key of its entry in the symbols archive
+const UUID: &str = "10111213-1415-1617-1819-1A1B1C1D1E1F";
+
+fn appgen(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen");
+    assert!(
+        output.status.success(),
+        "appgen {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// An arm64 executable with local, STABS, exported and undefined symbols, indirect symbols, an
+/// LC_UUID and a `__DWARF` segment
+fn fixture() -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "strip",
+        "symbols-arm64",
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/Sym.app` with `--strip`, returning the output and the path of its executable
+fn build_stripped_app(dir: &Path, executable: &str, extra: &[&str]) -> (String, PathBuf) {
+    let mut args = vec![
+        "--executable",
+        executable,
+        "--name",
+        "Sym",
+        "--bundle-id",
+        "com.acme.sym",
+        "--output",
+        dir.to_str().unwrap(),
+        "--strip",
+    ];
+    args.extend_from_slice(extra);
+    let stdout = appgen(&args);
+    (stdout, dir.join("Sym.app/Contents/MacOS/symbols-arm64"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+fn u64_at(data: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+fn c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap();
+    String::from_utf8(data[..end].to_vec()).unwrap()
+}
+
+/// What the test looks at in a thin 64-bit Mach-O
+#[derive(Debug, Default)]
+struct Image {
+    /// Segment names with their file offset and size
+    segments: Vec<(String, usize, usize)>,
+    /// Symbol names and types
+    symbols: Vec<(String, u8)>,
+    /// (first index, count) of the local, defined external and undefined symbols
+    dysymtab: Vec<(usize, usize)>,
+    indirect: Vec<usize>,
+    has_signature: bool,
+}
+
+fn parse(data: &[u8]) -> Image {
+    assert_eq!(u32_at(data, 0), 0xfeed_facf);
+    let mut image = Image::default();
+    let mut offset = 32;
+    let end = offset + u32_at(data, 20);
+    for _ in 0..u32_at(data, 16) {
+        let (cmd, size) = (u32_at(data, offset), u32_at(data, offset + 4));
+        match cmd {
+            0x19 => image.segments.push((
+                c_string(&data[offset + 8..offset + 24]),
+                u64_at(data, offset + 40),
+                u64_at(data, offset + 48),
+            )),
+            0x2 => {
+                let (symoff, nsyms) = (u32_at(data, offset + 8), u32_at(data, offset + 12));
+                let (stroff, strsize) = (u32_at(data, offset + 16), u32_at(data, offset + 20));
+                assert!(stroff + strsize <= data.len(), "string table out of bounds");
+                for i in 0..nsyms {
+                    let entry = &data[symoff + 16 * i..symoff + 16 * (i + 1)];
+                    let name = c_string(&data[stroff + u32_at(entry, 0)..stroff + strsize]);
+                    image.symbols.push((name, entry[4]));
+                }
+            }
+            0xb => {
+                image.dysymtab = [8, 16, 24]
+                    .iter()
+                    .map(|field| (u32_at(data, offset + field), u32_at(data, offset + field + 4)))
+                    .collect();
+                let (indirectoff, count) = (u32_at(data, offset + 56), u32_at(data, offset + 60));
+                image.indirect = (0..count).map(|i| u32_at(data, indirectoff + 4 * i)).collect();
+            }
+            0x1d => image.has_signature = true,
+            _ => {}
+        }
+        offset += size;
+    }
+    assert_eq!(offset, end, "load commands do not add up to sizeofcmds");
+    image
+}
+
+/// Names the indirect symbol table entries refer to
+fn indirect_names(image: &Image) -> Vec<String> {
+    image
+        .indirect
+        .iter()
+        .map(|&index| match index {
+            0x8000_0000 => "LOCAL".to_string(),
+            index => image.symbols[index].0.clone(),
+        })
+        .collect()
+}
+
+#[test]
+fn strip_keeps_external_symbols_and_archives_the_original() {
+    let original = fs::read(fixture()).unwrap();
+    let before = parse(&original);
+    assert_eq!(before.symbols.len(), 6);
+    assert!(before.segments.iter().any(|(name, _, _)| name == "__DWARF"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let (stdout, executable) = build_stripped_app(dir.path(), &fixture(), &[]);
+    let stripped = fs::read(&executable).unwrap();
+    assert!(
+        stdout.contains("Stripped MacOS/symbols-arm64: 3.2 KB -> 2.2 KB"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Saved 1.1 KB by stripping 1 file(s)"), "{}", stdout);
+
+    let after = parse(&stripped);
+    let segments: Vec<&str> = after.segments.iter().map(|(name, _, _)| name.as_str()).collect();
+    assert_eq!(segments, ["__PAGEZERO", "__TEXT", "__LINKEDIT"]);
+    let (_, linkedit_offset, linkedit_size) = after.segments[2];
+    assert_eq!(linkedit_offset + linkedit_size, stripped.len());
+    assert_eq!(
+        after.symbols,
+        [
+            ("_main".to_string(), 0x0f),
+            ("_exported".to_string(), 0x0f),
+            ("_puts".to_string(), 0x01),
+            ("_exit".to_string(), 0x01),
+        ]
+    );
+    assert_eq!(after.dysymtab, [(0, 0), (0, 2), (2, 2)]);
+    assert_eq!(indirect_names(&after), indirect_names(&before));
+    assert_eq!(indirect_names(&after), ["_exit", "_puts", "LOCAL", "_exported"]);
+    // __text is untouched
+    assert_eq!(stripped[0x400..0x40c], original[0x400..0x40c]);
+
+    let archive = fs::File::open(dir.path().join("Sym.symbols.zip")).unwrap();
+    let mut archive = zip::ZipArchive::new(archive).unwrap();
+    assert_eq!(archive.len(), 1);
+    let mut entry = archive.by_name(&format!("{}/symbols-arm64", UUID)).unwrap();
+    let mut archived = Vec::new();
+    entry.read_to_end(&mut archived).unwrap();
+    assert_eq!(archived, original);
+}
+
+#[test]
+fn stripped_executable_signs_and_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, executable) = build_stripped_app(dir.path(), &fixture(), &["--sign"]);
+    assert!(parse(&fs::read(executable).unwrap()).has_signature);
+    let app = dir.path().join("Sym.app");
+    let stdout = appgen(&["verify", app.to_str().unwrap()]);
+    assert!(stdout.contains("valid on disk"), "{}", stdout);
+}
+
+#[test]
+fn stripping_twice_finds_nothing_left() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, executable) = build_stripped_app(dir.path(), &fixture(), &[]);
+    let again = tempfile::tempdir().unwrap();
+    let (stdout, _) = build_stripped_app(again.path(), executable.to_str().unwrap(), &[]);
+    assert!(stdout.contains("No symbols to strip in Contents/MacOS"), "{}", stdout);
+}
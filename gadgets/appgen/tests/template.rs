@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(args)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// Writes `{dir}/appgen.toml` building Menu.app from hello.sh with the given template and extra keys
+fn write_manifest(dir: &Path, template: &str, extra: &str) -> String {
+    let manifest = dir.join("appgen.toml");
+    let text = format!(
+        "executable = {:?}\nname = \"Menu\"\nbundle-id = \"com.acme.menu\"\ntemplate = {:?}\n{}",
+        input("hello.sh"),
+        template,
+        extra
+    );
+    fs::write(&manifest, text).unwrap();
+    manifest.to_str().unwrap().to_string()
+}
+
+/// Builds `{dir}/out/Menu.app` from the manifest, returning its Info.plist
+fn build(dir: &Path, manifest: &str, extra: &[&str]) -> plist::Dictionary {
+    let output_dir = dir.join("out");
+    let mut args = vec!["--manifest", manifest, "--output", output_dir.to_str().unwrap()];
+    args.extend_from_slice(extra);
+    let output = run(&args);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    plist::Value::from_file(output_dir.join("Menu.app/Contents/Info.plist"))
+        .unwrap()
+        .into_dictionary()
+        .unwrap()
+}
+
+#[test]
+fn manifest_overrides_the_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(
+        dir.path(),
+        "menubar-agent",
+        "category = \"public.app-category.games\"\n",
+    );
+    let info = build(dir.path(), &manifest, &[]);
+    // The manifest value wins over the template's public.app-category.utilities
+    assert_eq!(
+        info["LSApplicationCategoryType"].as_string(),
+        Some("public.app-category.games")
+    );
+    // while the options the manifest leaves alone come from the template
+    assert_eq!(info["LSUIElement"].as_boolean(), Some(true));
+    assert_eq!(info["JVFApplicationSingleInstanceModeEnabled"].as_boolean(), Some(true));
+    assert!(
+        dir.path()
+            .join("out/Menu.app/Contents/MacOS/hello.sh_original")
+            .exists()
+    );
+}
+
+#[test]
+fn command_line_overrides_the_manifest_and_the_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(
+        dir.path(),
+        "menubar-agent",
+        "category = \"public.app-category.games\"\n",
+    );
+    let info = build(
+        dir.path(),
+        &manifest,
+        &["--category", "public.app-category.finance", "--single-instance=false"],
+    );
+    assert_eq!(
+        info["LSApplicationCategoryType"].as_string(),
+        Some("public.app-category.finance")
+    );
+    assert_eq!(info["LSUIElement"].as_boolean(), Some(true));
+    assert!(!info.contains_key("JVFApplicationSingleInstanceModeEnabled"));
+    assert!(
+        !dir.path()
+            .join("out/Menu.app/Contents/MacOS/hello.sh_original")
+            .exists()
+    );
+}
+
+#[test]
+fn unknown_template_in_the_manifest_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_manifest(dir.path(), "menubar-agnt", "");
+    let output_dir = dir.path().join("out");
+    let output = run(&["--manifest", &manifest, "--output", output_dir.to_str().unwrap()]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(2), "{}", stderr);
+    assert!(
+        stderr.contains("invalid value 'menubar-agnt' for '--template <TEMPLATE>'"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("[possible values: menubar-agent, background-daemon, cli-with-terminal, document-viewer]"),
+        "{}",
+        stderr
+    );
+    assert!(!output_dir.exists());
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use git2::{Oid, Repository, Signature};
+
+/// Build number variables appgen prefers over the commit count
+const CI_BUILD_NUMBERS: &[&str] = &[
+    "GITHUB_RUN_NUMBER",
+    "CI_PIPELINE_IID",
+    "BUILDKITE_BUILD_NUMBER",
+    "CIRCLE_BUILD_NUM",
+    "TRAVIS_BUILD_NUMBER",
+    "BUILD_NUMBER",
+];
+
+/// Runs appgen in `cwd` with no CI build number set
+fn run(cwd: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_appgen"));
+    for variable in CI_BUILD_NUMBERS {
+        command.env_remove(variable);
+    }
+    command
+        .args(args)
+        .envs(env.iter().copied())
+        .current_dir(cwd)
+        .output()
+        .expect("failed to run appgen")
+}
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+fn commit(repo: &Repository, update_ref: Option<&str>, parents: &[Oid], message: &str) -> Oid {
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let parents: Vec<_> = parents.iter().map(|id| repo.find_commit(*id).unwrap()).collect();
+    let parents: Vec<_> = parents.iter().collect();
+    repo.commit(update_ref, &signature, &signature, message, &tree, &parents)
+        .unwrap()
+}
+
+fn tag(repo: &Repository, name: &str, id: Oid) {
+    repo.tag_lightweight(name, &repo.find_object(id, None).unwrap(), false)
+        .unwrap();
+}
+
+/// Creates a repository in `dir` with three commits on HEAD, tagged v1.9.3, v1.10.0 and
+/// v2.0.0-rc1, and a v3.0.0 tag on a commit HEAD does not reach
+fn make_repo(dir: &Path) {
+    let repo = Repository::init(dir).unwrap();
+    let first = commit(&repo, Some("HEAD"), &[], "first");
+    let second = commit(&repo, Some("HEAD"), &[first], "second");
+    let third = commit(&repo, Some("HEAD"), &[second], "third");
+    let side = commit(&repo, None, &[first], "side");
+    tag(&repo, "v1.9.3", second);
+    tag(&repo, "v1.10.0", third);
+    tag(&repo, "v2.0.0-rc1", third);
+    tag(&repo, "v3.0.0", side);
+}
+
+/// CFBundleShortVersionString and CFBundleVersion of `{dir}/Hello.app`
+fn versions(dir: &Path) -> (String, String) {
+    let info = plist::Value::from_file(dir.join("Hello.app/Contents/Info.plist"))
+        .unwrap()
+        .into_dictionary()
+        .unwrap();
+    let get = |key: &str| info[key].as_string().unwrap().to_string();
+    (get("CFBundleShortVersionString"), get("CFBundleVersion"))
+}
+
+fn build_args<'a>(executable: &'a str, output: &'a str) -> Vec<&'a str> {
+    vec![
+        "--executable",
+        executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        output,
+        "--build-number",
+        "auto",
+    ]
+}
+
+#[test]
+fn versions_come_from_the_executable_repository() {
+    let repo = tempfile::tempdir().unwrap();
+    make_repo(repo.path());
+    let executable = repo.path().join("hello.sh");
+    fs::copy(input("hello.sh"), &executable).unwrap();
+    let output = tempfile::tempdir().unwrap();
+
+    // appgen runs outside the repository
+    let mut args = build_args(executable.to_str().unwrap(), output.path().to_str().unwrap());
+    args.extend(["--app-version", "from-git"]);
+    let result = run(output.path(), &args, &[]);
+    let stdout = String::from_utf8(result.stdout).unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(stdout.contains("Using version 1.10.0 from git tags"), "{}", stdout);
+    assert!(
+        stdout.contains("Using build number 3 from the commit count"),
+        "{}",
+        stdout
+    );
+    assert_eq!(versions(output.path()), ("1.10.0".to_string(), "3".to_string()));
+}
+
+#[test]
+fn versions_come_from_the_manifest_repository() {
+    let repo = tempfile::tempdir().unwrap();
+    make_repo(repo.path());
+    let output = tempfile::tempdir().unwrap();
+    let manifest = repo.path().join("appgen.toml");
+    let executable = input("hello.sh");
+    let mut args = build_args(&executable, output.path().to_str().unwrap());
+    fs::write(&manifest, "app-version = \"from-git\"\n").unwrap();
+    args.extend(["--manifest", manifest.to_str().unwrap()]);
+
+    let result = run(output.path(), &args, &[("BUILD_NUMBER", "42")]);
+    let stdout = String::from_utf8(result.stdout).unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(stdout.contains("Using build number 42 from BUILD_NUMBER"), "{}", stdout);
+    assert_eq!(versions(output.path()), ("1.10.0".to_string(), "42".to_string()));
+}
+
+#[test]
+fn from_git_outside_a_repository_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let executable = dir.path().join("hello.sh");
+    fs::copy(input("hello.sh"), &executable).unwrap();
+    let args = build_args(executable.to_str().unwrap(), dir.path().to_str().unwrap());
+    let result = run(dir.path(), &args, &[]);
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(!result.status.success());
+    assert!(
+        stderr.contains("--app-version from-git and --build-number auto need a git repository"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Path of an input of the bundle snapshot tests
+fn input(name: &str) -> String {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "bundles",
+        "inputs",
+        name,
+    ]
+    .iter()
+    .collect();
+    path.to_str().unwrap().to_string()
+}
+
+/// `appgen --watch` running in the background, stopped when dropped
+struct Watch {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Watch {
+    fn start(args: &[&str]) -> Watch {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_appgen"))
+            .args(args)
+            .arg("--watch")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("failed to run appgen");
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Watch { child, lines }
+    }
+
+    /// Waits for a line of output starting with `prefix`
+    fn wait_for(&self, prefix: &str) {
+        loop {
+            match self.lines.recv_timeout(Duration::from_secs(20)) {
+                Ok(line) if line.starts_with(prefix) => return,
+                Ok(_) => {}
+                Err(e) => panic!("no output starting with {:?}: {}", prefix, e),
+            }
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn changed_directory_replaces_only_its_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = dir.path().join("assets");
+    fs::create_dir(&assets).unwrap();
+    fs::write(assets.join("old.txt"), "old\n").unwrap();
+    let output = dir.path().join("out");
+    let assets_entry = format!("{}:Resources", assets.display());
+    let data_entry = format!("{}:Resources/data.txt", input("data.txt"));
+    let executable = input("hello.sh");
+    let icon = input("icon.png");
+
+    let watch = Watch::start(&[
+        "--executable",
+        &executable,
+        "--name",
+        "Hello",
+        "--bundle-id",
+        "com.acme.hello",
+        "--output",
+        output.to_str().unwrap(),
+        "--icon",
+        &icon,
+        "--additional-file",
+        &assets_entry,
+        "--additional-file",
+        &data_entry,
+    ]);
+    watch.wait_for("Watching");
+
+    let contents = output.join("Hello.app/Contents");
+    // A directory is copied into the parent of its target under its own name
+    let copy = contents.join("assets");
+    assert!(copy.join("old.txt").is_file());
+
+    fs::remove_file(assets.join("old.txt")).unwrap();
+    fs::write(assets.join("new.txt"), "new\n").unwrap();
+    watch.wait_for("Updated");
+
+    assert_eq!(fs::read_to_string(copy.join("new.txt")).unwrap(), "new\n");
+    assert!(!copy.join("old.txt").exists(), "removed files linger in the copy");
+    // Everything else in the bundle is left alone
+    let survivors = [
+        "Resources/data.txt",
+        "Resources/icon.icns",
+        "Info.plist",
+        "MacOS/hello.sh",
+    ];
+    for path in survivors {
+        assert!(contents.join(path).is_file(), "{} was removed", path);
+    }
+    assert!(Path::new(&contents).join("Resources").is_dir());
+}
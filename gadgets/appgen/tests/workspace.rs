@@ -0,0 +1,46 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn failing_app_does_not_stop_the_others() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("good.sh"), "#!/bin/sh\necho good\n").unwrap();
+    // `..` has no file name, the Broken app fails and the others still build
+    let manifest = dir.path().join("apps.toml");
+    fs::write(
+        &manifest,
+        r#"bundle-id-prefix = "com.acme"
+output = "dist"
+
+[[app]]
+name = "Good"
+executable = "good.sh"
+
+[[app]]
+name = "Broken"
+executable = ".."
+
+[[app]]
+name = "Also Good"
+executable = "good.sh"
+"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_appgen"))
+        .args(["--manifest", manifest.to_str().unwrap()])
+        .output()
+        .expect("failed to run appgen");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}{}", stdout, stderr);
+    assert!(stderr.contains("1 of 3 app(s) failed to build"), "{}", stderr);
+
+    let dist = dir.path().join("dist");
+    assert!(dist.join("Good.app/Contents/MacOS/good.sh").is_file());
+    assert!(dist.join("Also Good.app/Contents/MacOS/good.sh").is_file());
+    let broken = stdout.lines().find(|line| line.starts_with("Broken")).unwrap();
+    assert!(broken.contains("FAILED"), "{}", stdout);
+    assert!(broken.contains("Invalid executable path: "), "{}", stdout);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}